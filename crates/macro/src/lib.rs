@@ -301,6 +301,17 @@ fn create_types(attrs: &[syn::Attribute], variants: &[WalrusVariant]) -> impl qu
         })
         .collect();
 
+    let names: Vec<_> = variants
+        .iter()
+        .map(|v| {
+            let name = &v.syn.ident;
+            let snake_name = name.to_string().to_snake_case();
+            quote! {
+                Instr::#name(_) => #snake_name,
+            }
+        })
+        .collect();
+
     let variants: Vec<_> = variants
         .iter()
         .map(|v| {
@@ -323,6 +334,14 @@ fn create_types(attrs: &[syn::Attribute], variants: &[WalrusVariant]) -> impl qu
 
         impl Instr {
             #( #methods )*
+
+            /// Get the name of this kind of instruction, for example
+            /// `"local_get"` for a `LocalGet`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #( #names )*
+                }
+            }
         }
     }
 }
@@ -507,7 +526,8 @@ fn create_visit(variants: &[WalrusVariant]) -> impl quote::ToTokens {
                 // ...
             }
 
-            /// Visit `Instr`.
+            /// Visit `Instr`, along with the `InstrLocId` recording where it
+            /// came from in the original wasm binary.
             #[inline]
             fn visit_instr(&mut self, instr: &'instr Instr, instr_loc: &'instr InstrLocId) {
                 // ...
@@ -597,7 +617,8 @@ fn create_visit(variants: &[WalrusVariant]) -> impl quote::ToTokens {
                 // ...
             }
 
-            /// Visit `Instr`.
+            /// Visit `Instr`, along with the `InstrLocId` recording where it
+            /// came from in the original wasm binary.
             #[inline]
             fn visit_instr_mut(&mut self, instr: &mut Instr, instr_loc: &mut InstrLocId) {
                 // ...
@@ -62,6 +62,8 @@
 //! Note that this is somewhat experimental, so it's recommended to make liberal
 //! use of git to prevent destructive edits.
 
+pub mod testutils;
+
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
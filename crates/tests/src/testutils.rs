@@ -0,0 +1,85 @@
+//! Assertions shared by the integration tests.
+
+use walrus::ir::{Instr, InstrSeqId};
+use walrus::{FunctionId, Module, ModuleConfig};
+
+/// Parse and re-emit `wasm`, and assert that walrus's output isn't
+/// substantially larger than its input.
+///
+/// This catches encoding bloat, such as non-minimal LEB128s or block types.
+/// The producers section walrus adds is skipped, and walrus always encodes
+/// the sizes of sections, function bodies, and name subsections as padded
+/// 5-byte LEB128s, so each of those may grow by up to 4 bytes. Beyond that the
+/// output may only be 5% larger than the input.
+pub fn assert_reemit_no_larger(wasm: &[u8]) {
+    let mut config = ModuleConfig::new();
+    config.generate_producers_section(false);
+    let mut module = config.parse(wasm).expect("should parse the module");
+    let funcs = module.funcs.iter_local().count();
+    let emitted = module.emit_wasm();
+
+    let padded_sizes = sections(&emitted) + funcs + 3;
+    let tolerance = 4 * padded_sizes + wasm.len() / 20;
+    assert!(
+        emitted.len() <= wasm.len() + tolerance,
+        "re-emitting grew the module from {} to {} bytes, more than the allowed {}",
+        wasm.len(),
+        emitted.len(),
+        tolerance
+    );
+}
+
+/// Count the sections in an encoded module.
+fn sections(wasm: &[u8]) -> usize {
+    let mut pos = 8;
+    let mut count = 0;
+    while pos < wasm.len() {
+        // Skip the section's id, then its size and contents.
+        pos += 1;
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = wasm[pos];
+            pos += 1;
+            size |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        pos += size;
+        count += 1;
+    }
+    count
+}
+
+/// The instructions in the sequence `seq` of the local function `func`.
+pub fn block_instrs(module: &Module, func: FunctionId, seq: InstrSeqId) -> Vec<Instr> {
+    let func = module.funcs.get(func).kind.unwrap_local();
+    func.block(seq)
+        .iter()
+        .map(|(instr, _)| instr.clone())
+        .collect()
+}
+
+/// The names of the instructions in the sequence `seq` of the local function
+/// `func`.
+pub fn block_instr_names(module: &Module, func: FunctionId, seq: InstrSeqId) -> Vec<&'static str> {
+    block_instrs(module, func, seq)
+        .iter()
+        .map(|instr| instr.name())
+        .collect()
+}
+
+/// The instructions in the entry block of the local function `func`.
+pub fn entry_instrs(module: &Module, func: FunctionId) -> Vec<Instr> {
+    let entry = module.funcs.get(func).kind.unwrap_local().entry_block();
+    block_instrs(module, func, entry)
+}
+
+/// The names of the instructions in the entry block of the local function
+/// `func`.
+pub fn entry_instr_names(module: &Module, func: FunctionId) -> Vec<&'static str> {
+    let entry = module.funcs.get(func).kind.unwrap_local().entry_block();
+    block_instr_names(module, func, entry)
+}
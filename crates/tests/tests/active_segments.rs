@@ -0,0 +1,54 @@
+//! Tests for `Module::data_segments_for` and `Module::element_segments_for`.
+
+use walrus::ModuleConfig;
+
+#[test]
+fn segments_by_target() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory $a 1)
+                (memory $b 1)
+                (memory $c 1)
+                (data (memory $a) (i32.const 0) "a0")
+                (data (memory $b) (i32.const 0) "b0")
+                (data "passive")
+                (data (memory $a) (i32.const 8) "a1")
+
+                (table $t 2 funcref)
+                (table $u 2 funcref)
+                (elem (table $u) (i32.const 0) func $f)
+                (elem func $f)
+                (elem (table $t) (i32.const 1) func $f $f)
+                (elem declare func $f)
+                (func $f))
+        "#,
+    )?;
+    let mut config = ModuleConfig::new();
+    config.only_stable_features(false);
+    let module = config.parse(&wasm)?;
+
+    let memories = module.memories.iter().map(|m| m.id()).collect::<Vec<_>>();
+    let data = |memory| {
+        module
+            .data_segments_for(memory)
+            .into_iter()
+            .map(|d| module.data.get(d).value.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(data(memories[0]), [b"a0".to_vec(), b"a1".to_vec()]);
+    assert_eq!(data(memories[1]), [b"b0".to_vec()]);
+    assert!(data(memories[2]).is_empty());
+
+    let tables = module.tables.iter().map(|t| t.id()).collect::<Vec<_>>();
+    let elements = |table| {
+        module
+            .element_segments_for(table)
+            .into_iter()
+            .map(|e| module.elements.get(e).members.len())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(elements(tables[0]), [2]);
+    assert_eq!(elements(tables[1]), [1]);
+    Ok(())
+}
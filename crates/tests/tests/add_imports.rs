@@ -0,0 +1,58 @@
+//! Tests for the `Module::add_import_*` helpers.
+
+use walrus::{FunctionBuilder, ImportKind, Module, ValType};
+
+#[test]
+fn import_and_call_function() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    let ty = module.types.add(&[ValType::I32], &[ValType::I32]);
+    let (log, import) = module.add_import_func("env", "log", ty);
+    assert_eq!(module.imports.get(import).kind, ImportKind::Function(log));
+    assert_eq!(module.funcs.get(log).ty(), ty);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().i32_const(42).call(log);
+    let main = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("main", main);
+
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    let import = module.imports.find("env", "log").unwrap();
+    let log = match module.imports.get(import).kind {
+        ImportKind::Function(f) => f,
+        ref other => panic!("unexpected import: {:?}", other),
+    };
+    let main = module.exports.iter().find(|e| e.name == "main").unwrap();
+    let main = match main.item {
+        walrus::ExportItem::Function(f) => module.funcs.get(f).kind.unwrap_local(),
+        ref other => panic!("unexpected export: {:?}", other),
+    };
+    let calls = main.fold(0, |n, instr| match instr {
+        walrus::ir::Instr::Call(c) if c.func == log => n + 1,
+        _ => n,
+    });
+    assert_eq!(calls, 1);
+    Ok(())
+}
+
+#[test]
+fn import_global_memory_and_table() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    let (global, _) = module.add_import_global("env", "g", ValType::I64, true);
+    let (memory, _) = module.add_import_memory("env", "m", false, 1, Some(2));
+    let (table, _) = module.add_import_table("env", "t", 3, None, ValType::Funcref);
+    assert!(module.globals.get(global).mutable);
+    assert_eq!(module.memories.get(memory).maximum, Some(2));
+    assert_eq!(module.tables.get(table).initial, 3);
+
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    let kind = |name| {
+        &module
+            .imports
+            .get(module.imports.find("env", name).unwrap())
+            .kind
+    };
+    assert!(matches!(kind("g"), ImportKind::Global(_)));
+    assert!(matches!(kind("m"), ImportKind::Memory(_)));
+    assert!(matches!(kind("t"), ImportKind::Table(_)));
+    Ok(())
+}
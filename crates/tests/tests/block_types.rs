@@ -0,0 +1,109 @@
+//! Tests for the binary encoding of block types.
+
+use walrus::ir::InstrSeqType;
+use walrus::{FunctionBuilder, Module, ValType};
+
+/// Emit `module` and assert that the result contains `bytes`.
+fn assert_emits(module: &mut Module, bytes: &[u8]) -> Vec<u8> {
+    let wasm = module.emit_wasm();
+    assert!(
+        wasm.windows(bytes.len()).any(|w| w == bytes),
+        "expected {:x?} in emitted wasm {:x?}",
+        bytes,
+        wasm
+    );
+    wasm
+}
+
+/// Re-parse `wasm` and return the type of the first block in its only
+/// function.
+fn parsed_block_type(wasm: &[u8]) -> (Module, InstrSeqType) {
+    let module = Module::from_buffer(wasm).unwrap();
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    let ty = func
+        .block(func.entry_block())
+        .iter()
+        .find_map(|(instr, _)| match instr {
+            walrus::ir::Instr::Block(b) => Some(func.block(b.seq).ty),
+            _ => None,
+        })
+        .unwrap();
+    (module, ty)
+}
+
+#[test]
+fn empty_block_type() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().block(None, |block| {
+        block.i32_const(7).drop();
+    });
+    builder.finish(vec![], &mut module.funcs);
+
+    let wasm = assert_emits(&mut module, &[0x02, 0x40, 0x41, 0x07, 0x1a, 0x0b]);
+    let (_, ty) = parsed_block_type(&wasm);
+    assert_eq!(ty, InstrSeqType::Simple(None));
+}
+
+#[test]
+fn single_result_block_type() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .block(ValType::I32, |block| {
+            block.i32_const(7);
+        })
+        .drop();
+    builder.finish(vec![], &mut module.funcs);
+
+    let wasm = assert_emits(&mut module, &[0x02, 0x7f, 0x41, 0x07, 0x0b, 0x1a]);
+    let (_, ty) = parsed_block_type(&wasm);
+    assert_eq!(ty, InstrSeqType::Simple(Some(ValType::I32)));
+}
+
+#[test]
+fn simple_signature_as_multi_value_uses_short_encoding() {
+    let mut module = Module::default();
+    let ty = InstrSeqType::MultiValue(module.types.add(&[], &[ValType::F64]));
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .block(ty, |block| {
+            block.f64_const(0.0);
+        })
+        .drop();
+    builder.finish(vec![], &mut module.funcs);
+
+    let wasm = assert_emits(&mut module, &[0x02, 0x7c, 0x44]);
+    let (_, ty) = parsed_block_type(&wasm);
+    assert_eq!(ty, InstrSeqType::Simple(Some(ValType::F64)));
+}
+
+#[test]
+fn type_index_block_type() {
+    let mut module = Module::default();
+
+    // Add enough types that sort before the block's type so that its index
+    // needs a second byte in signed LEB128, but not in unsigned LEB128.
+    for n in 1..=70 {
+        module.types.add(&vec![ValType::I32; n], &[]);
+    }
+    let ty = module.types.add(&[ValType::I64], &[ValType::I64]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().i64_const(7).block(ty, |_| {}).drop();
+    builder.finish(vec![], &mut module.funcs);
+
+    // The function type `[] -> []` is index 0, then the 70 `i32` types, so
+    // the block's type is index 71.
+    let wasm = assert_emits(&mut module, &[0x42, 0x07, 0x02, 0xc7, 0x00, 0x0b, 0x1a]);
+    let (module, ty) = parsed_block_type(&wasm);
+    match ty {
+        InstrSeqType::MultiValue(ty) => {
+            assert_eq!(module.types.params(ty), [ValType::I64]);
+            assert_eq!(module.types.results(ty), [ValType::I64]);
+        }
+        other => panic!("expected a multi-value block type, found {:?}", other),
+    }
+}
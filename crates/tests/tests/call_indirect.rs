@@ -0,0 +1,137 @@
+//! Tests for `call_indirect`.
+//!
+//! The MVP encoding's reserved `0x00` byte and the reference types encoding's
+//! table index are the same LEB128 immediate, so a `call_indirect` of table 0
+//! is encoded identically whether or not the text format named its table.
+
+use walrus::ir::Instr;
+use walrus::{Module, ValType};
+
+/// Parse `wat`, check that its only function's `call_indirect` uses the table
+/// at `table`, and that the emitted code for it is `expected`.
+fn round_trip(wat: &str, table: usize, expected: &[u8]) {
+    let wasm = wat::parse_str(wat).unwrap();
+    assert!(wasm.windows(expected.len()).any(|w| w == expected));
+
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let table = module.tables.iter().nth(table).unwrap().id();
+    {
+        let (_, func) = module.funcs.iter_local().next().unwrap();
+        match &func.block(func.entry_block())[1].0 {
+            Instr::CallIndirect(call) => assert_eq!(call.table, table),
+            other => panic!("expected a call_indirect, found {:?}", other),
+        }
+    }
+
+    let emitted = module.emit_wasm();
+    assert!(
+        emitted.windows(expected.len()).any(|w| w == expected),
+        "expected {:x?} in {:x?}",
+        expected,
+        emitted
+    );
+}
+
+#[test]
+fn implicit_table() {
+    round_trip(
+        r#"
+        (module
+          (type $t (func))
+          (table 1 funcref)
+          (func
+            i32.const 0
+            call_indirect (type $t)))
+        "#,
+        0,
+        &[0x41, 0x00, 0x11, 0x00, 0x00, 0x0b],
+    );
+}
+
+#[test]
+fn explicit_table_0() {
+    round_trip(
+        r#"
+        (module
+          (type $t (func))
+          (table $a 1 funcref)
+          (func
+            i32.const 0
+            call_indirect $a (type $t)))
+        "#,
+        0,
+        &[0x41, 0x00, 0x11, 0x00, 0x00, 0x0b],
+    );
+}
+
+#[test]
+fn explicit_table_1() {
+    round_trip(
+        r#"
+        (module
+          (type $t (func))
+          (table $a 1 funcref)
+          (table $b 1 funcref)
+          (func
+            i32.const 0
+            call_indirect $b (type $t)))
+        "#,
+        1,
+        &[0x41, 0x00, 0x11, 0x00, 0x01, 0x0b],
+    );
+}
+
+#[test]
+fn find_and_rewrite_indirect_calls() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+        (module
+          (type $i32 (func (param i32) (result i32)))
+          (type $f32 (func (param f32) (result f32)))
+          (table 1 funcref)
+          (func $a (param i32) (result i32)
+            local.get 0
+            i32.const 0
+            call_indirect (type $i32))
+          (func $b (param f32) (result f32)
+            local.get 0
+            i32.const 0
+            call_indirect (type $f32))
+          (func $c (param i32) (result i32)
+            (block (result i32)
+              local.get 0
+              i32.const 0
+              call_indirect (type $i32))))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    let i32_ty = module.types.find(&[ValType::I32], &[ValType::I32]).unwrap();
+    let f32_ty = module.types.find(&[ValType::F32], &[ValType::F32]).unwrap();
+
+    let calls = module.find_indirect_calls(i32_ty);
+    assert_eq!(calls.len(), 2);
+    let a = module.funcs.by_name("a").unwrap();
+    let c = module.funcs.by_name("c").unwrap();
+    assert!(calls.iter().any(|(f, _, i)| *f == a && *i == 2));
+    assert!(calls.iter().any(|(f, _, i)| *f == c && *i == 2));
+    for (func, seq, i) in calls {
+        let func = module.funcs.get(func).kind.unwrap_local();
+        match &func.block(seq)[i].0 {
+            Instr::CallIndirect(call) => assert_eq!(call.ty, i32_ty),
+            other => panic!("expected a call_indirect, found {:?}", other),
+        }
+    }
+    assert_eq!(module.find_indirect_calls(f32_ty).len(), 1);
+
+    // Changing the number of parameters is rejected.
+    let two = module
+        .types
+        .add(&[ValType::I32, ValType::I32], &[ValType::I32]);
+    assert!(module.rewrite_indirect_calls(i32_ty, two).is_err());
+    assert_eq!(module.find_indirect_calls(i32_ty).len(), 2);
+
+    assert_eq!(module.rewrite_indirect_calls(f32_ty, i32_ty)?, 1);
+    assert_eq!(module.find_indirect_calls(i32_ty).len(), 3);
+    assert!(module.find_indirect_calls(f32_ty).is_empty());
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Tests for deep-copying modules.
+
+use walrus::ir::{Block, Const, Instr, Value};
+use walrus::{Module, RawCustomSection};
+
+#[test]
+fn mutating_clone_does_not_affect_original() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (global (mut i32) (i32.const 0))
+              (func (export "f") (result i32)
+                (block (result i32)
+                  i32.const 1)))
+        "#,
+    )?;
+    let mut original = Module::from_buffer(&wasm)?;
+    original.customs.add(RawCustomSection {
+        name: "hello".to_string(),
+        data: vec![1, 2, 3],
+    });
+    let f = original.function_by_export("f").unwrap();
+    let global = original.globals.iter().next().unwrap().id();
+
+    let mut clone = original.clone();
+
+    // Rewrite the constant nested inside the block in the clone.
+    let func = clone.funcs.get_mut(f).kind.unwrap_local_mut();
+    let block = match &func.block(func.entry_block()).instrs[0].0 {
+        Instr::Block(Block { seq }) => *seq,
+        other => panic!("expected a block, found {:?}", other),
+    };
+    func.block_mut(block).instrs[0].0 = Instr::Const(Const {
+        value: Value::I32(2),
+    });
+    clone.globals.get_mut(global).mutable = false;
+    clone.customs.remove_raw("hello").unwrap();
+    clone.exports.iter_mut().next().unwrap().name = "g".to_string();
+
+    let func = original.funcs.get(f).kind.unwrap_local();
+    match &func.block(block).instrs[0].0 {
+        Instr::Const(Const {
+            value: Value::I32(1),
+        }) => {}
+        other => panic!("expected `i32.const 1`, found {:?}", other),
+    }
+    assert!(original.globals.get(global).mutable);
+    assert_eq!(
+        original.customs.remove_raw("hello").unwrap().data,
+        [1, 2, 3]
+    );
+    assert_eq!(original.exports.iter().next().unwrap().name, "f");
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Tests for the limits in `ModuleConfig`.
+
+use walrus::ModuleConfig;
+
+fn parse_err(config: &ModuleConfig, wat: &str) -> String {
+    let wasm = wat::parse_str(wat).unwrap();
+    format!("{:?}", config.parse(&wasm).unwrap_err())
+}
+
+#[test]
+fn max_memories() {
+    let wat = r#"
+        (module
+          (import "env" "m" (memory 1))
+          (memory 1))
+    "#;
+    let mut config = ModuleConfig::new();
+    config.max_memories(2);
+    config.parse(&wat::parse_str(wat).unwrap()).unwrap();
+
+    config.max_memories(1);
+    let err = parse_err(&config, wat);
+    assert!(err.contains("module has 2 memories, but at most 1 are allowed"));
+}
+
+#[test]
+fn max_tables() {
+    let wat = r#"
+        (module
+          (table 1 funcref)
+          (table 1 externref))
+    "#;
+    let mut config = ModuleConfig::new();
+    config.max_tables(2);
+    config.parse(&wat::parse_str(wat).unwrap()).unwrap();
+
+    config.max_tables(1);
+    let err = parse_err(&config, wat);
+    assert!(err.contains("module has 2 tables, but at most 1 are allowed"));
+}
+
+#[test]
+fn max_memory_pages() {
+    let wat = r#"
+        (module
+          (memory 17))
+    "#;
+    let mut config = ModuleConfig::new();
+    config.max_memory_pages(17);
+    config.parse(&wat::parse_str(wat).unwrap()).unwrap();
+
+    config.max_memory_pages(16);
+    let err = parse_err(&config, wat);
+    assert!(err.contains("memory has an initial size of 17 pages, but at most 16 are allowed"));
+}
+
+#[test]
+fn max_locals_per_function() {
+    let wat = r#"
+        (module
+          (func)
+          (func (param i32) (local i32 i64) (local f32)))
+    "#;
+    let mut config = ModuleConfig::new();
+    config.max_locals_per_function(3);
+    config.parse(&wat::parse_str(wat).unwrap()).unwrap();
+
+    config.max_locals_per_function(2);
+    let err = parse_err(&config, wat);
+    assert!(err.contains("function 1 declares 3 locals, but at most 2 are allowed"));
+}
+
+#[test]
+fn max_operand_stack_depth() {
+    // Pushes 100 values before dropping any of them.
+    let mut body = "i32.const 0\n".repeat(100);
+    body.push_str(&"drop\n".repeat(100));
+    let wat = format!("(module (func {}))", body);
+
+    let mut config = ModuleConfig::new();
+    config.max_operand_stack_depth(100);
+    config.parse(&wat::parse_str(&wat).unwrap()).unwrap();
+
+    config.max_operand_stack_depth(99);
+    let err = parse_err(&config, &wat);
+    assert!(err.contains("operand stack depth exceeds the maximum of 99"));
+}
@@ -26,6 +26,9 @@ impl CustomSection for HelloCustomSection {
         let data = format!("Hello, {}!", self.0);
         data.into_bytes().into()
     }
+    fn clone_section(&self) -> Box<dyn CustomSection> {
+        Box::new(self.clone())
+    }
 }
 
 #[test]
@@ -84,6 +87,10 @@ fn smoke_test_code_transform() {
             vec![].into()
         }
 
+        fn clone_section(&self) -> Box<dyn CustomSection> {
+            Box::new(CheckCodeTransform)
+        }
+
         fn apply_code_transform(&mut self, transform: &CodeTransform) {
             APPLIED_CODE_TRANSFORM.store(1, Ordering::SeqCst);
             assert!(!transform.is_empty());
@@ -126,3 +133,97 @@ fn smoke_test_code_transform() {
 
     assert_eq!(APPLIED_CODE_TRANSFORM.load(Ordering::SeqCst), 1);
 }
+
+/// A module with one function, followed by a fake `.debug_info` section.
+fn module_with_debug_info(debug_info: &[u8]) -> Vec<u8> {
+    let mut wasm = wat::parse_str(
+        r#"
+            (module
+              (func (export "f") (result i32)
+                i32.const 1))
+        "#,
+    )
+    .unwrap();
+
+    let name = ".debug_info";
+    let size = 1 + name.len() + debug_info.len();
+    assert!(size < 0x80);
+    wasm.push(0);
+    wasm.push(size as u8);
+    wasm.push(name.len() as u8);
+    wasm.extend_from_slice(name.as_bytes());
+    wasm.extend_from_slice(debug_info);
+    wasm
+}
+
+fn debug_info_after_round_trip(module: &mut Module) -> Option<Vec<u8>> {
+    let wasm = module.emit_wasm();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    module.customs.remove_raw(".debug_info").map(|s| s.data)
+}
+
+fn modify_functions(module: &mut Module) {
+    for (_id, f) in module.funcs.iter_local_mut() {
+        let builder = f.builder_mut();
+        builder.func_body().drop_at(1);
+        builder.func_body().i32_const(2);
+    }
+}
+
+#[test]
+fn unmodified_code_preserves_dwarf() {
+    let debug_info = [0xde, 0xad, 0xbe, 0xef, 0x00, 0x01];
+    let wasm = module_with_debug_info(&debug_info);
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(
+        debug_info_after_round_trip(&mut module),
+        Some(debug_info.to_vec())
+    );
+}
+
+#[test]
+fn modified_code_drops_dwarf() {
+    let wasm = module_with_debug_info(&[1, 2, 3]);
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    modify_functions(&mut module);
+    assert_eq!(debug_info_after_round_trip(&mut module), None);
+}
+
+#[test]
+fn on_dwarf_invalidation_can_keep_dwarf() {
+    let wasm = module_with_debug_info(&[1, 2, 3]);
+    let mut config = ModuleConfig::new();
+    config.on_dwarf_invalidation(|name| name == ".debug_info");
+    let mut module = config.parse(&wasm).unwrap();
+    modify_functions(&mut module);
+    assert_eq!(
+        debug_info_after_round_trip(&mut module),
+        Some(vec![1, 2, 3])
+    );
+}
+
+#[test]
+fn remove_custom_section_by_name() {
+    let mut module = Module::default();
+    for (name, data) in [("keep", &b"kept"[..]), (".debug_info", &b"dwarf"[..])].iter() {
+        module.customs.add(walrus::RawCustomSection {
+            name: name.to_string(),
+            data: data.to_vec(),
+        });
+    }
+    let wasm = module.emit_wasm();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.custom_section(".debug_info"), Some(&b"dwarf"[..]));
+
+    assert_eq!(
+        module.remove_custom_section(".debug_info"),
+        Some(b"dwarf".to_vec())
+    );
+    assert_eq!(module.custom_section(".debug_info"), None);
+    assert_eq!(module.remove_custom_section(".debug_info"), None);
+
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.custom_section("keep"), Some(&b"kept"[..]));
+    assert_eq!(module.custom_section(".debug_info"), None);
+}
@@ -0,0 +1,76 @@
+//! Tests for the `DataCount` section.
+
+use walrus::{DataKind, Module};
+
+const DATA_COUNT: u8 = 12;
+
+/// The count in the emitted `DataCount` section, if there is one.
+fn emitted_data_count(module: &mut Module) -> Option<u8> {
+    let (wasm, layout) = module.emit_wasm_with_layout();
+    let range = layout.section(DATA_COUNT)?;
+    // Skip the section id and its padded five-byte size.
+    Some(wasm[range.start + 6])
+}
+
+/// The offset of the section with the given id in a small module, whose
+/// section sizes all fit in one byte.
+fn section_start(wasm: &[u8], id: u8) -> usize {
+    let mut pos = 8;
+    while wasm[pos] != id {
+        assert!(wasm[pos + 1] < 0x80);
+        pos += 2 + wasm[pos + 1] as usize;
+    }
+    pos
+}
+
+#[test]
+fn recomputed_after_adding_segments() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory 1)
+                (data $d "abc")
+                (func (export "f")
+                    i32.const 0
+                    i32.const 0
+                    i32.const 3
+                    memory.init $d
+                    data.drop $d))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    assert_eq!(emitted_data_count(&mut module), Some(1));
+
+    module.data.add(DataKind::Passive, b"def".to_vec());
+    assert_eq!(emitted_data_count(&mut module), Some(2));
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    assert_eq!(module.data.iter().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn emitted_for_new_passive_segment() -> anyhow::Result<()> {
+    let mut module = Module::from_buffer(&wat::parse_str("(module (memory 1))")?)?;
+    assert_eq!(emitted_data_count(&mut module), None);
+
+    module.data.add(DataKind::Passive, b"abc".to_vec());
+    assert_eq!(emitted_data_count(&mut module), Some(1));
+    Ok(())
+}
+
+#[test]
+fn inconsistent_data_count_is_reported() -> anyhow::Result<()> {
+    let mut wasm = wat::parse_str("(module (memory 1) (data \"abc\"))")?;
+    // Insert a `DataCount` section declaring two segments before the data
+    // section, which has one segment.
+    let data = section_start(&wasm, 11);
+    wasm.splice(data..data, vec![DATA_COUNT, 1, 2]);
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    assert!(format!("{:?}", err).contains("declared 2 data segments, found 1"));
+
+    let mut wasm = wat::parse_str("(module (memory 1))")?;
+    wasm.extend(&[DATA_COUNT, 1, 1]);
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    assert!(err.to_string().contains("there is no data section"));
+    Ok(())
+}
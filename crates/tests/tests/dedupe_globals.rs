@@ -0,0 +1,49 @@
+//! Tests for `Module::dedupe_globals`.
+
+use walrus::ir::{Instr, Value};
+use walrus::{ExportItem, FunctionBuilder, InitExpr, Module, ValType};
+
+#[test]
+fn merges_identical_immutable_globals() {
+    let mut module = Module::default();
+    let seven = InitExpr::Value(Value::I32(7));
+    let a = module.globals.add_local(ValType::I32, false, seven);
+    let b = module.globals.add_local(ValType::I32, false, seven);
+    let c = module
+        .globals
+        .add_local(ValType::I32, false, InitExpr::Value(Value::I32(8)));
+    let d = module
+        .globals
+        .add_local(ValType::I64, false, InitExpr::Value(Value::I64(7)));
+    let e = module.globals.add_local(ValType::I32, true, seven);
+    let f = module.globals.add_local(ValType::I32, true, seven);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut body = builder.func_body();
+    for global in [a, b, c, e, f].iter() {
+        body.global_get(*global).drop();
+    }
+    body.global_get(d).drop();
+    let func = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("b", b);
+
+    module.dedupe_globals();
+
+    let remaining = module.globals.iter().map(|g| g.id()).collect::<Vec<_>>();
+    assert_eq!(remaining, [a, c, d, e, f]);
+    let func = module.funcs.get(func).kind.unwrap_local();
+    let gets = func
+        .block(func.entry_block())
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::GlobalGet(get) => Some(get.global),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(gets, [a, a, c, e, f, d]);
+    let export = module.exports.iter().next().unwrap();
+    assert!(matches!(export.item, ExportItem::Global(g) if g == a));
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
@@ -0,0 +1,96 @@
+//! Tests for the `dylink.0` custom section.
+
+use walrus::{DylinkInfo, DylinkMemInfo, Module};
+
+/// A `dylink.0` section with memory info, one needed library, and a
+/// subsection walrus doesn't know about.
+fn dylink_section() -> Vec<u8> {
+    let mut payload = vec![8];
+    payload.extend(b"dylink.0");
+    payload.extend(&[1, 4, 16, 2, 1, 0]);
+    payload.extend(&[2, 9, 1, 7]);
+    payload.extend(b"libc.so");
+    payload.extend(&[9, 2, 0xaa, 0xbb]);
+
+    let mut section = vec![0, payload.len() as u8];
+    section.extend(payload);
+    section
+}
+
+#[test]
+fn round_trip() -> anyhow::Result<()> {
+    let wasm = wat::parse_str("(module (memory 1) (func (export \"f\")))")?;
+    let section = dylink_section();
+    let mut with_dylink = wasm[..8].to_vec();
+    with_dylink.extend(&section);
+    with_dylink.extend(&wasm[8..]);
+
+    let mut module = Module::from_buffer(&with_dylink)?;
+    let expected = DylinkInfo {
+        mem_info: Some(DylinkMemInfo {
+            memory_size: 16,
+            memory_alignment: 2,
+            table_size: 1,
+            table_alignment: 0,
+        }),
+        needed: vec!["libc.so".to_string()],
+        export_info: vec![],
+        import_info: vec![],
+        unknown: vec![(9, vec![0xaa, 0xbb])],
+    };
+    assert_eq!(module.dylink, Some(expected.clone()));
+    assert!(module.custom_section("dylink.0").is_none());
+
+    // It must be emitted as the very first section: a custom section whose
+    // name follows its (possibly padded) size.
+    let emitted = module.emit_wasm();
+    assert_eq!(emitted[8], 0);
+    let name = emitted.windows(9).position(|w| w == b"\x08dylink.0");
+    assert!(name.is_some_and(|i| i <= 8 + 1 + 5));
+
+    let module = Module::from_buffer(&emitted)?;
+    assert_eq!(module.dylink, Some(expected));
+    Ok(())
+}
+
+#[test]
+fn no_dylink_section() -> anyhow::Result<()> {
+    let wasm = wat::parse_str("(module)")?;
+    let module = Module::from_buffer(&wasm)?;
+    assert!(module.dylink.is_none());
+    Ok(())
+}
+
+#[test]
+fn invalid_dylink_section_is_kept_verbatim() -> anyhow::Result<()> {
+    let wasm = wat::parse_str("(module)")?;
+    // A memory info subsection with a trailing byte.
+    let mut payload = vec![8];
+    payload.extend(b"dylink.0");
+    payload.extend(&[1, 5, 16, 2, 1, 0, 0xff]);
+    let mut with_dylink = wasm[..8].to_vec();
+    with_dylink.extend(&[0, payload.len() as u8]);
+    with_dylink.extend(&payload);
+    with_dylink.extend(&wasm[8..]);
+
+    let mut module = Module::from_buffer(&with_dylink)?;
+    assert!(module.dylink.is_none());
+    assert_eq!(
+        module.custom_section("dylink.0"),
+        Some(&[1, 5, 16, 2, 1, 0, 0xff][..])
+    );
+
+    // It is still emitted first.
+    let emitted = module.emit_wasm();
+    assert_eq!(emitted[8], 0);
+    let name = emitted.windows(9).position(|w| w == b"\x08dylink.0");
+    assert!(name.is_some_and(|i| i <= 8 + 1 + 5));
+
+    let module = Module::from_buffer(&emitted)?;
+    assert!(module.dylink.is_none());
+    assert_eq!(
+        module.custom_section("dylink.0"),
+        Some(&[1, 5, 16, 2, 1, 0, 0xff][..])
+    );
+    Ok(())
+}
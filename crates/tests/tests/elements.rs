@@ -0,0 +1,36 @@
+//! Tests for reading and modifying element segments.
+
+use walrus::{ElementKind, FunctionBuilder, InitExpr, Module};
+
+#[test]
+fn append_to_active_segment() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (table 2 funcref)
+              (func $a)
+              (elem (i32.const 1) $a))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+
+    let b = FunctionBuilder::new(&mut module.types, &[], &[]).finish(vec![], &mut module.funcs);
+    let elem = module.elements.iter_mut().next().unwrap();
+    assert!(matches!(
+        elem.kind(),
+        ElementKind::Active {
+            offset: InitExpr::Value(walrus::ir::Value::I32(1)),
+            ..
+        }
+    ));
+    let mut items = elem.items().to_vec();
+    items.push(Some(b));
+    elem.set_items(items);
+
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    let elem = module.elements.iter().next().unwrap();
+    assert_eq!(elem.items().len(), 2);
+    assert!(elem.items().iter().all(|item| item.is_some()));
+    assert!(matches!(elem.kind(), ElementKind::Active { .. }));
+    Ok(())
+}
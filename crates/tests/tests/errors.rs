@@ -0,0 +1,85 @@
+//! Tests for the structured errors returned when parsing invalid wasm.
+
+use walrus::{IndexError, IndexKind, Module};
+
+#[test]
+fn out_of_range_call() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func $f
+                call 42))
+        "#,
+    )
+    .unwrap();
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    let index_err = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<IndexError>())
+        .expect("should have an `IndexError` in the error chain");
+    assert_eq!(
+        *index_err,
+        IndexError {
+            kind: IndexKind::Function,
+            index: 42,
+            max: 1,
+        }
+    );
+    assert_eq!(
+        index_err.to_string(),
+        "function index 42 out of range (max 1)"
+    );
+}
+
+#[test]
+fn out_of_range_local_get() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func $f (param i32 i32) (result i32)
+                (local i64)
+                local.get 5))
+        "#,
+    )
+    .unwrap();
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    let index_err = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<IndexError>())
+        .expect("should have an `IndexError` in the error chain");
+    assert_eq!(
+        *index_err,
+        IndexError {
+            kind: IndexKind::Local,
+            index: 5,
+            max: 3,
+        }
+    );
+    assert_eq!(
+        index_err.to_string(),
+        "local index 5 out of range (function has 3 locals)"
+    );
+}
+
+#[test]
+fn missing_end_names_unclosed_blocks() {
+    let body = [
+        0x00, // no locals
+        0x02, 0x40, // block
+        0x41, 0x01, // i32.const 1
+        0x04, 0x40, // if
+    ];
+    let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    wasm.extend(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]); // type section
+    wasm.extend(&[0x03, 0x02, 0x01, 0x00]); // function section
+    wasm.extend(&[0x0a, body.len() as u8 + 2, 0x01, body.len() as u8]); // code section
+    wasm.extend(&body);
+
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    let message = format!("{:?}", err);
+    assert!(
+        message.contains("2 control frame(s) left unclosed, innermost first: if, block"),
+        "{}",
+        message
+    );
+}
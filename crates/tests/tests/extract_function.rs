@@ -0,0 +1,100 @@
+//! Tests for `Module::extract_function`.
+
+use walrus::{ExportItem, FunctionKind, ImportKind, Module};
+
+const WAT: &str = r#"
+    (module
+      (import "host" "log" (func $log (param i32)))
+      (memory 1)
+      (global (mut i32) (i32.const 8))
+      (func $leaf (param $p i32) (result i32)
+        local.get $p
+        global.get 0
+        i32.add
+        i32.load)
+      (func $caller (result i32)
+        i32.const 1
+        call $log
+        i32.const 4
+        call $leaf))
+"#;
+
+fn func(module: &Module, name: &str) -> walrus::FunctionId {
+    module.funcs.by_name(name).unwrap()
+}
+
+fn imports(module: &Module) -> Vec<(String, String, &'static str)> {
+    let mut imports = module
+        .imports
+        .iter()
+        .map(|import| {
+            let kind = match import.kind {
+                ImportKind::Function(_) => "func",
+                ImportKind::Global(_) => "global",
+                ImportKind::Memory(_) => "memory",
+                ImportKind::Table(_) => "table",
+            };
+            (import.module.clone(), import.name.clone(), kind)
+        })
+        .collect::<Vec<_>>();
+    imports.sort();
+    imports
+}
+
+#[test]
+fn extract_leaf_function() -> anyhow::Result<()> {
+    let module = Module::from_buffer(&wat::parse_str(WAT)?)?;
+    let mut leaf = module.extract_function(func(&module, "leaf"))?;
+
+    assert_eq!(leaf.funcs.iter_local().count(), 1);
+    assert_eq!(
+        imports(&leaf),
+        [
+            ("env".to_string(), "global0".to_string(), "global"),
+            ("env".to_string(), "memory0".to_string(), "memory"),
+        ]
+    );
+    let export = leaf.exports.iter().next().unwrap();
+    assert_eq!(export.name, "leaf");
+    let id = match export.item {
+        ExportItem::Function(f) => f,
+        _ => panic!("expected a function export"),
+    };
+    let f = leaf.funcs.get(id);
+    assert!(matches!(f.kind, FunctionKind::Local(_)));
+    let (params, results) = leaf.types.params_results(f.ty());
+    assert_eq!((params.len(), results.len()), (1, 1));
+
+    // Only the function's own type is needed.
+    let wasm = leaf.emit_wasm();
+    Module::from_buffer(&wasm)?;
+    let text = wasmprinter::print_bytes(&wasm)?;
+    assert_eq!(text.matches("(type (;").count(), 1);
+    Ok(())
+}
+
+#[test]
+fn callees_become_imports() -> anyhow::Result<()> {
+    let module = Module::from_buffer(&wat::parse_str(WAT)?)?;
+    let mut caller = module.extract_function(func(&module, "caller"))?;
+
+    assert_eq!(caller.funcs.iter_local().count(), 1);
+    assert_eq!(
+        imports(&caller),
+        [
+            ("env".to_string(), "leaf".to_string(), "func"),
+            ("host".to_string(), "log".to_string(), "func"),
+        ]
+    );
+
+    let wasm = caller.emit_wasm();
+    Module::from_buffer(&wasm)?;
+    Ok(())
+}
+
+#[test]
+fn rejects_imported_functions() -> anyhow::Result<()> {
+    let module = Module::from_buffer(&wat::parse_str(WAT)?)?;
+    assert!(module.extract_function(func(&module, "log")).is_err());
+    Ok(())
+}
@@ -0,0 +1,268 @@
+//! Tests for emitting encodings for different `FeatureLevel`s.
+
+use walrus::ir::{BinaryOp, UnaryOp, Value};
+use walrus::{
+    FeatureLevel, FunctionBuilder, InstrSeqBuilder, LocalId, Module, ModuleConfig, ValType,
+};
+
+/// Every binary SIMD operation, along with its opcode in the standardized
+/// proposal.
+const SIMD_BINOPS: &[(BinaryOp, u32)] = &[
+    (BinaryOp::I8x16ReplaceLane { idx: 1 }, 0x17),
+    (BinaryOp::I16x8ReplaceLane { idx: 1 }, 0x1a),
+    (BinaryOp::I32x4ReplaceLane { idx: 1 }, 0x1c),
+    (BinaryOp::I64x2ReplaceLane { idx: 1 }, 0x1e),
+    (BinaryOp::F32x4ReplaceLane { idx: 1 }, 0x20),
+    (BinaryOp::F64x2ReplaceLane { idx: 1 }, 0x22),
+    (BinaryOp::I8x16Eq, 0x23),
+    (BinaryOp::I8x16Ne, 0x24),
+    (BinaryOp::I8x16LtS, 0x25),
+    (BinaryOp::I8x16LtU, 0x26),
+    (BinaryOp::I8x16GtS, 0x27),
+    (BinaryOp::I8x16GtU, 0x28),
+    (BinaryOp::I8x16LeS, 0x29),
+    (BinaryOp::I8x16LeU, 0x2a),
+    (BinaryOp::I8x16GeS, 0x2b),
+    (BinaryOp::I8x16GeU, 0x2c),
+    (BinaryOp::I16x8Eq, 0x2d),
+    (BinaryOp::I16x8Ne, 0x2e),
+    (BinaryOp::I16x8LtS, 0x2f),
+    (BinaryOp::I16x8LtU, 0x30),
+    (BinaryOp::I16x8GtS, 0x31),
+    (BinaryOp::I16x8GtU, 0x32),
+    (BinaryOp::I16x8LeS, 0x33),
+    (BinaryOp::I16x8LeU, 0x34),
+    (BinaryOp::I16x8GeS, 0x35),
+    (BinaryOp::I16x8GeU, 0x36),
+    (BinaryOp::I32x4Eq, 0x37),
+    (BinaryOp::I32x4Ne, 0x38),
+    (BinaryOp::I32x4LtS, 0x39),
+    (BinaryOp::I32x4LtU, 0x3a),
+    (BinaryOp::I32x4GtS, 0x3b),
+    (BinaryOp::I32x4GtU, 0x3c),
+    (BinaryOp::I32x4LeS, 0x3d),
+    (BinaryOp::I32x4LeU, 0x3e),
+    (BinaryOp::I32x4GeS, 0x3f),
+    (BinaryOp::I32x4GeU, 0x40),
+    (BinaryOp::F32x4Eq, 0x41),
+    (BinaryOp::F32x4Ne, 0x42),
+    (BinaryOp::F32x4Lt, 0x43),
+    (BinaryOp::F32x4Gt, 0x44),
+    (BinaryOp::F32x4Le, 0x45),
+    (BinaryOp::F32x4Ge, 0x46),
+    (BinaryOp::F64x2Eq, 0x47),
+    (BinaryOp::F64x2Ne, 0x48),
+    (BinaryOp::F64x2Lt, 0x49),
+    (BinaryOp::F64x2Gt, 0x4a),
+    (BinaryOp::F64x2Le, 0x4b),
+    (BinaryOp::F64x2Ge, 0x4c),
+    (BinaryOp::V128And, 0x4e),
+    (BinaryOp::V128AndNot, 0x4f),
+    (BinaryOp::V128Or, 0x50),
+    (BinaryOp::V128Xor, 0x51),
+    (BinaryOp::I8x16NarrowI16x8S, 0x65),
+    (BinaryOp::I8x16NarrowI16x8U, 0x66),
+    (BinaryOp::I8x16Shl, 0x6b),
+    (BinaryOp::I8x16ShrS, 0x6c),
+    (BinaryOp::I8x16ShrU, 0x6d),
+    (BinaryOp::I8x16Add, 0x6e),
+    (BinaryOp::I8x16AddSaturateS, 0x6f),
+    (BinaryOp::I8x16AddSaturateU, 0x70),
+    (BinaryOp::I8x16Sub, 0x71),
+    (BinaryOp::I8x16SubSaturateS, 0x72),
+    (BinaryOp::I8x16SubSaturateU, 0x73),
+    (BinaryOp::I8x16MinS, 0x76),
+    (BinaryOp::I8x16MinU, 0x77),
+    (BinaryOp::I8x16MaxS, 0x78),
+    (BinaryOp::I8x16MaxU, 0x79),
+    (BinaryOp::I8x16RoundingAverageU, 0x7b),
+    (BinaryOp::I16x8NarrowI32x4S, 0x85),
+    (BinaryOp::I16x8NarrowI32x4U, 0x86),
+    (BinaryOp::I16x8Shl, 0x8b),
+    (BinaryOp::I16x8ShrS, 0x8c),
+    (BinaryOp::I16x8ShrU, 0x8d),
+    (BinaryOp::I16x8Add, 0x8e),
+    (BinaryOp::I16x8AddSaturateS, 0x8f),
+    (BinaryOp::I16x8AddSaturateU, 0x90),
+    (BinaryOp::I16x8Sub, 0x91),
+    (BinaryOp::I16x8SubSaturateS, 0x92),
+    (BinaryOp::I16x8SubSaturateU, 0x93),
+    (BinaryOp::I16x8Mul, 0x95),
+    (BinaryOp::I16x8MinS, 0x96),
+    (BinaryOp::I16x8MinU, 0x97),
+    (BinaryOp::I16x8MaxS, 0x98),
+    (BinaryOp::I16x8MaxU, 0x99),
+    (BinaryOp::I16x8RoundingAverageU, 0x9b),
+    (BinaryOp::I32x4Shl, 0xab),
+    (BinaryOp::I32x4ShrS, 0xac),
+    (BinaryOp::I32x4ShrU, 0xad),
+    (BinaryOp::I32x4Add, 0xae),
+    (BinaryOp::I32x4Sub, 0xb1),
+    (BinaryOp::I32x4Mul, 0xb5),
+    (BinaryOp::I32x4MinS, 0xb6),
+    (BinaryOp::I32x4MinU, 0xb7),
+    (BinaryOp::I32x4MaxS, 0xb8),
+    (BinaryOp::I32x4MaxU, 0xb9),
+    (BinaryOp::I64x2Shl, 0xcb),
+    (BinaryOp::I64x2ShrS, 0xcc),
+    (BinaryOp::I64x2ShrU, 0xcd),
+    (BinaryOp::I64x2Add, 0xce),
+    (BinaryOp::I64x2Sub, 0xd1),
+    (BinaryOp::I64x2Mul, 0xd5),
+    (BinaryOp::F32x4Add, 0xe4),
+    (BinaryOp::F32x4Sub, 0xe5),
+    (BinaryOp::F32x4Mul, 0xe6),
+    (BinaryOp::F32x4Div, 0xe7),
+    (BinaryOp::F32x4Min, 0xe8),
+    (BinaryOp::F32x4Max, 0xe9),
+    (BinaryOp::F64x2Add, 0xf0),
+    (BinaryOp::F64x2Sub, 0xf1),
+    (BinaryOp::F64x2Mul, 0xf2),
+    (BinaryOp::F64x2Div, 0xf3),
+    (BinaryOp::F64x2Min, 0xf4),
+    (BinaryOp::F64x2Max, 0xf5),
+];
+
+/// Every unary SIMD operation, along with its opcode in the standardized
+/// proposal.
+const SIMD_UNOPS: &[(UnaryOp, u32)] = &[
+    (UnaryOp::I8x16Splat, 0x0f),
+    (UnaryOp::I16x8Splat, 0x10),
+    (UnaryOp::I32x4Splat, 0x11),
+    (UnaryOp::I64x2Splat, 0x12),
+    (UnaryOp::F32x4Splat, 0x13),
+    (UnaryOp::F64x2Splat, 0x14),
+    (UnaryOp::I8x16ExtractLaneS { idx: 1 }, 0x15),
+    (UnaryOp::I8x16ExtractLaneU { idx: 1 }, 0x16),
+    (UnaryOp::I16x8ExtractLaneS { idx: 1 }, 0x18),
+    (UnaryOp::I16x8ExtractLaneU { idx: 1 }, 0x19),
+    (UnaryOp::I32x4ExtractLane { idx: 1 }, 0x1b),
+    (UnaryOp::I64x2ExtractLane { idx: 1 }, 0x1d),
+    (UnaryOp::F32x4ExtractLane { idx: 1 }, 0x1f),
+    (UnaryOp::F64x2ExtractLane { idx: 1 }, 0x21),
+    (UnaryOp::V128Not, 0x4d),
+    (UnaryOp::I8x16Abs, 0x60),
+    (UnaryOp::I8x16Neg, 0x61),
+    (UnaryOp::I8x16AnyTrue, 0x53),
+    (UnaryOp::I8x16AllTrue, 0x63),
+    (UnaryOp::I16x8Abs, 0x80),
+    (UnaryOp::I16x8Neg, 0x81),
+    (UnaryOp::I16x8AnyTrue, 0x53),
+    (UnaryOp::I16x8AllTrue, 0x83),
+    (UnaryOp::I16x8WidenLowI8x16S, 0x87),
+    (UnaryOp::I16x8WidenHighI8x16S, 0x88),
+    (UnaryOp::I16x8WidenLowI8x16U, 0x89),
+    (UnaryOp::I16x8WidenHighI8x16U, 0x8a),
+    (UnaryOp::I32x4Abs, 0xa0),
+    (UnaryOp::I32x4Neg, 0xa1),
+    (UnaryOp::I32x4AnyTrue, 0x53),
+    (UnaryOp::I32x4AllTrue, 0xa3),
+    (UnaryOp::I32x4WidenLowI16x8S, 0xa7),
+    (UnaryOp::I32x4WidenHighI16x8S, 0xa8),
+    (UnaryOp::I32x4WidenLowI16x8U, 0xa9),
+    (UnaryOp::I32x4WidenHighI16x8U, 0xaa),
+    (UnaryOp::I64x2Neg, 0xc1),
+    (UnaryOp::F32x4Abs, 0xe0),
+    (UnaryOp::F32x4Neg, 0xe1),
+    (UnaryOp::F32x4Sqrt, 0xe3),
+    (UnaryOp::F64x2Abs, 0xec),
+    (UnaryOp::F64x2Neg, 0xed),
+    (UnaryOp::F64x2Sqrt, 0xef),
+    (UnaryOp::I32x4TruncSatF32x4S, 0xf8),
+    (UnaryOp::I32x4TruncSatF32x4U, 0xf9),
+    (UnaryOp::F32x4ConvertI32x4S, 0xfa),
+    (UnaryOp::F32x4ConvertI32x4U, 0xfb),
+    (UnaryOp::I32x4TruncSatF64x2SZero, 0xfc),
+    (UnaryOp::I32x4TruncSatF64x2UZero, 0xfd),
+    (UnaryOp::F64x2ConvertLowI32x4S, 0xfe),
+    (UnaryOp::F64x2ConvertLowI32x4U, 0xff),
+    (UnaryOp::F32x4DemoteF64x2Zero, 0x5e),
+    (UnaryOp::F64x2PromoteLowF32x4, 0x5f),
+];
+
+/// Emit a function using `i8x16.any_true` and `i8x16.add` for `level`.
+fn emit(level: FeatureLevel) -> Vec<u8> {
+    let mut config = ModuleConfig::new();
+    config.feature_level(level);
+    let mut module = Module::with_config(config);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .const_(Value::V128(1))
+        .const_(Value::V128(2))
+        .binop(BinaryOp::I8x16Add)
+        .unop(UnaryOp::I8x16AnyTrue);
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+    module.emit_wasm()
+}
+
+/// Emit a function whose body is built by `build` for `level`, passing it a
+/// `v128` local to use for operands.
+fn emit_body(level: FeatureLevel, build: impl FnOnce(&mut InstrSeqBuilder, LocalId)) -> Vec<u8> {
+    let mut config = ModuleConfig::new();
+    config.feature_level(level);
+    let mut module = Module::with_config(config);
+    let x = module.locals.add(ValType::V128);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    build(&mut builder.func_body(), x);
+    builder.finish(vec![], &mut module.funcs);
+    module.emit_wasm()
+}
+
+/// The encoding of a SIMD instruction with the given opcode, after a
+/// `local.get 0` of its last operand.
+fn simd(opcode: u32) -> Vec<u8> {
+    let mut bytes = vec![0x20, 0x00, 0xfd];
+    let mut opcode = opcode;
+    loop {
+        let byte = (opcode & 0x7f) as u8;
+        opcode >>= 7;
+        if opcode == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn contains(wasm: &[u8], bytes: &[u8]) -> bool {
+    wasm.windows(bytes.len()).any(|w| w == bytes)
+}
+
+#[test]
+fn legacy_simd_opcodes() {
+    let wasm = emit(FeatureLevel::Legacy);
+    assert!(contains(&wasm, &[0xfd, 0x6e, 0xfd, 0x62, 0x0b]));
+
+    // This is the encoding walrus parses.
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn standard_simd_opcodes() {
+    let wasm = emit(FeatureLevel::Standard);
+    assert!(contains(&wasm, &[0xfd, 0x6e, 0xfd, 0x53, 0x0b]));
+    assert!(!contains(&wasm, &[0xfd, 0x62]));
+}
+
+#[test]
+fn legacy_is_the_default() {
+    assert_eq!(FeatureLevel::default(), FeatureLevel::Legacy);
+}
+
+#[test]
+fn standard_opcodes_of_every_simd_op() {
+    for (op, opcode) in SIMD_BINOPS {
+        let wasm = emit_body(FeatureLevel::Standard, |body, x| {
+            body.local_get(x).local_get(x).binop(*op);
+        });
+        assert!(contains(&wasm, &simd(*opcode)), "{:?}", op);
+    }
+    for (op, opcode) in SIMD_UNOPS {
+        let wasm = emit_body(FeatureLevel::Standard, |body, x| {
+            body.local_get(x).unop(*op);
+        });
+        assert!(contains(&wasm, &simd(*opcode)), "{:?}", op);
+    }
+}
@@ -0,0 +1,103 @@
+//! Tests for `Module::fix_memory_minimums`.
+
+use walrus::{ActiveData, ActiveDataLocation, DataKind, MemoryId, Module};
+
+fn add(module: &mut Module, memory: MemoryId, location: ActiveDataLocation, len: usize) {
+    let kind = DataKind::Active(ActiveData { memory, location });
+    module.data.add(kind, vec![0; len]);
+}
+
+#[test]
+fn fix_memory_minimums() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    let small = module.memories.add_local(false, 1, Some(4));
+    let large = module.memories.add_local(false, 4, None);
+
+    // Ends one byte into the fourth page of `small`.
+    add(
+        &mut module,
+        small,
+        ActiveDataLocation::Absolute(0x2_fff0),
+        0x11,
+    );
+    // Fits within `large` already.
+    add(
+        &mut module,
+        large,
+        ActiveDataLocation::Absolute(0x10000),
+        0x10000,
+    );
+    // Relative segments are ignored.
+    let global = module.globals.add_local(
+        walrus::ValType::I32,
+        false,
+        walrus::InitExpr::Value(walrus::ir::Value::I32(0x100000)),
+    );
+    add(&mut module, large, ActiveDataLocation::Relative(global), 1);
+
+    module.fix_memory_minimums()?;
+    let small = module.memories.get(small);
+    assert_eq!((small.initial, small.maximum), (4, Some(4)));
+    let large = module.memories.get(large);
+    assert_eq!((large.initial, large.maximum), (4, None));
+    Ok(())
+}
+
+#[test]
+fn minimum_above_maximum_is_an_error() {
+    let mut module = Module::default();
+    let fits = module.memories.add_local(false, 1, None);
+    let memory = module.memories.add_local(false, 1, Some(2));
+    add(&mut module, fits, ActiveDataLocation::Absolute(0x10000), 1);
+    add(
+        &mut module,
+        memory,
+        ActiveDataLocation::Absolute(0x20000),
+        1,
+    );
+
+    assert!(module.fix_memory_minimums().is_err());
+    // Nothing is changed, even in memories that could have grown.
+    assert_eq!(module.memories.get(fits).initial, 1);
+    let memory = module.memories.get(memory);
+    assert_eq!((memory.initial, memory.maximum), (1, Some(2)));
+}
+
+#[test]
+fn minimum_above_spec_limit_is_an_error() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    add(
+        &mut module,
+        memory,
+        ActiveDataLocation::Absolute(u32::MAX),
+        2,
+    );
+
+    assert!(module.fix_memory_minimums().is_err());
+    assert_eq!(module.memories.get(memory).initial, 1);
+}
+
+#[test]
+fn imported_memories_are_not_grown() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    let (memory, _) = module.add_import_memory("env", "memory", false, 1, None);
+    add(
+        &mut module,
+        memory,
+        ActiveDataLocation::Absolute(0),
+        0x10000,
+    );
+    // Imported memories that are already large enough are fine.
+    module.fix_memory_minimums()?;
+
+    add(
+        &mut module,
+        memory,
+        ActiveDataLocation::Absolute(0x10000),
+        1,
+    );
+    assert!(module.fix_memory_minimums().is_err());
+    assert_eq!(module.memories.get(memory).initial, 1);
+    Ok(())
+}
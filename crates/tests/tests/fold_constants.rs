@@ -0,0 +1,65 @@
+//! Tests for `Module::fold_constants`.
+
+use walrus::ir::{BinaryOp, Instr, Value};
+use walrus::{FunctionBuilder, FunctionId, Module, ModuleConfig, ValType};
+use walrus_tests::testutils::entry_instrs;
+
+fn add_div(module: &mut Module, a: f32, b: f32) -> FunctionId {
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::F32]);
+    builder
+        .func_body()
+        .f32_const(a)
+        .f32_const(b)
+        .binop(BinaryOp::F32Div);
+    builder.finish(vec![], &mut module.funcs)
+}
+
+#[test]
+fn folds_integers() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(i32::MAX)
+        .i32_const(2)
+        .i32_const(3)
+        .binop(BinaryOp::I32Mul)
+        .binop(BinaryOp::I32Add);
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    module.fold_constants();
+    match &entry_instrs(&module, f)[..] {
+        [Instr::Const(c)] => assert!(matches!(c.value, Value::I32(v) if v == i32::MIN + 5)),
+        other => panic!("unexpected body: {:?}", other),
+    }
+}
+
+#[test]
+fn folds_infinity_but_not_nan() {
+    let mut module = Module::default();
+    let inf = add_div(&mut module, 1.0, 0.0);
+    let nan = add_div(&mut module, 0.0, 0.0);
+
+    module.fold_constants();
+    match &entry_instrs(&module, inf)[..] {
+        [Instr::Const(c)] => {
+            assert!(matches!(c.value, Value::F32(v) if v == f32::INFINITY))
+        }
+        other => panic!("unexpected body: {:?}", other),
+    }
+    assert_eq!(entry_instrs(&module, nan).len(), 3);
+}
+
+#[test]
+fn folds_nan_when_enabled() {
+    let mut config = ModuleConfig::new();
+    config.fold_float_constants(true);
+    let mut module = Module::with_config(config);
+    let nan = add_div(&mut module, 0.0, 0.0);
+
+    module.fold_constants();
+    match &entry_instrs(&module, nan)[..] {
+        [Instr::Const(c)] => assert!(matches!(c.value, Value::F32(v) if v.is_nan())),
+        other => panic!("unexpected body: {:?}", other),
+    }
+}
@@ -0,0 +1,80 @@
+//! Tests for function layout hints and the `walrus.hints` custom section.
+
+use walrus::{FunctionHint, Module};
+
+fn wasm() -> Vec<u8> {
+    wat::parse_str(
+        r#"
+            (module
+                (func $big (export "big") (result i32)
+                    i32.const 1
+                    i32.const 2
+                    i32.add
+                    i32.const 3
+                    i32.add)
+                (func $medium (export "medium") (result i32)
+                    i32.const 1
+                    i32.const 2
+                    i32.add)
+                (func $small (export "small")))
+        "#,
+    )
+    .unwrap()
+}
+
+fn exported(module: &Module, name: &str) -> walrus::FunctionId {
+    match module.exports.iter().find(|e| e.name == name).unwrap().item {
+        walrus::ExportItem::Function(f) => f,
+        _ => panic!("not a function export"),
+    }
+}
+
+/// Names of the functions in the order their bodies are emitted.
+fn code_order(module: &mut Module) -> Vec<String> {
+    let (_, layout) = module.emit_wasm_with_layout();
+    let mut functions = layout.functions.clone();
+    functions.sort_by_key(|(_, range)| range.start);
+    functions
+        .into_iter()
+        .map(|(id, _)| module.funcs.get(id).name.clone().unwrap())
+        .collect()
+}
+
+#[test]
+fn hints_round_trip() -> anyhow::Result<()> {
+    let mut module = Module::from_buffer(&wasm())?;
+    let small = exported(&module, "small");
+    let big = exported(&module, "big");
+    module.set_function_hint(small, FunctionHint::Hot);
+    module.set_function_hint(big, FunctionHint::Cold);
+
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    assert_eq!(module.customs.iter().count(), 0);
+    let hint = |name| module.function_hint(exported(&module, name));
+    assert_eq!(hint("small"), Some(FunctionHint::Hot));
+    assert_eq!(hint("big"), Some(FunctionHint::Cold));
+    assert_eq!(hint("medium"), None);
+    Ok(())
+}
+
+#[test]
+fn hints_affect_order_when_requested() -> anyhow::Result<()> {
+    let mut module = Module::from_buffer(&wasm())?;
+    let small = exported(&module, "small");
+    let big = exported(&module, "big");
+    module.set_function_hint(small, FunctionHint::Hot);
+    module.set_function_hint(big, FunctionHint::Cold);
+
+    // Hints alone don't change the layout.
+    assert_eq!(code_order(&mut module), ["big", "medium", "small"]);
+
+    module.order_functions_by_hint()?;
+    assert_eq!(code_order(&mut module), ["small", "medium", "big"]);
+
+    // The hints survive a round trip, and so can be used to order the
+    // re-parsed module too.
+    let mut module = Module::from_buffer(&module.emit_wasm())?;
+    module.order_functions_by_hint()?;
+    assert_eq!(code_order(&mut module), ["small", "medium", "big"]);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Tests for controlling the layout of functions in the code section.
+
+use walrus::ir::{Call, Instr};
+use walrus::{FunctionBuilder, FunctionId, Module};
+
+/// Build a module with an exported `caller` function that calls `callee`.
+fn caller_and_callee() -> (Module, FunctionId, FunctionId) {
+    let mut module = Module::default();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .name("callee".to_string())
+        .func_body()
+        .i32_const(1)
+        .drop();
+    let callee = builder.finish(vec![], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("caller".to_string()).func_body().call(callee);
+    let caller = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("caller", caller);
+
+    (module, caller, callee)
+}
+
+/// Emit and re-parse `module`, returning the names of its local functions in
+/// index order and the name of the function that `caller` calls.
+fn emitted_layout(module: &mut Module) -> (Vec<String>, String) {
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let names = module
+        .funcs
+        .iter()
+        .map(|f| f.name.clone().unwrap())
+        .collect::<Vec<_>>();
+    let caller = module.funcs.by_name("caller").unwrap();
+    let caller = module.funcs.get(caller).kind.unwrap_local();
+    let callee = match &caller.block(caller.entry_block())[0].0 {
+        Instr::Call(Call { func }) => module.funcs.get(*func).name.clone().unwrap(),
+        other => panic!("expected a call, found {:?}", other),
+    };
+    (names, callee)
+}
+
+#[test]
+fn reorder_two_functions() {
+    let (mut module, caller, callee) = caller_and_callee();
+
+    module.set_function_order(vec![callee, caller]).unwrap();
+    let (names, called) = emitted_layout(&mut module);
+    assert_eq!(names, ["callee", "caller"]);
+    assert_eq!(called, "callee");
+
+    module.set_function_order(vec![caller, callee]).unwrap();
+    let (names, called) = emitted_layout(&mut module);
+    assert_eq!(names, ["caller", "callee"]);
+    assert_eq!(called, "callee");
+}
+
+#[test]
+fn order_must_be_a_permutation() {
+    let (mut module, caller, callee) = caller_and_callee();
+    assert!(module.set_function_order(vec![caller]).is_err());
+    assert!(module.set_function_order(vec![caller, caller]).is_err());
+    assert!(module
+        .set_function_order(vec![caller, callee, callee])
+        .is_err());
+}
@@ -0,0 +1,328 @@
+//! Tests for `ModuleFunctions` and the helpers on `Function`.
+
+use walrus::ir::{BinaryOp, Call, Const, Instr, InstrLocId, ModuleVisitor, Value};
+use walrus::{FunctionBuilder, FunctionId, Module, ValType};
+
+fn add_named_function(module: &mut Module, name: &str) -> FunctionId {
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .name(name.to_string())
+        .func_body()
+        .i32_const(1)
+        .drop();
+    builder.finish(vec![], &mut module.funcs)
+}
+
+#[test]
+fn function_by_name() {
+    let mut module = Module::default();
+    let a = add_named_function(&mut module, "a");
+    let b = add_named_function(&mut module, "b");
+    assert_eq!(module.function_by_name("a"), Some(a));
+    assert_eq!(module.function_by_name("b"), Some(b));
+    assert_eq!(module.function_by_name("c"), None);
+
+    // Renaming and adding functions is reflected in later lookups.
+    module.funcs.get_mut(b).name = Some("c".to_string());
+    assert_eq!(module.function_by_name("b"), None);
+    assert_eq!(module.function_by_name("c"), Some(b));
+    let a2 = add_named_function(&mut module, "a");
+    assert_eq!(module.function_by_name("a"), Some(a));
+
+    module.funcs.delete(a);
+    assert_eq!(module.function_by_name("a"), Some(a2));
+}
+
+#[test]
+fn function_by_export() {
+    let mut module = Module::default();
+    let a = add_named_function(&mut module, "a");
+    module.exports.add("exported_a", a);
+    assert_eq!(module.function_by_export("exported_a"), Some(a));
+    assert_eq!(module.function_by_export("a"), None);
+}
+
+#[test]
+fn add_stub_function() {
+    let mut module = Module::default();
+    let sigs: &[(&[ValType], &[ValType])] = &[
+        (&[], &[]),
+        (&[ValType::I32, ValType::F64], &[ValType::I64]),
+        (&[ValType::Externref], &[ValType::F32, ValType::V128]),
+    ];
+    for (params, results) in sigs {
+        let ty = module.types.add(params, results);
+        let stub = module.add_stub_function(ty);
+        assert_eq!(module.funcs.get(stub).ty(), ty);
+        assert_eq!(module.func_params(stub), *params);
+        let func = module.funcs.get(stub).kind.unwrap_local();
+        assert_eq!(func.args.len(), params.len());
+        let body = func.block(func.entry_block());
+        assert_eq!(body.len(), 1);
+        assert!(body[0].0.is_unreachable());
+    }
+
+    // Parsing the emitted module validates each stub against its type.
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.funcs.iter_local().count(), sigs.len());
+}
+
+#[test]
+fn replace_function_body() {
+    let mut module = Module::default();
+    let add_const = |module: &mut Module, value| {
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+        builder.func_body().i32_const(value);
+        builder.finish(vec![], &mut module.funcs)
+    };
+    let f = add_const(&mut module, 1);
+    module.funcs.get_mut(f).name = Some("f".to_string());
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().call(f);
+    let caller = builder.finish(vec![], &mut module.funcs);
+
+    let replacement = add_const(&mut module, 2);
+    let new = module.funcs.get(replacement).kind.unwrap_local().clone();
+    module.funcs.delete(replacement);
+    module.replace_function_body(f, new).unwrap();
+
+    let func = module.funcs.get(f);
+    assert_eq!(func.name.as_deref(), Some("f"));
+    let body = func.kind.unwrap_local();
+    let body = body.block(body.entry_block());
+    assert!(matches!(
+        body[0].0,
+        Instr::Const(Const {
+            value: Value::I32(2)
+        })
+    ));
+
+    // The caller still calls the same function, now with the new body.
+    let caller = module.funcs.get(caller).kind.unwrap_local();
+    let call = &caller.block(caller.entry_block())[0].0;
+    assert!(matches!(call, Instr::Call(Call { func }) if *func == f));
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+
+    // Bodies of a different type are rejected.
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body();
+    let wrong = builder.finish(vec![], &mut module.funcs);
+    let new = module.funcs.get(wrong).kind.unwrap_local().clone();
+    assert!(module.replace_function_body(f, new).is_err());
+}
+
+#[test]
+fn is_pure_leaf() {
+    let mut module = Module::default();
+    let x = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        .local_get(x)
+        .if_else(
+            ValType::I32,
+            |then| {
+                then.local_get(x).i32_const(1).binop(BinaryOp::I32Add);
+            },
+            |else_| {
+                else_.i32_const(0);
+            },
+        )
+        .local_tee(x);
+    let pure = builder.finish(vec![x], &mut module.funcs);
+    assert!(module.is_pure_leaf(pure));
+
+    // Calls, global writes, traps, and loops all make a function impure.
+    let global = module.globals.add_local(
+        ValType::I32,
+        true,
+        walrus::InitExpr::Value(walrus::ir::Value::I32(0)),
+    );
+    let add = |module: &mut Module, fill: &dyn Fn(&mut walrus::InstrSeqBuilder)| {
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+        builder.func_body().local_get(x);
+        fill(&mut builder.func_body());
+        builder.finish(vec![x], &mut module.funcs)
+    };
+    let impure = [
+        add(&mut module, &|body| {
+            body.call(pure).drop();
+        }),
+        add(&mut module, &|body| {
+            body.global_set(global);
+        }),
+        add(&mut module, &|body| {
+            body.i32_const(1).binop(BinaryOp::I32DivS).drop();
+        }),
+        add(&mut module, &|body| {
+            body.drop().loop_(None, |_| {});
+        }),
+    ];
+    for f in impure.iter() {
+        assert!(!module.is_pure_leaf(*f));
+    }
+
+    let ty = module.types.add(&[], &[]);
+    let (import, _) = module.add_import_func("env", "f", ty);
+    assert!(!module.is_pure_leaf(import));
+}
+
+#[test]
+fn visit_all_instrs() {
+    let mut module = Module::default();
+    let a = add_named_function(&mut module, "a");
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().call(a).block(None, |block| {
+        block.call(a);
+    });
+    let b = builder.finish(vec![], &mut module.funcs);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().call(b);
+    let c = builder.finish(vec![], &mut module.funcs);
+
+    #[derive(Default)]
+    struct CollectCalls(Vec<(FunctionId, FunctionId)>);
+
+    impl<'a> ModuleVisitor<'a> for CollectCalls {
+        fn visit_instr(&mut self, func: FunctionId, instr: &'a Instr, _: &'a InstrLocId) {
+            if let Instr::Call(Call { func: target }) = instr {
+                self.0.push((func, *target));
+            }
+        }
+    }
+
+    let mut calls = CollectCalls::default();
+    module.visit_all_instrs(&mut calls);
+    calls.0.sort();
+    assert_eq!(calls.0, vec![(b, a), (b, a), (c, b)]);
+}
+
+#[test]
+fn opcode_histogram() {
+    let mut module = Module::default();
+    let a = add_named_function(&mut module, "a");
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().call(a).block(None, |block| {
+        block
+            .i32_const(1)
+            .i32_const(2)
+            .binop(BinaryOp::I32Add)
+            .drop();
+    });
+    builder.finish(vec![], &mut module.funcs);
+
+    let histogram = module.opcode_histogram();
+    let expected = vec![
+        ("binop", 1),
+        ("block", 1),
+        ("call", 1),
+        ("const", 3),
+        ("drop", 2),
+    ];
+    assert_eq!(histogram.into_iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn func_params_and_results() {
+    let mut module = Module::default();
+    let builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::F64],
+        &[ValType::I64],
+    );
+    let f = builder.finish(vec![], &mut module.funcs);
+    assert_eq!(module.func_params(f), [ValType::I32, ValType::F64]);
+    assert_eq!(module.func_results(f), [ValType::I64]);
+
+    let ty = module.types.add(&[ValType::F32], &[]);
+    let (g, _) = module.add_import_func("env", "g", ty);
+    assert_eq!(module.func_params(g), [ValType::F32]);
+    assert_eq!(module.func_results(g), []);
+}
+
+#[test]
+fn func_arity() {
+    let mut module = Module::default();
+    let builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::F64, ValType::I32],
+        &[ValType::I64, ValType::I64],
+    );
+    let f = builder.finish(vec![], &mut module.funcs);
+    assert_eq!(module.func_arity(f), (3, 2));
+
+    let ty = module.types.add(&[], &[]);
+    let (g, _) = module.add_import_func("env", "g", ty);
+    assert_eq!(module.func_arity(g), (0, 0));
+}
+
+#[test]
+fn function_resource_usage() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let global = module.globals.add_local(
+        ValType::I32,
+        true,
+        walrus::InitExpr::Value(walrus::ir::Value::I32(0)),
+    );
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .i32_const(2)
+        .binop(BinaryOp::I32Add);
+    let arithmetic = builder.finish(vec![], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .global_get(global)
+        .memory_grow(memory)
+        .global_set(global);
+    let grows = builder.finish(vec![], &mut module.funcs);
+
+    let usage = module.function_resource_usage();
+    assert_eq!(usage.len(), 2);
+    assert!(usage[&arithmetic].is_empty());
+    let grows = &usage[&grows];
+    assert!(grows.memories_read.is_empty());
+    assert_eq!(grows.memories_written.iter().collect::<Vec<_>>(), [&memory]);
+    assert_eq!(grows.globals_read.iter().collect::<Vec<_>>(), [&global]);
+    assert_eq!(grows.globals_written.iter().collect::<Vec<_>>(), [&global]);
+    assert!(grows.tables_read.is_empty() && grows.tables_written.is_empty());
+}
+
+#[test]
+fn function_type_closure() {
+    let mut module = Module::default();
+    let table = module.tables.add_local(1, None, ValType::Funcref);
+    let indirect = module.types.add(&[ValType::I32], &[ValType::I32]);
+    let pair = module.types.add(&[], &[ValType::I32, ValType::I32]);
+    let unrelated = module.types.add(&[ValType::F32], &[]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .block(pair, |block| {
+            block.i32_const(1).i32_const(2);
+        })
+        .call_indirect(indirect, table)
+        .drop()
+        .drop();
+    let f = builder.finish(vec![], &mut module.funcs);
+    let own = module.funcs.get(f).ty();
+
+    let closure = module.function_type_closure(f);
+    assert_eq!(closure.len(), 3);
+    for ty in [own, indirect, pair].iter() {
+        assert!(closure.contains(ty));
+    }
+    assert!(!closure.contains(&unrelated));
+
+    let (import, _) = module.add_import_func("env", "f", unrelated);
+    let closure = module.function_type_closure(import);
+    assert_eq!(closure.into_iter().collect::<Vec<_>>(), [unrelated]);
+}
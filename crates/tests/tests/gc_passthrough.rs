@@ -0,0 +1,67 @@
+//! Tests for preserving GC types with `ModuleConfig::gc_passthrough`.
+
+use walrus::{Module, ModuleConfig};
+
+const TYPE: u8 = 1;
+
+fn wasm() -> Vec<u8> {
+    wat::parse_str(
+        r#"
+            (module
+                (type $s (struct
+                    (field i32)
+                    (field (mut i64))
+                    (field i31ref)
+                    (field i8)))
+                (func (export "f") (param i32) (result i32)
+                    local.get 0))
+        "#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn rejected_by_default() {
+    assert!(Module::from_buffer(&wasm()).is_err());
+}
+
+#[test]
+fn struct_type_round_trips() -> anyhow::Result<()> {
+    let wasm = wasm();
+    let mut config = ModuleConfig::new();
+    config.gc_passthrough(true);
+    let mut module = config.parse(&wasm)?;
+    assert_eq!(module.types.iter().filter(|t| t.is_opaque()).count(), 1);
+
+    let (emitted, layout) = module.emit_wasm_with_layout();
+
+    // The type section is the first one `wat` emits, and is small enough for
+    // its size to fit in one byte, whereas walrus always pads it to five.
+    assert_eq!(wasm[8], TYPE);
+    let original = &wasm[10..10 + wasm[9] as usize];
+    let range = layout.section(TYPE).unwrap();
+    assert_eq!(&emitted[range.start + 6..range.end], original);
+
+    let module = config.parse(&emitted)?;
+    assert_eq!(module.types.iter().filter(|t| t.is_opaque()).count(), 1);
+    Ok(())
+}
+
+#[test]
+fn struct_type_is_not_a_function_signature() {
+    let mut config = ModuleConfig::new();
+    config.gc_passthrough(true);
+    let invalid = [
+        r#"(module (type (struct)) (func (type 0)))"#,
+        r#"(module (type (struct)) (import "a" "b" (func (type 0))))"#,
+        r#"(module
+            (type (struct))
+            (table 1 funcref)
+            (func i32.const 0 call_indirect (type 0)))"#,
+        r#"(module (type (struct)) (func block (type 0) end))"#,
+    ];
+    for wat in invalid.iter() {
+        let wasm = wat::parse_str(wat).unwrap();
+        assert!(config.parse(&wasm).is_err(), "{}", wat);
+    }
+}
@@ -0,0 +1,168 @@
+//! Tests for `Module::inline_small_functions`.
+
+use walrus::ir::{BinaryOp, Instr, InstrSeqType};
+use walrus::{FunctionBuilder, InitExpr, Module, ValType};
+use walrus_tests::testutils::entry_instr_names;
+
+#[test]
+fn inlines_accessor_into_callers() {
+    let mut module = Module::default();
+    let global = module.globals.add_local(
+        ValType::I32,
+        true,
+        InitExpr::Value(walrus::ir::Value::I32(7)),
+    );
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().global_get(global);
+    let get = builder.finish(vec![], &mut module.funcs);
+
+    let mut callers = Vec::new();
+    for i in 0..3 {
+        let x = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        builder
+            .func_body()
+            .call(get)
+            .local_get(x)
+            .binop(BinaryOp::I32Add)
+            .call(get)
+            .binop(BinaryOp::I32Mul);
+        callers.push(builder.finish(vec![x], &mut module.funcs));
+        module
+            .exports
+            .add(&format!("caller{}", i), *callers.last().unwrap());
+    }
+
+    module.inline_small_functions(2);
+    for caller in callers {
+        assert_eq!(
+            entry_instr_names(&module, caller),
+            ["block", "local_get", "binop", "block", "binop"]
+        );
+    }
+
+    // The accessor is no longer used.
+    walrus::passes::gc::run(&mut module);
+    assert_eq!(module.funcs.iter().count(), 3);
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn inlines_arguments_locals_and_returns() {
+    let mut module = Module::default();
+    let a = module.locals.add(ValType::I32);
+    let b = module.locals.add(ValType::I32);
+    let tmp = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32],
+    );
+    builder
+        .func_body()
+        .local_get(tmp)
+        .local_get(a)
+        .binop(BinaryOp::I32Add)
+        .local_set(tmp)
+        .local_get(tmp)
+        .local_get(b)
+        .binop(BinaryOp::I32Sub)
+        .return_();
+    let sub = builder.finish(vec![a, b], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(5)
+        .i32_const(3)
+        .call(sub)
+        .i32_const(1)
+        .i32_const(1)
+        .call(sub)
+        .binop(BinaryOp::I32Add);
+    let caller = builder.finish(vec![], &mut module.funcs);
+
+    let before = module.locals.iter().count();
+    module.inline_small_functions(100);
+    // Each copy gets one new local per argument and one for `tmp`.
+    assert_eq!(module.locals.iter().count(), before + 6);
+
+    // The arguments are stored before entering a block without parameters.
+    assert_eq!(
+        entry_instr_names(&module, caller),
+        [
+            "const",
+            "const",
+            "local_set",
+            "local_set",
+            "block",
+            "const",
+            "const",
+            "local_set",
+            "local_set",
+            "block",
+            "binop"
+        ]
+    );
+    let func = module.funcs.get(caller).kind.unwrap_local();
+    let blocks = func
+        .block(func.entry_block())
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::Block(block) => Some(func.block(block.seq)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks
+        .iter()
+        .all(|block| block.ty == InstrSeqType::Simple(Some(ValType::I32))));
+
+    // Each copy has its own locals, and `tmp` is zeroed first.
+    let locals = |seq: &walrus::ir::InstrSeq| {
+        seq.iter()
+            .filter_map(|(instr, _)| match instr {
+                Instr::LocalSet(set) => Some(set.local),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let (first, second) = (locals(blocks[0]), locals(blocks[1]));
+    assert_eq!(first.len(), 2);
+    assert!(first.iter().all(|l| !second.contains(l)));
+    assert!(!first.contains(&a) && !first.contains(&tmp));
+    assert!(matches!(blocks[0][0].0, Instr::Const(_)));
+    assert!(matches!(blocks[0].last().unwrap().0, Instr::Br(_)));
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn skips_recursive_and_large_functions() {
+    let mut module = Module::default();
+    let ty = module.types.add(&[], &[]);
+    let recursive = module.add_stub_function(ty);
+    module
+        .funcs
+        .get_mut(recursive)
+        .kind
+        .unwrap_local_mut()
+        .builder_mut()
+        .func_body()
+        .instr_at(0, walrus::ir::Call { func: recursive });
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().i32_const(1).drop().i32_const(2).drop();
+    let large = builder.finish(vec![], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().call(recursive).call(large);
+    let caller = builder.finish(vec![], &mut module.funcs);
+
+    module.inline_small_functions(4);
+    assert_eq!(entry_instr_names(&module, caller), ["call", "call"]);
+    module.inline_small_functions(5);
+    assert_eq!(entry_instr_names(&module, caller), ["call", "block"]);
+}
@@ -0,0 +1,209 @@
+//! Tests for the instruction helpers in `walrus::ir`.
+
+use walrus::ir::*;
+use walrus::{FunctionBuilder, InitExpr, Module, ValType};
+
+#[test]
+fn v128_i8x16_lanes() {
+    let lanes = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, -1];
+    let value = Value::v128_from_i8x16(lanes);
+    match value {
+        Value::V128(n) => assert_eq!(n, 0xff0e0d0c_0b0a0908_07060504_03020100),
+        _ => unreachable!(),
+    }
+    assert_eq!(value.v128_lanes_i8x16(), Some(lanes));
+    assert_eq!(
+        value.v128_lanes_i32x4(),
+        Some([0x03020100, 0x07060504, 0x0b0a0908, 0xff0e0d0c_u32 as i32])
+    );
+}
+
+#[test]
+fn v128_f64x2_lanes() {
+    let lanes = [1.5, -0.25];
+    let value = Value::v128_from_f64x2(lanes);
+    assert_eq!(value.v128_lanes_f64x2(), Some(lanes));
+    assert_eq!(
+        value.v128_lanes_i64x2(),
+        Some([1.5f64.to_bits() as i64, (-0.25f64).to_bits() as i64])
+    );
+    assert_eq!(Value::I32(1).v128_lanes_f64x2(), None);
+}
+
+#[test]
+fn immediates() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+
+    let arg = MemArg {
+        align: 4,
+        offset: 16,
+    };
+    let load = Instr::Load(Load {
+        memory,
+        kind: LoadKind::I32 { atomic: false },
+        arg,
+    });
+    match load.immediates()[..] {
+        [Immediate::Memory(m), Immediate::MemArg(a)] => {
+            assert_eq!(m, memory);
+            assert_eq!((a.align, a.offset), (4, 16));
+        }
+        ref other => panic!("unexpected immediates: {:?}", other),
+    }
+
+    let value = Instr::Const(Const {
+        value: Value::I64(-42),
+    });
+    match value.immediates()[..] {
+        [Immediate::Value(Value::I64(-42))] => {}
+        ref other => panic!("unexpected immediates: {:?}", other),
+    }
+
+    let lane = Instr::Unop(Unop {
+        op: UnaryOp::I32x4ExtractLane { idx: 3 },
+    });
+    match lane.immediates()[..] {
+        [Immediate::Lane(3)] => {}
+        ref other => panic!("unexpected immediates: {:?}", other),
+    }
+    assert!(Instr::Drop(Drop {}).immediates().is_empty());
+}
+
+#[test]
+fn table_copy_immediates() {
+    let mut module = Module::default();
+    let src = module.tables.add_local(1, None, ValType::Funcref);
+    let dst = module.tables.add_local(1, None, ValType::Funcref);
+    let copy = Instr::TableCopy(TableCopy { src, dst });
+    match copy.immediates()[..] {
+        [Immediate::Table(a), Immediate::Table(b)] => assert_eq!((a, b), (dst, src)),
+        ref other => panic!("unexpected immediates: {:?}", other),
+    }
+}
+
+#[test]
+fn has_side_effects() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let global = module
+        .globals
+        .add_local(ValType::I32, true, InitExpr::Value(Value::I32(0)));
+    let func = FunctionBuilder::new(&mut module.types, &[], &[]).finish(vec![], &mut module.funcs);
+    let local = module.locals.add(ValType::I32);
+    let arg = MemArg {
+        align: 4,
+        offset: 0,
+    };
+
+    let pure: Vec<Instr> = vec![
+        Const {
+            value: Value::I32(1),
+        }
+        .into(),
+        LocalGet { local }.into(),
+        GlobalGet { global }.into(),
+        Binop {
+            op: BinaryOp::I32Add,
+        }
+        .into(),
+        Unop {
+            op: UnaryOp::F64Neg,
+        }
+        .into(),
+        MemorySize { memory }.into(),
+        Drop {}.into(),
+    ];
+    for instr in pure {
+        assert!(!instr.has_side_effects(), "{:?} is pure", instr);
+    }
+
+    let effectful: Vec<Instr> = vec![
+        Store {
+            memory,
+            kind: StoreKind::I32 { atomic: false },
+            arg,
+        }
+        .into(),
+        Load {
+            memory,
+            kind: LoadKind::I32 { atomic: false },
+            arg,
+        }
+        .into(),
+        Call { func }.into(),
+        MemoryGrow { memory }.into(),
+        GlobalSet { global }.into(),
+        LocalSet { local }.into(),
+        Binop {
+            op: BinaryOp::I32DivU,
+        }
+        .into(),
+        Unop {
+            op: UnaryOp::I32TruncSF32,
+        }
+        .into(),
+        Unreachable {}.into(),
+    ];
+    for instr in effectful {
+        assert!(instr.has_side_effects(), "{:?} has side effects", instr);
+    }
+}
+
+#[test]
+fn stack_effect() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I64, ValType::F32],
+        &[ValType::F64],
+    );
+    builder.func_body().f64_const(1.0);
+    let args = vec![
+        module.locals.add(ValType::I32),
+        module.locals.add(ValType::I64),
+        module.locals.add(ValType::F32),
+    ];
+    let func = builder.finish(args, &mut module.funcs);
+
+    assert_eq!(Instr::Call(Call { func }).stack_effect(&module), (3, 1));
+    assert_eq!(Instr::Drop(Drop {}).stack_effect(&module), (1, 0));
+    let value = Value::I32(1);
+    assert_eq!(Instr::Const(Const { value }).stack_effect(&module), (0, 1));
+    for ty in [None, Some(ValType::I64)].iter() {
+        let select = Instr::Select(Select { ty: *ty });
+        assert_eq!(select.stack_effect(&module), (3, 1));
+    }
+}
+
+#[test]
+fn branches_and_terminators() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let block = builder.func_body().id();
+    let instrs = [
+        (Instr::Br(Br { block }), true, true),
+        (Instr::BrIf(BrIf { block }), true, false),
+        (
+            Instr::BrTable(BrTable {
+                blocks: Box::new([]),
+                default: block,
+            }),
+            true,
+            true,
+        ),
+        (Instr::Return(Return {}), false, true),
+        (Instr::Unreachable(Unreachable {}), false, true),
+        (Instr::Block(Block { seq: block }), false, false),
+        (Instr::Drop(Drop {}), false, false),
+    ];
+    for (instr, branch, terminator) in instrs.iter() {
+        assert_eq!(instr.is_branch(), *branch, "{:?}", instr);
+        assert_eq!(
+            instr.is_unconditional_terminator(),
+            *terminator,
+            "{:?}",
+            instr
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Tests for `Module::instrument_memory_accesses`.
+
+use walrus::ir::{Call, Instr, LoadKind, MemArg, StoreKind};
+use walrus::{FunctionBuilder, FunctionId, Module, ValType};
+use walrus_tests::testutils::{entry_instr_names, entry_instrs};
+
+fn add_hook(module: &mut Module) -> FunctionId {
+    let params = [ValType::I32, ValType::I32, ValType::I32];
+    let builder = FunctionBuilder::new(&mut module.types, &params, &[]);
+    builder.finish(vec![], &mut module.funcs)
+}
+
+#[test]
+fn calls_hooks_before_accesses() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let on_load = add_hook(&mut module);
+    let on_store = add_hook(&mut module);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let arg = |offset| MemArg { align: 4, offset };
+    builder
+        .func_body()
+        .i32_const(16)
+        .i32_const(0)
+        .load(memory, LoadKind::I64 { atomic: false }, arg(8))
+        .store(memory, StoreKind::I64 { atomic: false }, arg(0));
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    module
+        .instrument_memory_accesses(on_load, on_store)
+        .unwrap();
+
+    assert_eq!(
+        entry_instr_names(&module, f),
+        [
+            "const",
+            // load
+            "const",
+            "local_tee",
+            "const",
+            "const",
+            "call",
+            "local_get",
+            "load",
+            // store
+            "local_set",
+            "local_tee",
+            "const",
+            "const",
+            "call",
+            "local_get",
+            "local_get",
+            "store",
+        ]
+    );
+    let hooks = entry_instrs(&module, f)
+        .into_iter()
+        .filter_map(|i| match i {
+            Instr::Call(Call { func }) => Some(func),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(hooks, [on_load, on_store]);
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn rejects_hooks_of_the_wrong_type() {
+    let mut module = Module::default();
+    let hook = add_hook(&mut module);
+    let builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+    let bad = builder.finish(vec![], &mut module.funcs);
+    assert!(module.instrument_memory_accesses(hook, bad).is_err());
+}
@@ -0,0 +1,6 @@
+;; A branch to a block must carry the block's results, not its parameters.
+(module
+  (func (result f32)
+    i32.const 0
+    (block $b (param i32) (result f32)
+      br $b)))
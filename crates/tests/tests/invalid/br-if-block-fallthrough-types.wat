@@ -0,0 +1,10 @@
+;; The values a `br_if` leaves on the stack have its label's types.
+(module
+  (func (param i32) (result i32 i64)
+    (block $b (result i32 i64)
+      i32.const 1
+      i64.const 2
+      local.get 0
+      br_if $b
+      i32.const 3
+      i32.add)))
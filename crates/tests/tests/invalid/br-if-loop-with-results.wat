@@ -0,0 +1,9 @@
+;; A `br_if` to a loop must carry the loop's parameters, not its results.
+(module
+  (func (param i32) (result f32)
+    i32.const 0
+    (loop $l (param i32) (result f32)
+      drop
+      f32.const 1
+      local.get 0
+      br_if $l)))
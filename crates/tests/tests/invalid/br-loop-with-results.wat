@@ -0,0 +1,8 @@
+;; A branch to a loop must carry the loop's parameters, not its results.
+(module
+  (func (result f32)
+    i32.const 0
+    (loop $l (param i32) (result f32)
+      drop
+      f32.const 1
+      br $l)))
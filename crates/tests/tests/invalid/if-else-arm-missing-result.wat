@@ -0,0 +1,8 @@
+;; An `else` arm that leaves nothing on the stack doesn't produce the `if`'s
+;; result, even though the `then` arm does.
+(module
+  (func (param i32) (result i32)
+    local.get 0
+    (if (result i32)
+      (then (i32.const 1))
+      (else))))
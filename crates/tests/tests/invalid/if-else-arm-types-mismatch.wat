@@ -0,0 +1,7 @@
+;; Both arms of an `if` must produce its declared results.
+(module
+  (func (param i32) (result i32)
+    local.get 0
+    (if (result i32)
+      (then (i32.const 1))
+      (else (i64.const 2)))))
@@ -0,0 +1,10 @@
+;; A missing `else` passes the `if`'s parameters through unchanged, so they
+;; must match its results.
+(module
+  (func (param i32) (result i64)
+    i32.const 1
+    local.get 0
+    (if (param i32) (result i64)
+      (then
+        drop
+        i64.const 2))))
@@ -0,0 +1,8 @@
+(module
+  (func (export "f") (result externref)
+    unreachable
+    ref.null extern
+    i32.const 0
+    ;; Only typed `select`s may choose between references, even in unreachable
+    ;; code.
+    select))
@@ -0,0 +1,8 @@
+(module
+  (table $funcs 1 funcref)
+  (table $refs 1 externref)
+  (func (export "f")
+    i32.const 0
+    i32.const 0
+    i32.const 1
+    table.copy $refs $funcs))
@@ -0,0 +1,9 @@
+(module
+  (table $refs 1 externref)
+  (func $f)
+  (elem $e func $f)
+  (func (export "f")
+    i32.const 0
+    i32.const 0
+    i32.const 1
+    table.init $refs $e))
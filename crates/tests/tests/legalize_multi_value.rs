@@ -0,0 +1,177 @@
+//! Tests for lowering multi-value blocks to MVP blocks.
+
+use walrus::ir::{InstrSeq, InstrSeqType, Visitor};
+use walrus::Module;
+
+/// Collect the types of every instruction sequence in every local function,
+/// except for the functions' entry blocks.
+fn block_types(module: &Module) -> Vec<InstrSeqType> {
+    struct Types<'a> {
+        entry: walrus::ir::InstrSeqId,
+        types: &'a mut Vec<InstrSeqType>,
+    }
+
+    impl<'instr> Visitor<'instr> for Types<'_> {
+        fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
+            if seq.id() != self.entry {
+                self.types.push(seq.ty);
+            }
+        }
+    }
+
+    let mut types = Vec::new();
+    for (_, func) in module.funcs.iter_local() {
+        let mut visitor = Types {
+            entry: func.entry_block(),
+            types: &mut types,
+        };
+        walrus::ir::dfs_in_order(&mut visitor, func, func.entry_block());
+    }
+    types
+}
+
+/// Parse `wat`, lower it, and check that only MVP block types remain and that
+/// the result is still valid.
+fn legalize(wat: &str) -> Vec<InstrSeqType> {
+    let wasm = wat::parse_str(wat).unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    assert!(block_types(&module)
+        .iter()
+        .any(|ty| matches!(ty, InstrSeqType::MultiValue(_))));
+
+    module.legalize_multi_value();
+    let types = block_types(&module);
+    assert!(types.iter().all(|ty| matches!(ty, InstrSeqType::Simple(_))));
+
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(block_types(&module), types);
+    types
+}
+
+#[test]
+fn two_result_block() {
+    let types = legalize(
+        r#"
+        (module
+          (func (export "f") (result i32)
+            block (result i32 i32)
+              i32.const 1
+              i32.const 2
+            end
+            i32.add))
+        "#,
+    );
+    assert_eq!(types, [InstrSeqType::Simple(None)]);
+}
+
+#[test]
+fn two_result_block_with_branches() {
+    legalize(
+        r#"
+        (module
+          (func (export "f") (param i32) (result i32)
+            block (result i32 i32)
+              i32.const 1
+              i32.const 2
+              local.get 0
+              br_if 0
+              drop
+              drop
+              block
+                i32.const 3
+                i32.const 4
+                br 1
+              end
+              i32.const 5
+              i32.const 6
+              local.get 0
+              br_table 0 0
+            end
+            i32.sub))
+        "#,
+    );
+}
+
+#[test]
+fn block_with_params() {
+    let types = legalize(
+        r#"
+        (module
+          (func (export "f") (result i32)
+            i32.const 1
+            i32.const 2
+            block (param i32 i32) (result i32)
+              i32.add
+            end))
+        "#,
+    );
+    assert_eq!(types, [InstrSeqType::Simple(Some(walrus::ValType::I32))]);
+}
+
+#[test]
+fn loop_with_params() {
+    legalize(
+        r#"
+        (module
+          (func (export "f") (param i32) (result i32)
+            i32.const 0
+            local.get 0
+            loop (param i32 i32) (result i32)
+              i32.const 1
+              i32.sub
+              local.tee 0
+              i32.add
+              local.get 0
+              local.get 0
+              br_if 0
+              drop
+            end))
+        "#,
+    );
+}
+
+#[test]
+fn if_else_with_params_and_results() {
+    let types = legalize(
+        r#"
+        (module
+          (func (export "f") (param i32) (result i32)
+            i32.const 1
+            local.get 0
+            if (param i32) (result i32 i32)
+              i32.const 2
+            else
+              i32.const 3
+            end
+            i32.add))
+        "#,
+    );
+    assert_eq!(types.len(), 2);
+}
+
+#[test]
+fn br_table_to_function_body_is_left_alone() {
+    let wasm = wat::parse_str(
+        r#"
+        (module
+          (func (export "f") (param i32) (result i32 i32)
+            block (result i32 i32)
+              i32.const 1
+              i32.const 2
+              local.get 0
+              br_table 0 1
+            end))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    module.legalize_multi_value();
+    assert!(matches!(
+        block_types(&module)[..],
+        [InstrSeqType::MultiValue(_)]
+    ));
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
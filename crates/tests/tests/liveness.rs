@@ -0,0 +1,80 @@
+//! Tests for `LocalFunction::liveness`.
+
+use walrus::ir::BinaryOp;
+use walrus::{FunctionBuilder, Module, ValType};
+
+#[test]
+fn straight_line_lifetimes() {
+    let mut module = Module::default();
+    let a = module.locals.add(ValType::I32);
+    let b = module.locals.add(ValType::I32);
+    let c = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        // 0: `a` and `b` overlap...
+        .local_get(a)
+        .local_set(b)
+        .i32_const(1)
+        .local_get(b)
+        .binop(BinaryOp::I32Add)
+        .local_get(a)
+        .binop(BinaryOp::I32Add)
+        // 7: ...but neither overlaps `c`.
+        .local_set(c)
+        .local_get(c);
+    let f = builder.finish(vec![a], &mut module.funcs);
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let entry = func.entry_block();
+    let liveness = func.liveness();
+
+    let live = |local, range: std::ops::Range<usize>| {
+        range
+            .filter(|i| liveness.is_live(local, entry, *i))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(live(a, 0..10), [0, 1, 2, 3, 4, 5]);
+    assert_eq!(live(b, 0..10), [2, 3]);
+    assert_eq!(live(c, 0..10), [8]);
+    assert_eq!(liveness.max_live(), 2);
+    assert!(liveness.live_before(entry, 9).unwrap().is_empty());
+    assert!(liveness.live_before(entry, 10).is_none());
+}
+
+#[test]
+fn loop_keeps_locals_live() {
+    let mut module = Module::default();
+    let i = module.locals.add(ValType::I32);
+    let done = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    let mut body = None;
+    builder
+        .func_body()
+        .i32_const(0)
+        .local_set(done)
+        .loop_(None, |l| {
+            let id = l.id();
+            body = Some(id);
+            // `i` is read at the top of the loop, so it must stay live at
+            // the `br_if` that jumps back there.
+            l.local_get(i)
+                .i32_const(1)
+                .binop(BinaryOp::I32Add)
+                .local_set(i)
+                .local_get(i)
+                .br_if(id);
+        })
+        .local_get(done);
+    let f = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let entry = func.entry_block();
+    let body = body.unwrap();
+    let liveness = func.liveness();
+
+    assert!(liveness.is_live(i, body, 5));
+    assert!(liveness.is_live(done, body, 5));
+    assert!(!liveness.is_live(i, body, 3));
+    assert!(liveness.is_live(i, entry, 0));
+    assert!(!liveness.is_live(done, entry, 0));
+    assert!(liveness.is_live(done, entry, 2));
+}
@@ -0,0 +1,305 @@
+//! Tests for the analyses and rewrites on `LocalFunction`.
+
+use walrus::ir::{BinaryOp, Value};
+use walrus::{FunctionBuilder, Module, ValType};
+use walrus_tests::testutils::entry_instr_names;
+
+#[test]
+fn max_stack_depth_straight_line() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .i32_const(2)
+        .i32_const(3)
+        .binop(BinaryOp::I32Add)
+        .binop(BinaryOp::I32Add)
+        .drop();
+    let id = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(id).kind.unwrap_local();
+    assert_eq!(func.max_stack_depth(&module), 3);
+}
+
+#[test]
+fn max_stack_depth_nested_blocks() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .block(ValType::I32, |block| {
+            block.i32_const(0).if_else(
+                None,
+                |then| {
+                    then.i32_const(2)
+                        .i32_const(3)
+                        .i32_const(4)
+                        .drop()
+                        .drop()
+                        .drop();
+                },
+                |else_| {
+                    else_.i32_const(5).drop();
+                },
+            );
+            block.i32_const(6);
+        })
+        .drop()
+        .drop();
+    let id = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(id).kind.unwrap_local();
+    assert_eq!(func.max_stack_depth(&module), 4);
+}
+
+#[test]
+fn max_stack_depth_ignores_unreachable_code() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .drop()
+        .unreachable()
+        .i32_const(2)
+        .i32_const(3);
+    let id = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(id).kind.unwrap_local();
+    assert_eq!(func.max_stack_depth(&module), 1);
+}
+
+#[test]
+fn dead_code_ranges() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut inner = None;
+    builder
+        .func_body()
+        .block(None, |block| {
+            inner = Some(block.id());
+            let id = block.id();
+            block.br(id).i32_const(1).drop();
+        })
+        .i32_const(2)
+        .drop()
+        .return_()
+        .block(None, |block| {
+            block.unreachable().i32_const(3).drop();
+        })
+        .unreachable();
+    let entry = builder.func_body_id();
+    let id = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get_mut(id).kind.unwrap_local_mut();
+
+    let mut ranges = func.dead_code_ranges();
+    ranges.sort_by_key(|(seq, _)| *seq);
+    assert_eq!(ranges, [(entry, 4..6), (inner.unwrap(), 1..3)]);
+
+    func.remove_dead_code();
+    assert!(func.dead_code_ranges().is_empty());
+    assert_eq!(func.block(entry).len(), 4);
+    assert_eq!(func.block(inner.unwrap()).len(), 1);
+}
+
+#[test]
+fn self_tail_calls() {
+    let mut module = Module::default();
+    let n = module.locals.add(ValType::I32);
+    let builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    let f = builder.finish(vec![n], &mut module.funcs);
+
+    let mut inner = None;
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    func.append_to_entry(|body| {
+        body.block(None, |block| {
+            inner = Some(block.id());
+            // Not a tail call, since its result is used.
+            block
+                .local_get(n)
+                .call(f)
+                .i32_const(1)
+                .binop(BinaryOp::I32Add)
+                .drop();
+            block.local_get(n).call(f).return_();
+        })
+        .local_get(n)
+        .call(f);
+    });
+    let entry = func.entry_block();
+    assert_eq!(func.self_tail_calls(f), [(entry, 2), (inner.unwrap(), 6)]);
+}
+
+#[test]
+fn append_to_entry() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().i32_const(1);
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    func.append_to_entry(|body| {
+        body.i32_const(2).drop();
+    });
+    assert_eq!(func.size(), 3);
+
+    // The function still returns its `i32`.
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn metadata() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct TripCount(u32);
+
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut loop_id = None;
+    builder.func_body().loop_(None, |l| {
+        loop_id = Some(l.id());
+    });
+    let loop_id = loop_id.unwrap();
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    assert!(func.metadata::<TripCount>().is_none());
+    func.metadata_mut().insert(loop_id, TripCount(10));
+    let entry = func.entry_block();
+    func.metadata_mut().insert(entry, 1.5f64);
+
+    let clone = module.clone();
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    func.metadata_mut::<TripCount>()
+        .get_mut(&loop_id)
+        .unwrap()
+        .0 = 20;
+
+    let cloned = clone.funcs.get(f).kind.unwrap_local();
+    assert_eq!(
+        cloned.metadata::<TripCount>().unwrap().get(&loop_id),
+        Some(&TripCount(10))
+    );
+    assert_eq!(
+        cloned.metadata::<f64>().unwrap().get(&cloned.entry_block()),
+        Some(&1.5)
+    );
+    let func = module.funcs.get(f).kind.unwrap_local();
+    assert_eq!(
+        func.metadata::<TripCount>().unwrap().get(&loop_id),
+        Some(&TripCount(20))
+    );
+}
+
+#[test]
+fn constants() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::F64]);
+    builder.func_body().i32_const(7).if_else(
+        ValType::F64,
+        |then| {
+            then.f64_const(1.5);
+        },
+        |else_| {
+            else_.i32_const(-1).drop().f64_const(2.5);
+        },
+    );
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let constants = func.constants();
+    assert_eq!(constants.len(), 4);
+    assert!(matches!(constants[0], Value::I32(7)));
+    assert!(matches!(constants[1], Value::F64(x) if x == 1.5));
+    assert!(matches!(constants[2], Value::I32(-1)));
+    assert!(matches!(constants[3], Value::F64(x) if x == 2.5));
+}
+
+#[test]
+fn sink_local_gets() {
+    let mut module = Module::default();
+    let g = module
+        .globals
+        .add_local(ValType::I32, true, walrus::InitExpr::Value(Value::I32(0)));
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body();
+    let callee = builder.finish(vec![], &mut module.funcs);
+
+    let x = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        // Moved past the unrelated `global.set`, but not the `i32.const`
+        // that is the other operand of the `i32.add`.
+        .local_get(x)
+        .i32_const(5)
+        .global_set(g)
+        .i32_const(1)
+        .binop(BinaryOp::I32Add)
+        // Not moved past a write to the local.
+        .local_get(x)
+        .i32_const(5)
+        .local_set(x)
+        .binop(BinaryOp::I32Add)
+        // Not moved past a call.
+        .local_get(x)
+        .call(callee)
+        .binop(BinaryOp::I32Add);
+    let f = builder.finish(vec![x], &mut module.funcs);
+
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    func.sink_local_gets(&module.types);
+    assert_eq!(
+        entry_instr_names(&module, f),
+        [
+            "const",
+            "global_set",
+            "local_get",
+            "const",
+            "binop",
+            "local_get",
+            "const",
+            "local_set",
+            "binop",
+            "local_get",
+            "call",
+            "binop",
+        ]
+    );
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn instr_sizes() {
+    let mut module = Module::default();
+    let mut callees = vec![];
+    for _ in 0..200 {
+        let builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        callees.push(builder.finish(vec![], &mut module.funcs));
+    }
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .call(callees[0])
+        .call(callees[199])
+        .block(None, |block| {
+            block.i32_const(1).drop();
+        });
+    let f = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let entry = func.entry_block();
+    let sizes = func.instr_sizes(&module);
+    let block = func.block(entry)[2].0.unwrap_block().seq;
+    assert_eq!(
+        sizes,
+        [
+            ((entry, 0), 2),
+            ((entry, 1), 3),
+            ((entry, 2), 3),
+            ((block, 0), 2),
+            ((block, 1), 1),
+        ]
+    );
+}
@@ -0,0 +1,74 @@
+//! Tests for `ModuleLocals` and `LocalPool`.
+
+use walrus::{FunctionBuilder, LocalPool, Module, ModuleLocals, ValType};
+
+#[test]
+fn reuses_freed_scratch_locals() {
+    let mut locals = ModuleLocals::default();
+    let mut pool = LocalPool::new();
+
+    let a = pool.get_scratch(&mut locals, ValType::I32);
+    pool.free_scratch(a);
+    let b = pool.get_scratch(&mut locals, ValType::I32);
+    assert_eq!(a, b);
+
+    // Scratch locals in use, of other types, or fresh are not reused.
+    let c = pool.get_scratch(&mut locals, ValType::I32);
+    assert_ne!(b, c);
+    pool.free_scratch(b);
+    let d = pool.get_scratch(&mut locals, ValType::I64);
+    assert_ne!(b, d);
+    let e = pool.fresh(&mut locals, ValType::I32);
+    assert_ne!(b, e);
+    assert_eq!(locals.iter().count(), 4);
+}
+
+#[test]
+#[should_panic(expected = "can only free scratch locals that are in use")]
+fn double_free() {
+    let mut locals = ModuleLocals::default();
+    let mut pool = LocalPool::new();
+    let a = pool.get_scratch(&mut locals, ValType::F32);
+    pool.free_scratch(a);
+    pool.free_scratch(a);
+}
+
+#[test]
+fn local_names_survive_round_trip() {
+    let mut module = Module::default();
+    let x = module.locals.add(ValType::I32);
+    let wide = module.locals.add(ValType::I64);
+    let narrow = module.locals.add(ValType::I32);
+    module.set_local_name(x, "x");
+    module.set_local_name(wide, "wide");
+    module.set_local_name(narrow, "narrow");
+    assert_eq!(module.local_name(wide), Some("wide"));
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+    builder
+        .func_body()
+        .i64_const(1)
+        .local_set(wide)
+        .local_get(x)
+        .local_set(narrow);
+    let f = builder.finish(vec![x], &mut module.funcs);
+    module.exports.add("f", f);
+
+    // `narrow` is emitted before `wide` since locals are grouped by type,
+    // but the names follow the locals.
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let names = module
+        .locals
+        .iter()
+        .map(|local| (module.local_name(local.id()).unwrap(), local.ty()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("x", ValType::I32),
+            ("narrow", ValType::I32),
+            ("wide", ValType::I64)
+        ]
+    );
+}
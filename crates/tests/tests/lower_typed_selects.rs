@@ -0,0 +1,96 @@
+//! Tests for `Module::lower_typed_selects` and `Module::lower_reference_selects`.
+
+use walrus::ir::{IfElse, Instr, Select};
+use walrus::{FunctionBuilder, FunctionId, InstrSeqBuilder, Module, ValType};
+use walrus_tests::testutils::{block_instrs, entry_instr_names, entry_instrs};
+
+fn add_select(
+    module: &mut Module,
+    ty: ValType,
+    operand: impl Fn(&mut InstrSeqBuilder),
+) -> FunctionId {
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ty]);
+    let mut body = builder.func_body();
+    operand(&mut body);
+    operand(&mut body);
+    body.i32_const(1).select(Some(ty));
+    builder.finish(vec![], &mut module.funcs)
+}
+
+fn select_ty(module: &Module, func: FunctionId) -> Option<ValType> {
+    match entry_instrs(module, func).last() {
+        Some(Instr::Select(Select { ty })) => *ty,
+        _ => panic!("expected a select"),
+    }
+}
+
+#[test]
+fn lowers_numeric_selects() {
+    let mut module = Module::default();
+    let f = add_select(&mut module, ValType::F64, |body| {
+        body.f64_const(1.0);
+    });
+    module.lower_typed_selects();
+    assert_eq!(select_ty(&module, f), None);
+}
+
+#[test]
+fn keeps_reference_selects() {
+    let mut module = Module::default();
+    let f = add_select(&mut module, ValType::Externref, |body| {
+        body.ref_null(ValType::Externref);
+    });
+    module.lower_typed_selects();
+    assert_eq!(select_ty(&module, f), Some(ValType::Externref));
+}
+
+#[test]
+fn lowers_reference_selects_to_if_else() {
+    let mut module = Module::default();
+    let refs = add_select(&mut module, ValType::Externref, |body| {
+        body.ref_null(ValType::Externref);
+    });
+    let ints = add_select(&mut module, ValType::I32, |body| {
+        body.i32_const(1);
+    });
+    module.lower_reference_selects();
+    assert_eq!(select_ty(&module, ints), Some(ValType::I32));
+
+    assert_eq!(
+        entry_instr_names(&module, refs),
+        [
+            "ref_null",
+            "ref_null",
+            "const",
+            "local_set",
+            "local_set",
+            "local_set",
+            "local_get",
+            "if_else"
+        ]
+    );
+    let body = entry_instrs(&module, refs);
+    let (a, b) = match (&body[4], &body[5]) {
+        (Instr::LocalSet(b), Instr::LocalSet(a)) => (a.local, b.local),
+        _ => unreachable!(),
+    };
+    match &body[7] {
+        Instr::IfElse(IfElse {
+            consequent,
+            alternative,
+        }) => {
+            assert!(
+                matches!(&block_instrs(&module, refs, *consequent)[..], [Instr::LocalGet(g)] if g.local == a)
+            );
+            assert!(
+                matches!(&block_instrs(&module, refs, *alternative)[..], [Instr::LocalGet(g)] if g.local == b)
+            );
+        }
+        other => panic!("expected an if/else, found {:?}", other),
+    }
+    assert_eq!(module.locals.get(a).ty(), ValType::Externref);
+
+    // The lowered function is still valid.
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
@@ -0,0 +1,60 @@
+//! Tests for emitting the limits of 64-bit memories.
+
+use walrus::Module;
+
+const MEMORY: u8 = 5;
+
+/// The memory section's payload, after walrus' padded five byte size.
+fn memory_section(module: &mut Module) -> Vec<u8> {
+    let (wasm, layout) = module.emit_wasm_with_layout();
+    let range = layout.section(MEMORY).unwrap();
+    wasm[range.start + 6..range.end].to_vec()
+}
+
+// The version of `wasmparser` walrus uses can't parse 64-bit memories yet, so
+// these only check how they are encoded.
+#[test]
+fn large_limits_are_encoded_as_u64() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1 << 32, Some(1 << 40));
+    module.memories.get_mut(memory).memory64 = true;
+    assert!(module.validate_all().is_empty());
+
+    // One memory with a maximum and 64-bit indices, whose limits are wider
+    // than a `u32`.
+    let mut expected = vec![1, 0x05];
+    expected.extend(&[0x80, 0x80, 0x80, 0x80, 0x10]);
+    expected.extend(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x20]);
+    assert_eq!(memory_section(&mut module), expected);
+}
+
+#[test]
+fn memory32_limits_are_unchanged() {
+    let mut module = Module::default();
+    module.memories.add_local(false, 1, None);
+    assert_eq!(memory_section(&mut module), [1, 0x00, 1]);
+}
+
+#[test]
+fn memory32_limits_must_fit_in_u32() {
+    let mut module = Module::default();
+    module.memories.add_local(false, 1 << 32, None);
+    assert!(!module.validate_all().is_empty());
+    let err = module.try_emit_wasm().unwrap_err();
+    assert!(format!("{:?}", err).contains("invalid limits"), "{:?}", err);
+}
+
+#[test]
+fn shared_memory64_can_exceed_memory32_limit() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let memory = module.memories.get_mut(memory);
+    assert!(memory.set_shared(1 << 20).is_err());
+
+    memory.memory64 = true;
+    assert!(memory.set_shared((1 << 48) + 1).is_err());
+    memory.set_shared(1 << 20)?;
+    assert_eq!(memory.maximum, Some(1 << 20));
+    assert!(module.validate_all().is_empty());
+    Ok(())
+}
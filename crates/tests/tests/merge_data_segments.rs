@@ -0,0 +1,83 @@
+//! Tests for `Module::merge_data_segments`.
+
+use walrus::{ActiveData, ActiveDataLocation, DataKind, FunctionBuilder, MemoryId, Module};
+
+fn add_active(module: &mut Module, memory: MemoryId, offset: u32, value: &[u8]) {
+    let kind = DataKind::Active(ActiveData {
+        memory,
+        location: ActiveDataLocation::Absolute(offset),
+    });
+    module.data.add(kind, value.to_vec());
+}
+
+fn segments(module: &Module) -> Vec<(Option<u32>, Vec<u8>)> {
+    module
+        .data
+        .iter()
+        .map(|data| {
+            let offset = match &data.kind {
+                DataKind::Active(ActiveData {
+                    location: ActiveDataLocation::Absolute(offset),
+                    ..
+                }) => Some(*offset),
+                _ => None,
+            };
+            (offset, data.value.clone())
+        })
+        .collect()
+}
+
+#[test]
+fn merges_adjacent_segments() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    add_active(&mut module, memory, 4, &[3, 4]);
+    add_active(&mut module, memory, 0, &[1, 2, 3, 4]);
+    add_active(&mut module, memory, 6, &[5]);
+    add_active(&mut module, memory, 8, &[6]);
+
+    module.merge_data_segments();
+    assert_eq!(
+        segments(&module),
+        vec![(Some(0), vec![1, 2, 3, 4, 3, 4, 5]), (Some(8), vec![6])]
+    );
+
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.data.iter().count(), 2);
+}
+
+#[test]
+fn skips_passive_and_referenced_segments() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    add_active(&mut module, memory, 0, &[1]);
+    module.data.add(DataKind::Passive, vec![2]);
+    add_active(&mut module, memory, 1, &[3]);
+    add_active(&mut module, memory, 2, &[4]);
+    let dropped = module.data.iter().last().unwrap().id();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().data_drop(dropped);
+    builder.finish(vec![], &mut module.funcs);
+
+    module.merge_data_segments();
+    assert_eq!(
+        segments(&module),
+        vec![(Some(0), vec![1, 3]), (None, vec![2]), (Some(2), vec![4])]
+    );
+}
+
+#[test]
+fn skips_segments_with_overlapping_segment_in_between() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    add_active(&mut module, memory, 0, &[1, 1]);
+    add_active(&mut module, memory, 1, &[2, 2]);
+    add_active(&mut module, memory, 2, &[3]);
+
+    // Merging the first and last segments would let the middle one
+    // overwrite the last one's byte, so nothing can be merged.
+    module.merge_data_segments();
+    assert_eq!(segments(&module).len(), 3);
+}
@@ -0,0 +1,78 @@
+//! Tests for building and emitting instructions that target a memory other
+//! than the first one.
+
+use walrus::ir::{Instr, MemArg, StoreKind};
+use walrus::{FunctionBuilder, Module, ModuleConfig, ValType};
+
+#[test]
+fn store_into_memory_1() {
+    let mut module = Module::default();
+    let _mem0 = module.memories.add_local(false, 1, None);
+    let mem1 = module.memories.add_local(false, 1, None);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().i32_const(0).i32_const(42).store(
+        mem1,
+        StoreKind::I32 { atomic: false },
+        MemArg {
+            align: 4,
+            offset: 8,
+        },
+    );
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    match &func.block(func.entry_block())[2].0 {
+        Instr::Store(s) => assert_eq!(s.memory, mem1),
+        other => panic!("expected a store, found {:?}", other),
+    }
+
+    // i32.store with the multi-memory flag set in the alignment, then memory
+    // index 1 and offset 8.
+    let wasm = module.emit_wasm();
+    let expected = [0x36, 0x42, 0x01, 0x08];
+    assert!(
+        wasm.windows(expected.len()).any(|w| w == expected),
+        "emitted wasm should contain a store into memory 1"
+    );
+}
+
+/// Build a module with two memories and a function that grows the second.
+fn grow_memory_1() -> Vec<u8> {
+    let mut module = Module::default();
+    let _mem0 = module.memories.add_local(false, 1, None);
+    let mem1 = module.memories.add_local(false, 1, None);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().i32_const(1).memory_grow(mem1);
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+    module.emit_wasm()
+}
+
+#[test]
+fn memory_grow_on_memory_1() {
+    let wasm = grow_memory_1();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let mem1 = module.memories.iter().nth(1).unwrap().id();
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    match &func.block(func.entry_block())[1].0 {
+        Instr::MemoryGrow(g) => assert_eq!(g.memory, mem1),
+        other => panic!("expected a memory.grow, found {:?}", other),
+    }
+}
+
+#[test]
+fn memory_grow_on_memory_1_requires_multi_memory() {
+    let wasm = grow_memory_1();
+    let err = ModuleConfig::new()
+        .only_stable_features(true)
+        .parse(&wasm)
+        .unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("reserved byte isn't zero"),
+        "unexpected error: {:?}",
+        err
+    );
+}
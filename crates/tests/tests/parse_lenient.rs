@@ -0,0 +1,68 @@
+//! Tests for `Module::parse_lenient`.
+
+use walrus::Module;
+
+fn wasm() -> Vec<u8> {
+    wat::parse_str(
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (global (export "g") i32 (i32.const 42))
+                (func (export "f") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add)
+                (func (export "h")))
+        "#,
+    )
+    .unwrap()
+}
+
+/// The offset of the section with the given id in a small module, whose
+/// section sizes all fit in one byte.
+fn section_start(wasm: &[u8], id: u8) -> usize {
+    let mut pos = 8;
+    while wasm[pos] != id {
+        assert!(wasm[pos + 1] < 0x80);
+        pos += 2 + wasm[pos + 1] as usize;
+    }
+    pos
+}
+
+#[test]
+fn complete_module() -> anyhow::Result<()> {
+    let wasm = wasm();
+    let (module, offset) = Module::parse_lenient(&wasm)?;
+    assert_eq!(offset, wasm.len());
+    assert_eq!(module.funcs.iter_local().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn truncated_code_section() -> anyhow::Result<()> {
+    let mut wasm = wasm();
+    let code = section_start(&wasm, 10);
+    wasm.truncate(code + 6);
+    assert!(Module::from_buffer(&wasm).is_err());
+
+    let (mut module, offset) = Module::parse_lenient(&wasm)?;
+    assert_eq!(offset, code);
+
+    // Everything before the code section is recovered.
+    assert_eq!(module.memories.iter().count(), 1);
+    assert_eq!(module.globals.iter().count(), 1);
+    let exports = module
+        .exports
+        .iter()
+        .map(|e| &e.name[..])
+        .collect::<Vec<_>>();
+    assert_eq!(exports, ["memory", "g", "f", "h"]);
+
+    // The functions are still there, with placeholder bodies.
+    for (_, func) in module.funcs.iter_local() {
+        let body = func.block(func.entry_block());
+        assert!(body.len() == 1 && body[0].0.is_unreachable());
+    }
+    Module::from_buffer(&module.emit_wasm())?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Tests for `Module::polyfill`.
+
+use walrus::ir::{BinaryOp, Binop, Const, Instr, UnaryOp, Value};
+use walrus::passes::FeatureSet;
+use walrus::{FunctionBuilder, Module, ValType};
+use walrus_tests::testutils::entry_instrs;
+
+/// Build a function that applies `op` to its argument, polyfill it, and
+/// return the resulting body.
+fn polyfilled(ty: ValType, op: UnaryOp) -> Vec<Instr> {
+    let mut module = Module::default();
+    let arg = module.locals.add(ty);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ty], &[ty]);
+    builder.func_body().local_get(arg).unop(op);
+    let f = builder.finish(vec![arg], &mut module.funcs);
+
+    module.polyfill(FeatureSet::mvp());
+
+    entry_instrs(&module, f)
+}
+
+/// Evaluate a polyfilled body with the given argument.
+fn eval(instrs: &[Instr], arg: Value) -> Value {
+    let mut stack = vec![];
+    for instr in instrs {
+        match instr {
+            Instr::LocalGet(_) => stack.push(arg),
+            Instr::Const(Const { value }) => stack.push(*value),
+            Instr::Binop(Binop { op }) => {
+                let b = stack.pop().unwrap();
+                let a = stack.pop().unwrap();
+                stack.push(match (op, a, b) {
+                    (BinaryOp::I32Shl, Value::I32(a), Value::I32(b)) => Value::I32(a << b),
+                    (BinaryOp::I32ShrS, Value::I32(a), Value::I32(b)) => Value::I32(a >> b),
+                    (BinaryOp::I64Shl, Value::I64(a), Value::I64(b)) => Value::I64(a << b),
+                    (BinaryOp::I64ShrS, Value::I64(a), Value::I64(b)) => Value::I64(a >> b),
+                    other => panic!("unexpected binop {:?}", other),
+                });
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+    }
+    assert_eq!(stack.len(), 1);
+    stack.pop().unwrap()
+}
+
+fn assert_i32(op: UnaryOp, native: impl Fn(i32) -> i32) {
+    let body = polyfilled(ValType::I32, op);
+    assert!(!body.iter().any(|i| i.is_unop()));
+    for &x in &[
+        0,
+        1,
+        0x7f,
+        0x80,
+        0xff,
+        0x7fff,
+        0x8000,
+        0x1234_5680,
+        -1,
+        i32::MIN,
+    ] {
+        match eval(&body, Value::I32(x)) {
+            Value::I32(y) => assert_eq!(y, native(x), "{:?} of {:#x}", op, x),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
+
+fn assert_i64(op: UnaryOp, native: impl Fn(i64) -> i64) {
+    let body = polyfilled(ValType::I64, op);
+    assert!(!body.iter().any(|i| i.is_unop()));
+    for &x in &[
+        0,
+        1,
+        0x80,
+        0x8000,
+        0x8000_0000,
+        0x1234_5678_9abc_def0,
+        -1,
+        i64::MIN,
+    ] {
+        match eval(&body, Value::I64(x)) {
+            Value::I64(y) => assert_eq!(y, native(x), "{:?} of {:#x}", op, x),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn sign_extension() {
+    assert_i32(UnaryOp::I32Extend8S, |x| x as i8 as i32);
+    assert_i32(UnaryOp::I32Extend16S, |x| x as i16 as i32);
+    assert_i64(UnaryOp::I64Extend8S, |x| x as i8 as i64);
+    assert_i64(UnaryOp::I64Extend16S, |x| x as i16 as i64);
+    assert_i64(UnaryOp::I64Extend32S, |x| x as i32 as i64);
+}
+
+#[test]
+fn supported_features_are_left_alone() {
+    let mut module = Module::default();
+    let arg = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        .local_get(arg)
+        .unop(UnaryOp::I32Extend8S);
+    let f = builder.finish(vec![arg], &mut module.funcs);
+
+    module.polyfill(FeatureSet::all());
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    assert_eq!(func.size(), 2);
+}
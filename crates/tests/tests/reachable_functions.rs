@@ -0,0 +1,82 @@
+//! Tests for `Module::reachable_functions`.
+
+use std::collections::BTreeSet;
+use walrus::Module;
+
+fn reachable(wat: &str, roots: &[&str]) -> BTreeSet<String> {
+    let wasm = wat::parse_str(wat).unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let roots = roots
+        .iter()
+        .map(|name| {
+            module
+                .exports
+                .iter()
+                .find(|e| e.name == *name)
+                .unwrap()
+                .id()
+        })
+        .collect::<Vec<_>>();
+    module
+        .reachable_functions(&roots)
+        .into_iter()
+        .map(|f| module.funcs.get(f).name.clone().unwrap())
+        .collect()
+}
+
+const WAT: &str = r#"
+    (module
+      (type $t (func))
+      (import "env" "imported" (func $imported))
+      (table $table 2 funcref)
+      (elem (table $table) (i32.const 0) func $in_table)
+      (elem $passive func $in_passive)
+      (elem declare func $referenced)
+      (func $a (export "a")
+        call $b
+        ref.func $referenced
+        drop)
+      (func $b
+        call $imported)
+      (func $referenced)
+      (func $indirect (export "indirect")
+        i32.const 0
+        call_indirect (type $t))
+      (func $in_table)
+      (func $init (export "init")
+        i32.const 1
+        i32.const 0
+        i32.const 1
+        table.init $table $passive)
+      (func $in_passive)
+      (func $unused
+        call $a)
+      (export "table" (table $table)))
+"#;
+
+fn names(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn calls_and_ref_func() {
+    assert_eq!(
+        reachable(WAT, &["a"]),
+        names(&["a", "b", "imported", "referenced"])
+    );
+}
+
+#[test]
+fn call_indirect() {
+    assert_eq!(
+        reachable(WAT, &["indirect"]),
+        names(&["indirect", "in_table"])
+    );
+}
+
+#[test]
+fn table_init_and_exported_table() {
+    assert_eq!(reachable(WAT, &["init"]), names(&["init", "in_passive"]));
+    assert_eq!(reachable(WAT, &["table"]), names(&["in_table"]));
+    assert!(reachable(WAT, &[]).is_empty());
+}
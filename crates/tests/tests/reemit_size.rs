@@ -0,0 +1,38 @@
+//! Tests that re-emitting a module doesn't bloat its encoding.
+
+use walrus_tests::testutils::assert_reemit_no_larger;
+
+fn check(fixture: &str) {
+    let path = format!(
+        "{}/tests/round_trip/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        fixture
+    );
+    let wasm = wat::parse_file(&path).unwrap();
+    assert_reemit_no_larger(&wasm);
+}
+
+#[test]
+fn locals() {
+    check("used-local-set.wat");
+    check("params.wat");
+}
+
+#[test]
+fn block_types() {
+    check("block.wat");
+    check("multi-1.wat");
+    check("entry-block-type.wat");
+}
+
+#[test]
+fn memargs() {
+    check("mem.wat");
+    check("bulk-memory.wat");
+}
+
+#[test]
+fn many_functions() {
+    check("many_funcs.wat");
+    check("fac.wat");
+}
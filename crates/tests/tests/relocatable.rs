@@ -0,0 +1,41 @@
+//! Tests for emitting relocatable code.
+
+use walrus::{Module, ModuleConfig};
+
+const WAT: &str = r#"
+    (module
+      (global $g (mut i32) (i32.const 0))
+      (func $f)
+      (func (export "g")
+        call $f
+        global.get $g
+        global.set $g))
+"#;
+
+fn emit(relocatable: bool) -> Vec<u8> {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut config = ModuleConfig::new();
+    config.relocatable(relocatable);
+    let mut module = config.parse(&wasm).unwrap();
+    module.emit_wasm()
+}
+
+/// Does `wasm` contain `opcode` followed by a small index padded to 5 bytes?
+fn contains_padded(wasm: &[u8], opcode: u8) -> bool {
+    wasm.windows(6)
+        .any(|w| w[0] == opcode && w[1] & 0x80 != 0 && w[2..] == [0x80, 0x80, 0x80, 0x00])
+}
+
+#[test]
+fn relocatable_indices_are_padded() {
+    let wasm = emit(true);
+    for opcode in [0x10, 0x23, 0x24].iter() {
+        assert!(contains_padded(&wasm, *opcode));
+    }
+    Module::from_buffer(&wasm).unwrap();
+
+    let wasm = emit(false);
+    for opcode in [0x10, 0x23, 0x24].iter() {
+        assert!(!contains_padded(&wasm, *opcode));
+    }
+}
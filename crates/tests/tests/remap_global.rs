@@ -0,0 +1,53 @@
+//! Tests for `Module::remap_global`.
+
+use walrus::ir::{Instr, Value};
+use walrus::{FunctionBuilder, InitExpr, Module, ValType};
+
+#[test]
+fn merges_identical_globals() {
+    let mut module = Module::default();
+    let init = InitExpr::Value(Value::I32(0));
+    let a = module.globals.add_local(ValType::I32, true, init);
+    let b = module.globals.add_local(ValType::I32, true, init);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .global_get(a)
+        .global_get(b)
+        .binop(walrus::ir::BinaryOp::I32Add)
+        .global_set(b);
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+
+    module.remap_global(b, a).unwrap();
+    let func = module.funcs.get(f).kind.unwrap_local();
+    for (instr, _) in func.block(func.entry_block()).iter() {
+        match instr {
+            Instr::GlobalGet(get) => assert_eq!(get.global, a),
+            Instr::GlobalSet(set) => assert_eq!(set.global, a),
+            _ => {}
+        }
+    }
+
+    walrus::passes::gc::run(&mut module);
+    assert_eq!(module.globals.iter().count(), 1);
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn rejects_different_types() {
+    let mut module = Module::default();
+    let a = module
+        .globals
+        .add_local(ValType::I32, false, InitExpr::Value(Value::I32(0)));
+    let b = module
+        .globals
+        .add_local(ValType::I64, false, InitExpr::Value(Value::I64(0)));
+    let c = module
+        .globals
+        .add_local(ValType::I32, true, InitExpr::Value(Value::I32(0)));
+    assert!(module.remap_global(a, b).is_err());
+    assert!(module.remap_global(a, c).is_err());
+}
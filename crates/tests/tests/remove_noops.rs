@@ -0,0 +1,76 @@
+//! Tests for `Module::remove_noops`.
+
+use walrus::ir::{BinaryOp, Instr};
+use walrus::{FunctionBuilder, FunctionId, InstrSeqBuilder, LocalId, Module, ValType};
+use walrus_tests::testutils::entry_instr_names;
+
+fn add_function(
+    module: &mut Module,
+    build: impl FnOnce(&mut InstrSeqBuilder, LocalId),
+) -> FunctionId {
+    let x = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    build(&mut builder.func_body(), x);
+    builder.finish(vec![x], &mut module.funcs)
+}
+
+#[test]
+fn removes_or_zero() {
+    let mut module = Module::default();
+    let f = add_function(&mut module, |body, x| {
+        body.local_get(x).i32_const(0).binop(BinaryOp::I32Or);
+    });
+    module.remove_noops();
+    assert_eq!(entry_instr_names(&module, f), ["local_get"]);
+}
+
+#[test]
+fn removes_mul_one() {
+    let mut module = Module::default();
+    let f = add_function(&mut module, |body, x| {
+        body.local_get(x).i32_const(1).binop(BinaryOp::I32Mul);
+    });
+    module.remove_noops();
+    assert_eq!(entry_instr_names(&module, f), ["local_get"]);
+}
+
+#[test]
+fn removes_dropped_local_get() {
+    let mut module = Module::default();
+    let f = add_function(&mut module, |body, x| {
+        body.local_get(x)
+            .local_get(x)
+            .i32_const(0)
+            .binop(BinaryOp::I32Or)
+            .drop()
+            .block(None, |block| {
+                block.local_get(x).drop();
+            });
+    });
+    module.remove_noops();
+    assert_eq!(entry_instr_names(&module, f), ["local_get", "block"]);
+    let func = module.funcs.get(f).kind.unwrap_local();
+    match &func.block(func.entry_block())[1].0 {
+        Instr::Block(b) => assert!(func.block(b.seq).is_empty()),
+        other => panic!("unexpected instruction: {:?}", other),
+    }
+}
+
+#[test]
+fn keeps_other_operations() {
+    let mut module = Module::default();
+    let f = add_function(&mut module, |body, x| {
+        body.local_get(x)
+            .i32_const(1)
+            .binop(BinaryOp::I32Or)
+            .i32_const(0)
+            .binop(BinaryOp::I32Mul)
+            .i32_const(0)
+            .binop(BinaryOp::I32And)
+            .local_get(x)
+            .local_set(x);
+    });
+    let before = entry_instr_names(&module, f);
+    module.remove_noops();
+    assert_eq!(entry_instr_names(&module, f), before);
+}
@@ -0,0 +1,76 @@
+//! Tests for `Module::remove_unused_pure_calls`.
+
+use walrus::ir::{BinaryOp, Instr};
+use walrus::{FunctionBuilder, FunctionId, Module, ValType};
+use walrus_tests::testutils::entry_instr_names;
+
+/// Add a function that calls `callee` with two `i32` arguments and drops
+/// both of its results.
+fn add_caller(module: &mut Module, callee: FunctionId) -> FunctionId {
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .i32_const(2)
+        .call(callee)
+        .drop()
+        .drop();
+    builder.finish(vec![], &mut module.funcs)
+}
+
+fn add_callee(module: &mut Module, op: BinaryOp) -> FunctionId {
+    let a = module.locals.add(ValType::I32);
+    let b = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32],
+    );
+    builder
+        .func_body()
+        .local_get(a)
+        .local_get(b)
+        .binop(op)
+        .local_get(a);
+    builder.finish(vec![a, b], &mut module.funcs)
+}
+
+#[test]
+fn removes_pure_call() {
+    let mut module = Module::default();
+    let callee = add_callee(&mut module, BinaryOp::I32Add);
+    let caller = add_caller(&mut module, callee);
+
+    module.remove_unused_pure_calls();
+    assert_eq!(
+        entry_instr_names(&module, caller),
+        ["const", "const", "drop", "drop"]
+    );
+
+    let wasm = module.emit_wasm();
+    assert!(Module::from_buffer(&wasm).is_ok());
+}
+
+#[test]
+fn keeps_impure_calls() {
+    let mut module = Module::default();
+
+    // Division can trap.
+    let trapping = add_callee(&mut module, BinaryOp::I32DivS);
+    let caller = add_caller(&mut module, trapping);
+
+    // And imported functions could do anything.
+    let ty = module
+        .types
+        .add(&[ValType::I32, ValType::I32], &[ValType::I32, ValType::I32]);
+    let (imported, _) = module.add_import_func("env", "f", ty);
+    let import_caller = add_caller(&mut module, imported);
+
+    module.remove_unused_pure_calls();
+    for caller in [caller, import_caller].iter() {
+        let func = module.funcs.get(*caller).kind.unwrap_local();
+        let body = func.block(func.entry_block());
+        assert!(matches!(body[2].0, Instr::Call(_)));
+        assert_eq!(body.len(), 5);
+    }
+}
@@ -0,0 +1,11 @@
+(module
+  (func $f (result i32)
+    unreachable
+    i32.const 1
+    select
+    i32.const 2
+    i32.add)
+  (export "f" (func $f)))
+
+;; CHECK: (func $f (type 0) (result i32)
+;; NEXT:    unreachable)
@@ -0,0 +1,47 @@
+//! Tests for the section layout reported by `Module::emit_wasm_with_layout`.
+
+use walrus::Module;
+
+const CODE: u8 = 10;
+
+#[test]
+fn function_ranges_are_within_code_section() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory 1)
+                (func $a (export "a") (result i32)
+                    i32.const 1)
+                (func $b (export "b") (param i32) (result i32)
+                    local.get 0
+                    call $a
+                    i32.add)
+                (data (i32.const 0) "hello"))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    let (wasm, layout) = module.emit_wasm_with_layout();
+
+    // Sections are laid out one after another, starting after the header.
+    let mut pos = 8;
+    for (_id, range) in layout.sections.iter() {
+        assert_eq!(range.start, pos);
+        pos = range.end;
+    }
+    assert_eq!(pos, wasm.len());
+
+    let code = layout.section(CODE).unwrap();
+    assert_eq!(wasm[code.start], CODE);
+    assert_eq!(layout.functions.len(), 2);
+    for (id, range) in layout.functions.iter() {
+        assert!(code.start < range.start && range.end <= code.end);
+        assert_eq!(layout.function(*id), Some(range.clone()));
+    }
+
+    // Neither function has any locals, and both bodies end with `end`.
+    for (_id, range) in layout.functions.iter() {
+        assert_eq!(wasm[range.start], 0);
+        assert_eq!(wasm[range.end - 1], 0x0b);
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Tests for validating references to data and element segments.
+
+use walrus::passes::validate;
+use walrus::{DataKind, ElementKind, FunctionBuilder, Module, ValType};
+
+fn assert_invalid(module: &Module, msg: &str) {
+    let err = validate::run(module).unwrap_err();
+    assert!(
+        err.to_string().contains(msg),
+        "expected {:?} in error: {}",
+        msg,
+        err
+    );
+}
+
+#[test]
+fn dangling_data_segment() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let data = module.data.add(DataKind::Passive, vec![1, 2, 3]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(0)
+        .i32_const(0)
+        .i32_const(3)
+        .memory_init(memory, data)
+        .data_drop(data);
+    builder.finish(vec![], &mut module.funcs);
+    validate::run(&module).unwrap();
+
+    module.data.delete(data);
+    assert_invalid(&module, "memory.init references a data segment");
+    assert_invalid(&module, "data.drop references a data segment");
+}
+
+#[test]
+fn dangling_element_segment() {
+    let mut module = Module::default();
+    let table = module.tables.add_local(1, None, ValType::Funcref);
+    let elem = module
+        .elements
+        .add(ElementKind::Passive, ValType::Funcref, vec![None]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(0)
+        .i32_const(0)
+        .i32_const(1)
+        .table_init(table, elem)
+        .elem_drop(elem);
+    builder.finish(vec![], &mut module.funcs);
+    validate::run(&module).unwrap();
+
+    module.elements.delete(elem);
+    assert_invalid(&module, "table.init references an element segment");
+    assert_invalid(&module, "elem.drop references an element segment");
+}
+
+#[test]
+fn init_after_drop_is_valid() {
+    let mut module = Module::default();
+    let memory = module.memories.add_local(false, 1, None);
+    let data = module.data.add(DataKind::Passive, vec![1, 2, 3]);
+
+    // This traps at runtime, but is still valid, so it only produces a
+    // warning.
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .data_drop(data)
+        .i32_const(0)
+        .i32_const(0)
+        .i32_const(3)
+        .memory_init(memory, data);
+    builder.finish(vec![], &mut module.funcs);
+    validate::run(&module).unwrap();
+}
@@ -0,0 +1,70 @@
+//! Tests for converting memories to shared memories.
+
+use walrus::{Module, ModuleConfig};
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> u32 {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+        shift += 7;
+    }
+}
+
+/// Get the payload of the first section with the given id.
+fn section(wasm: &[u8], id: u8) -> Option<&[u8]> {
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let section_id = wasm[pos];
+        pos += 1;
+        let size = read_u32(wasm, &mut pos) as usize;
+        if section_id == id {
+            return Some(&wasm[pos..pos + size]);
+        }
+        pos += size;
+    }
+    None
+}
+
+#[test]
+fn convert_memory_to_shared() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (memory 1)
+              (func (export "f") (result i32)
+                i32.const 0
+                i32.atomic.load))
+        "#,
+    )?;
+
+    // Atomic instructions on a non-shared memory don't validate...
+    assert!(Module::from_buffer(&wasm).is_err());
+    let mut module = ModuleConfig::new().strict_validate(false).parse(&wasm)?;
+    assert!(walrus::passes::validate::run(&module).is_err());
+
+    // ...but they do once the memory is shared.
+    let memory = module.memories.iter().next().unwrap().id();
+    let memory = module.memories.get_mut(memory);
+    assert!(memory.set_shared(0).is_err());
+    assert!(memory.set_shared(65537).is_err());
+    assert!(!memory.shared);
+    memory.set_shared(2)?;
+    walrus::passes::validate::run(&module)?;
+
+    // The memory section is `count = 1, flags = shared | has max, initial =
+    // 1, max = 2`.
+    let wasm = module.emit_wasm();
+    assert_eq!(section(&wasm, 5), Some(&[0x01, 0x03, 0x01, 0x02][..]));
+
+    let module = Module::from_buffer(&wasm)?;
+    let memory = module.memories.iter().next().unwrap();
+    assert!(memory.shared);
+    assert_eq!(memory.maximum, Some(2));
+    Ok(())
+}
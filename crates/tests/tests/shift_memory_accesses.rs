@@ -0,0 +1,69 @@
+//! Tests for `Module::shift_memory_accesses`.
+
+use walrus::ir::{Instr, LoadKind, MemArg, StoreKind};
+use walrus::{ActiveData, ActiveDataLocation, DataKind, FunctionBuilder, FunctionId, Module};
+
+fn add_function(module: &mut Module) -> FunctionId {
+    let memory = module.memories.add_local(false, 1, None);
+    let location = ActiveDataLocation::Absolute(8);
+    module
+        .data
+        .add(DataKind::Active(ActiveData { memory, location }), vec![1]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let arg = |offset| MemArg { align: 4, offset };
+    builder
+        .func_body()
+        .i32_const(0)
+        .i32_const(0)
+        .load(memory, LoadKind::I32 { atomic: false }, arg(4))
+        .store(memory, StoreKind::I32 { atomic: false }, arg(0));
+    builder.finish(vec![], &mut module.funcs)
+}
+
+fn offsets(module: &Module, f: FunctionId) -> Vec<u32> {
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let mut offsets = func
+        .block(func.entry_block())
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::Load(load) => Some(load.arg.offset),
+            Instr::Store(store) => Some(store.arg.offset),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for data in module.data.iter() {
+        if let DataKind::Active(active) = &data.kind {
+            if let ActiveDataLocation::Absolute(offset) = active.location {
+                offsets.push(offset);
+            }
+        }
+    }
+    offsets
+}
+
+#[test]
+fn shifts_offsets() {
+    let mut module = Module::default();
+    let f = add_function(&mut module);
+    let memory = module.memories.iter().next().unwrap().id();
+
+    module.shift_memory_accesses(memory, 16).unwrap();
+    assert_eq!(offsets(&module, f), [20, 16, 24]);
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn rejects_out_of_range_offsets() {
+    let mut module = Module::default();
+    let f = add_function(&mut module);
+    let memory = module.memories.iter().next().unwrap().id();
+
+    assert!(module.shift_memory_accesses(memory, -1).is_err());
+    assert!(module
+        .shift_memory_accesses(memory, i64::from(u32::MAX))
+        .is_err());
+    assert_eq!(offsets(&module, f), [4, 0, 8]);
+}
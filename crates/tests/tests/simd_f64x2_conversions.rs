@@ -0,0 +1,67 @@
+//! Tests for the SIMD double-precision conversions.
+
+use walrus::ir::UnaryOp;
+use walrus::{FeatureLevel, FunctionBuilder, Module, ModuleConfig, ValType};
+
+/// Each conversion along with its text format mnemonic.
+const CONVERSIONS: &[(UnaryOp, &str)] = &[
+    (
+        UnaryOp::I32x4TruncSatF64x2SZero,
+        "i32x4.trunc_sat_f64x2_s_zero",
+    ),
+    (
+        UnaryOp::I32x4TruncSatF64x2UZero,
+        "i32x4.trunc_sat_f64x2_u_zero",
+    ),
+    (UnaryOp::F64x2ConvertLowI32x4S, "f64x2.convert_low_i32x4_s"),
+    (UnaryOp::F64x2ConvertLowI32x4U, "f64x2.convert_low_i32x4_u"),
+    (UnaryOp::F32x4DemoteF64x2Zero, "f32x4.demote_f64x2_zero"),
+    (UnaryOp::F64x2PromoteLowF32x4, "f64x2.promote_low_f32x4"),
+];
+
+/// Build a module exporting a function that applies `op` to its argument.
+fn module(level: FeatureLevel, op: UnaryOp) -> Module {
+    let mut config = ModuleConfig::new();
+    config.feature_level(level);
+    let mut module = Module::with_config(config);
+    let x = module.locals.add(ValType::V128);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::V128], &[ValType::V128]);
+    builder.func_body().local_get(x).unop(op);
+    let f = builder.finish(vec![x], &mut module.funcs);
+    module.exports.add("f", f);
+    module
+}
+
+#[test]
+fn emit_conversions() {
+    for (op, mnemonic) in CONVERSIONS {
+        let wasm = module(FeatureLevel::Standard, *op).try_emit_wasm().unwrap();
+        let text = wasmprinter::print_bytes(&wasm).unwrap();
+        assert!(
+            text.contains(&format!("local.get 0\n    {}\n", mnemonic)),
+            "expected `{}` in:\n{}",
+            mnemonic,
+            text
+        );
+    }
+}
+
+#[test]
+fn conversions_have_no_legacy_encoding() {
+    for (op, _) in CONVERSIONS {
+        let err = module(FeatureLevel::Legacy, *op)
+            .try_emit_wasm()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("has no legacy encoding"),
+            "{}",
+            err
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "has no legacy encoding")]
+fn emitting_conversion_for_legacy_panics() {
+    module(FeatureLevel::Legacy, UnaryOp::F64x2PromoteLowF32x4).emit_wasm();
+}
@@ -0,0 +1,53 @@
+//! Tests for `Module::simplify` and `Module::simplify_with`.
+
+use walrus::ir::BinaryOp;
+use walrus::passes::SimplifyOptions;
+use walrus::{FunctionBuilder, Module, ValType};
+use walrus_tests::testutils::{entry_instr_names, entry_instrs};
+
+#[test]
+fn folds_and_removes_dead_code_in_one_call() {
+    let mut module = Module::default();
+    let x = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        // Removing the `local.get` and `drop` exposes the addition to
+        // `fold_constants` in the next round.
+        .i32_const(2)
+        .local_get(x)
+        .drop()
+        .i32_const(3)
+        .binop(BinaryOp::I32Add)
+        .return_()
+        .local_get(x)
+        .drop();
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    module.simplify();
+
+    assert_eq!(entry_instr_names(&module, f), ["const", "return"]);
+    assert_eq!(module.simplify_with(&SimplifyOptions::default()), 1);
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn only_runs_selected_passes() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(2)
+        .i32_const(3)
+        .binop(BinaryOp::I32Add);
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    let options = SimplifyOptions {
+        fold_constants: false,
+        ..SimplifyOptions::default()
+    };
+    assert_eq!(module.simplify_with(&options), 1);
+    assert_eq!(entry_instrs(&module, f).len(), 3);
+}
@@ -0,0 +1,131 @@
+//! Tests for `Module::eliminate_identity_shuffles` and `Module::compose_shuffles`.
+
+use walrus::ir::{Instr, ShuffleIndices, Value};
+use walrus::{FunctionBuilder, FunctionId, InstrSeqBuilder, LocalId, Module, ValType};
+use walrus_tests::testutils::{entry_instr_names, entry_instrs};
+
+const IDENTITY: ShuffleIndices = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+const REVERSE: ShuffleIndices = [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0];
+
+fn add_function(
+    module: &mut Module,
+    build: impl FnOnce(&mut InstrSeqBuilder, LocalId, LocalId),
+) -> FunctionId {
+    let a = module.locals.add(ValType::V128);
+    let b = module.locals.add(ValType::V128);
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::V128, ValType::V128],
+        &[ValType::V128],
+    );
+    build(&mut builder.func_body(), a, b);
+    builder.finish(vec![a, b], &mut module.funcs)
+}
+
+fn args(module: &Module, func: FunctionId) -> &[LocalId] {
+    &module.funcs.get(func).kind.unwrap_local().args
+}
+
+#[test]
+fn eliminates_identity_shuffles() {
+    let mut module = Module::default();
+    let first = add_function(&mut module, |body, a, b| {
+        body.local_get(a).local_get(b).v128_shuffle(IDENTITY);
+    });
+    let mut second = [0; 16];
+    for (i, lane) in second.iter_mut().enumerate() {
+        *lane = i as u8 + 16;
+    }
+    let second = add_function(&mut module, |body, a, b| {
+        body.local_get(a).local_get(b).v128_shuffle(second);
+    });
+    let mut repeated = IDENTITY;
+    repeated[3] += 16;
+    let repeated = add_function(&mut module, |body, a, _| {
+        body.local_get(a).local_get(a).v128_shuffle(repeated);
+    });
+    let dropped = add_function(&mut module, |body, a, b| {
+        body.local_get(a)
+            .local_get(b)
+            .local_get(b)
+            .v128_shuffle(REVERSE)
+            .v128_shuffle(IDENTITY);
+    });
+    let kept = add_function(&mut module, |body, a, b| {
+        body.local_get(a).local_get(b).v128_shuffle(REVERSE);
+    });
+
+    module.eliminate_identity_shuffles();
+    match &entry_instrs(&module, first)[..] {
+        [Instr::LocalGet(a)] => assert_eq!(a.local, args(&module, first)[0]),
+        other => panic!("unexpected body: {:?}", other),
+    }
+    match &entry_instrs(&module, second)[..] {
+        [Instr::LocalGet(b)] => assert_eq!(b.local, args(&module, second)[1]),
+        other => panic!("unexpected body: {:?}", other),
+    }
+    assert_eq!(entry_instr_names(&module, repeated), ["local_get"]);
+    assert_eq!(
+        entry_instr_names(&module, dropped),
+        [
+            "local_get",
+            "local_get",
+            "local_get",
+            "v128_shuffle",
+            "drop"
+        ]
+    );
+    assert_eq!(
+        entry_instr_names(&module, kept),
+        ["local_get", "local_get", "v128_shuffle"]
+    );
+
+    let wasm = module.emit_wasm();
+    assert!(Module::from_buffer(&wasm).is_ok());
+}
+
+#[test]
+fn composes_shuffle_pair() {
+    let mut module = Module::default();
+
+    // Swap the two halves of the interleaved lanes of `a` and `b`.
+    let mut interleave = [0; 16];
+    for (i, lane) in interleave.iter_mut().enumerate() {
+        *lane = (i / 2 + (i % 2) * 16) as u8;
+    }
+    let mut swap_halves = [0; 16];
+    for (i, lane) in swap_halves.iter_mut().enumerate() {
+        *lane = ((i + 8) % 16) as u8;
+    }
+    let composable = add_function(&mut module, |body, a, b| {
+        body.local_get(a)
+            .local_get(b)
+            .v128_shuffle(interleave)
+            .const_(Value::V128(0))
+            .v128_shuffle(swap_halves);
+    });
+
+    // The second shuffle reads from the constant, so can't be composed.
+    let mut mixed = swap_halves;
+    mixed[0] = 16;
+    let not_composable = add_function(&mut module, |body, a, b| {
+        body.local_get(a)
+            .local_get(b)
+            .v128_shuffle(interleave)
+            .const_(Value::V128(0))
+            .v128_shuffle(mixed);
+    });
+
+    module.compose_shuffles();
+    match &entry_instrs(&module, composable)[..] {
+        [Instr::LocalGet(_), Instr::LocalGet(_), Instr::V128Shuffle(s)] => {
+            let expected = [4, 20, 5, 21, 6, 22, 7, 23, 0, 16, 1, 17, 2, 18, 3, 19];
+            assert_eq!(s.indices, expected);
+        }
+        other => panic!("unexpected body: {:?}", other),
+    }
+    assert_eq!(entry_instrs(&module, not_composable).len(), 5);
+
+    let wasm = module.emit_wasm();
+    assert!(Module::from_buffer(&wasm).is_ok());
+}
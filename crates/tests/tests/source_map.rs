@@ -0,0 +1,33 @@
+//! Tests for `Module::emit_wasm_with_source_map`.
+
+use walrus::{InstrLocId, Module};
+
+fn find(wasm: &[u8], bytes: &[u8]) -> usize {
+    wasm.windows(bytes.len()).position(|w| w == bytes).unwrap()
+}
+
+#[test]
+fn maps_instructions_both_ways() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (func (export "f") (result i32)
+                    i32.const 42))
+        "#,
+    )?;
+    let input = find(&wasm, &[0x41, 42]);
+    let loc = InstrLocId::new(input as u32);
+
+    // This isn't configured with `preserve_code_transform`.
+    let mut module = Module::from_buffer(&wasm)?;
+    let (emitted, map) = module.emit_wasm_with_source_map();
+
+    let output = map.output_offsets(loc).collect::<Vec<_>>();
+    assert_eq!(output.len(), 1);
+    assert_eq!(&emitted[output[0]..output[0] + 2], &[0x41, 42]);
+
+    assert_eq!(map.input_location(output[0]), Some(loc));
+    assert_eq!(map.input_location(output[0] + 1), Some(loc));
+    assert_eq!(map.input_location(0), None);
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Tests for `Module::split_large_functions`.
+
+use walrus::ir::{BinaryOp, Instr};
+use walrus::{FunctionBuilder, Module, ValType};
+
+#[test]
+fn splits_straight_line_tail() {
+    let mut module = Module::default();
+    let x = module.locals.add(ValType::I32);
+    let sum = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    let mut body = builder.func_body();
+    body.block(None, |block| {
+        block.local_get(x).local_set(sum);
+    });
+    for i in 0..100 {
+        body.local_get(sum)
+            .local_get(x)
+            .i32_const(i)
+            .binop(BinaryOp::I32Mul)
+            .binop(BinaryOp::I32Add)
+            .local_set(sum);
+    }
+    body.local_get(sum);
+    let f = builder.finish(vec![x], &mut module.funcs);
+    module.exports.add("f", f);
+
+    let before = module.funcs.get(f).kind.unwrap_local().encoded_len(&module);
+    module.split_large_functions(200);
+
+    let funcs = module.funcs.iter_local().collect::<Vec<_>>();
+    assert!(funcs.len() > 2);
+    for (_, func) in funcs.iter() {
+        assert!(func.encoded_len(&module) <= 200);
+    }
+    let func = module.funcs.get(f).kind.unwrap_local();
+    assert!(func.encoded_len(&module) < before);
+    let body = func.block(func.entry_block());
+    assert!(matches!(body[0].0, Instr::Block(_)));
+    assert!(matches!(body.instrs.last().unwrap().0, Instr::Call(_)));
+
+    // The helper is passed both locals the tail uses.
+    let n = body.len();
+    assert!(matches!(body[n - 3].0, Instr::LocalGet(_)));
+    assert!(matches!(body[n - 2].0, Instr::LocalGet(_)));
+    let helper = match &body[n - 1].0 {
+        Instr::Call(call) => call.func,
+        _ => unreachable!(),
+    };
+    assert_eq!(module.funcs.get(helper).kind.unwrap_local().args.len(), 2);
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn leaves_functions_without_straight_line_tails() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().block(None, |block| {
+        for _ in 0..100 {
+            block.i32_const(1).drop();
+        }
+    });
+    builder.finish(vec![], &mut module.funcs);
+
+    module.split_large_functions(10);
+    assert_eq!(module.funcs.iter_local().count(), 1);
+}
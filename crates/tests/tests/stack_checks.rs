@@ -0,0 +1,76 @@
+//! Tests for `FunctionBuilder::with_stack_checks`.
+
+use walrus::ir::BinaryOp;
+use walrus::{FunctionBuilder, Module, ValType};
+
+fn checked_builder(
+    module: &mut Module,
+    params: &[ValType],
+    results: &[ValType],
+) -> FunctionBuilder {
+    FunctionBuilder::with_stack_checks(&mut module.types, &module.funcs, params, results)
+}
+
+#[test]
+fn balanced_function() {
+    let mut module = Module::default();
+    let callee_ty = module.types.add(&[ValType::I32], &[ValType::I32]);
+    let (callee, _) = module.add_import_func("env", "f", callee_ty);
+
+    let mut builder = checked_builder(&mut module, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .call(callee)
+        .block(ValType::I32, |block| {
+            block.i32_const(2).i32_const(3).binop(BinaryOp::I32Add);
+        })
+        .binop(BinaryOp::I32Add)
+        .if_else(
+            ValType::I32,
+            |then| {
+                then.i32_const(4);
+            },
+            |else_| {
+                else_.unreachable();
+            },
+        );
+    builder.finish(vec![], &mut module.funcs);
+}
+
+#[test]
+#[should_panic(expected = "stack underflow")]
+fn underflow() {
+    let mut module = Module::default();
+    let mut builder = checked_builder(&mut module, &[], &[]);
+    builder.func_body().i32_const(1).binop(BinaryOp::I32Add);
+}
+
+#[test]
+#[should_panic(expected = "ends with 0 values on the stack, but its type expects 1")]
+fn block_missing_result() {
+    let mut module = Module::default();
+    let mut builder = checked_builder(&mut module, &[], &[]);
+    builder.func_body().block(ValType::I32, |block| {
+        block.i32_const(1).drop();
+    });
+}
+
+#[test]
+#[should_panic(expected = "ends with 2 values on the stack, but its type expects 1")]
+fn function_with_extra_result() {
+    let mut module = Module::default();
+    let mut builder = checked_builder(&mut module, &[], &[ValType::I32]);
+    builder.func_body().i32_const(1).i32_const(2);
+    builder.finish(vec![], &mut module.funcs);
+}
+
+#[test]
+fn unchecked_by_default() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().block(ValType::I32, |block| {
+        block.binop(BinaryOp::I32Add);
+    });
+    builder.finish(vec![], &mut module.funcs);
+}
@@ -0,0 +1,60 @@
+//! Tests for `ModuleConfig::strip_unreachable`.
+
+use walrus::ir::BinaryOp;
+use walrus::{FunctionBuilder, Module, ModuleConfig, ValType};
+
+/// A module whose function has unreachable code after a `return`, both at
+/// the end of a block and at the end of the function.
+fn module(config: ModuleConfig) -> Module {
+    let mut module = Module::with_config(config);
+    let arg = module.locals.add(ValType::I32);
+    let dead = module.locals.add(ValType::I64);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        .block(None, |block| {
+            let id = block.id();
+            block
+                .local_get(arg)
+                .br_if(id)
+                .i32_const(1)
+                .return_()
+                .local_get(dead)
+                .drop();
+        })
+        .local_get(arg)
+        .return_()
+        .i32_const(3)
+        .i32_const(4)
+        .binop(BinaryOp::I32Add);
+    let f = builder.finish(vec![arg], &mut module.funcs);
+    module.exports.add("f", f);
+    module
+}
+
+fn function_size(module: &Module) -> u64 {
+    module.funcs.iter_local().map(|(_, f)| f.size()).sum()
+}
+
+#[test]
+fn strip_unreachable_shrinks_output() -> anyhow::Result<()> {
+    let mut kept = module(ModuleConfig::new());
+    let kept_wasm = kept.emit_wasm();
+
+    let mut config = ModuleConfig::new();
+    config.strip_unreachable(true);
+    let mut stripped = module(config);
+    let size = function_size(&stripped);
+    let stripped_wasm = stripped.emit_wasm();
+
+    assert!(stripped_wasm.len() < kept_wasm.len());
+    // The in-memory IR is left alone.
+    assert_eq!(function_size(&stripped), size);
+    assert_eq!(function_size(&kept), size);
+
+    let reparsed = Module::from_buffer(&stripped_wasm)?;
+    assert!(function_size(&reparsed) < size);
+    // The `i64` local was only used in unreachable code.
+    assert_eq!(reparsed.locals.iter().count(), 1);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Tests for the `target_features` custom section.
+
+use walrus::{FeaturePrefix, Module};
+
+/// A `target_features` section using `simd128` and `mutable-globals`.
+fn target_features_payload() -> Vec<u8> {
+    let mut payload = vec![2];
+    payload.push(b'+');
+    payload.push(7);
+    payload.extend(b"simd128");
+    payload.push(b'+');
+    payload.push(15);
+    payload.extend(b"mutable-globals");
+    payload
+}
+
+fn features(module: &Module) -> Vec<(&str, FeaturePrefix)> {
+    module.target_features.iter().collect()
+}
+
+#[test]
+fn round_trip() -> anyhow::Result<()> {
+    let mut wasm = wat::parse_str("(module (func (export \"f\")))")?;
+    let payload = target_features_payload();
+    let name = b"target_features";
+    wasm.push(0);
+    wasm.push((1 + name.len() + payload.len()) as u8);
+    wasm.push(name.len() as u8);
+    wasm.extend(name);
+    wasm.extend(&payload);
+
+    let mut module = Module::from_buffer(&wasm)?;
+    let expected = [
+        ("simd128", FeaturePrefix::Used),
+        ("mutable-globals", FeaturePrefix::Used),
+    ];
+    assert_eq!(features(&module), expected);
+    assert_eq!(module.customs.iter().count(), 0);
+
+    let emitted = module.emit_wasm();
+    assert!(emitted
+        .windows(payload.len())
+        .any(|window| window == &payload[..]));
+    let module = Module::from_buffer(&emitted)?;
+    assert_eq!(features(&module), expected);
+    Ok(())
+}
+
+#[test]
+fn add_feature() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    assert!(!module
+        .emit_wasm()
+        .windows(15)
+        .any(|w| w == b"target_features"));
+
+    module
+        .target_features
+        .add_feature("atomics", FeaturePrefix::Used);
+    module
+        .target_features
+        .add_feature("bulk-memory", FeaturePrefix::Disallowed);
+    module
+        .target_features
+        .add_feature("atomics", FeaturePrefix::Required);
+    assert_eq!(
+        module.target_features.get("atomics"),
+        Some(FeaturePrefix::Required)
+    );
+
+    let module = Module::from_buffer(&module.emit_wasm())?;
+    assert_eq!(
+        features(&module),
+        [
+            ("atomics", FeaturePrefix::Required),
+            ("bulk-memory", FeaturePrefix::Disallowed),
+        ]
+    );
+    Ok(())
+}
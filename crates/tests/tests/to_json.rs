@@ -0,0 +1,54 @@
+//! Tests for `Module::to_json`.
+
+#![cfg(feature = "serde")]
+
+use walrus::Module;
+
+#[test]
+fn describes_exports_and_signatures() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (memory (export "memory") 1)
+                (func $add (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add))
+        "#,
+    )?;
+    let module = Module::from_buffer(&wasm)?;
+    let json = module.to_json();
+
+    let exports = json["exports"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["name"].as_str().unwrap(), e["kind"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(exports, [("memory", "memory"), ("add", "function")]);
+
+    assert_eq!(json["imports"][0]["module"], "env");
+    assert_eq!(json["imports"][0]["name"], "log");
+
+    let add = json["functions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["name"] == "add")
+        .unwrap();
+    assert_eq!(add["kind"], "local");
+    assert_eq!(add["params"].as_array().unwrap().len(), 2);
+    assert_eq!(add["results"], serde_json::json!(["i32"]));
+    assert_eq!(add["instructions"], 3);
+
+    let log = json["functions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["name"] == "log")
+        .unwrap();
+    assert_eq!(log["kind"], "import");
+    assert_eq!(log["params"], serde_json::json!(["i32"]));
+    Ok(())
+}
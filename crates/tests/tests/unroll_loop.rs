@@ -0,0 +1,121 @@
+//! Tests for `LocalFunction::unroll_loop` and the loop analysis it relies on.
+
+use walrus::ir::{BinaryOp, InstrSeqId};
+use walrus::{FunctionBuilder, FunctionId, Module, ValType};
+use walrus_tests::testutils::block_instr_names;
+
+/// Add a function summing the counter of a loop that runs `trips` times
+/// into an accumulator, returning the function and the loop's body.
+fn add_sum(module: &mut Module, trips: i32) -> (FunctionId, InstrSeqId) {
+    let i = module.locals.add(ValType::I32);
+    let acc = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    let mut body = None;
+    builder
+        .func_body()
+        .i32_const(0)
+        .local_set(i)
+        .loop_(None, |l| {
+            let id = l.id();
+            body = Some(id);
+            l.local_get(acc)
+                .local_get(i)
+                .binop(BinaryOp::I32Add)
+                .local_set(acc)
+                .local_get(i)
+                .i32_const(1)
+                .binop(BinaryOp::I32Add)
+                .local_tee(i)
+                .i32_const(trips)
+                .binop(BinaryOp::I32LtS)
+                .br_if(id);
+        })
+        .local_get(acc);
+    let f = builder.finish(vec![], &mut module.funcs);
+    (f, body.unwrap())
+}
+
+#[test]
+fn unrolls_counted_loop() {
+    let mut module = Module::default();
+    let (f, seq) = add_sum(&mut module, 12);
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    assert!(func.unroll_loop(seq, 3));
+
+    let copy = [
+        "local_get",
+        "local_get",
+        "binop",
+        "local_set",
+        "local_get",
+        "const",
+        "binop",
+    ];
+    let mut expected = Vec::new();
+    for _ in 0..2 {
+        expected.extend(copy.iter());
+        expected.push("local_set");
+    }
+    expected.extend(copy.iter());
+    expected.extend(["local_tee", "const", "binop", "br_if"].iter());
+    assert_eq!(block_instr_names(&module, f, seq), expected);
+
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn keeps_loops_it_cant_unroll() {
+    let mut module = Module::default();
+
+    // 10 iterations can't be unrolled 4 times.
+    let (f, seq) = add_sum(&mut module, 10);
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    assert!(!func.unroll_loop(seq, 4));
+    assert_eq!(func.block(seq).len(), 11);
+
+    // Nor can a loop with a branch out of it.
+    let (f, seq) = add_sum(&mut module, 10);
+    let func = module.funcs.get_mut(f).kind.unwrap_local_mut();
+    let entry = func.entry_block();
+    func.builder_mut()
+        .instr_seq(seq)
+        .instr_at(0, walrus::ir::Br { block: entry });
+    assert!(!func.unroll_loop(seq, 2));
+    assert_eq!(func.block(seq).len(), 12);
+}
+
+#[test]
+fn analyzes_counted_loop() {
+    let mut module = Module::default();
+    let (f, seq) = add_sum(&mut module, 10);
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let i = func.block(seq)[1].0.unwrap_local_get().local;
+
+    let loops = func.analyze_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].seq, seq);
+    let induction = loops[0].induction.as_ref().unwrap();
+    assert_eq!(induction.local, i);
+    assert_eq!(induction.init, Some(0));
+    assert_eq!(induction.step, 1);
+    assert!(matches!(induction.comparison, BinaryOp::I32LtS));
+    assert_eq!(induction.bound, 10);
+    assert_eq!(induction.trip_count, Some(10));
+}
+
+#[test]
+fn analyzes_uncounted_loop() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().loop_(None, |l| {
+        let id = l.id();
+        l.i32_const(0).br_if(id);
+    });
+    let f = builder.finish(vec![], &mut module.funcs);
+    let func = module.funcs.get(f).kind.unwrap_local();
+
+    let loops = func.analyze_loops();
+    assert_eq!(loops.len(), 1);
+    assert!(loops[0].induction.is_none());
+}
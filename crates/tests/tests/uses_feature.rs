@@ -0,0 +1,104 @@
+//! Tests for `Module::uses_feature`.
+
+use walrus::ir::{BinaryOp, UnaryOp, Value};
+use walrus::passes::{Feature, FeatureSet};
+use walrus::{FunctionBuilder, Module, ValType};
+
+const ALL: [Feature; 7] = [
+    Feature::Simd,
+    Feature::Atomics,
+    Feature::BulkMemory,
+    Feature::ReferenceTypes,
+    Feature::MultiValue,
+    Feature::SignExtension,
+    Feature::SaturatingFloatToInt,
+];
+
+fn used(module: &Module) -> Vec<Feature> {
+    ALL.iter()
+        .cloned()
+        .filter(|f| module.uses_feature(*f))
+        .collect()
+}
+
+#[test]
+fn plain_module() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory 1)
+                (data (i32.const 0) "hello")
+                (func (export "f") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add
+                    i32.load))
+        "#,
+    )?;
+    let module = Module::from_buffer(&wasm)?;
+    assert_eq!(used(&module), []);
+    Ok(())
+}
+
+#[test]
+fn simd_instructions() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .const_(Value::V128(1))
+        .const_(Value::V128(2))
+        .binop(BinaryOp::I8x16Add)
+        .unop(UnaryOp::I8x16AnyTrue);
+    builder.finish(vec![], &mut module.funcs);
+    assert_eq!(used(&module), [Feature::Simd]);
+}
+
+#[test]
+fn simd_types() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::V128], &[]);
+    builder.func_body();
+    let arg = module.locals.add(ValType::V128);
+    builder.finish(vec![arg], &mut module.funcs);
+    assert_eq!(used(&module), [Feature::Simd]);
+}
+
+#[test]
+fn other_proposals() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (func (export "f") (param i32 f32) (result i32 i32)
+                    local.get 0
+                    i32.extend8_s
+                    local.get 1
+                    i32.trunc_sat_f32_s))
+        "#,
+    )?;
+    let module = Module::from_buffer(&wasm)?;
+    assert_eq!(
+        used(&module),
+        [
+            Feature::MultiValue,
+            Feature::SignExtension,
+            Feature::SaturatingFloatToInt
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn feature_sets() {
+    let mut set = FeatureSet::mvp();
+    assert!(ALL.iter().all(|f| !set.contains(*f)));
+    set.insert(Feature::SignExtension);
+    assert!(set.contains(Feature::SignExtension));
+    assert!(!set.contains(Feature::Simd));
+
+    let mut all = FeatureSet::all();
+    assert!(ALL.iter().all(|f| all.contains(*f)));
+    all.remove(Feature::Simd);
+    assert!(!all.contains(Feature::Simd));
+    assert!(all.contains(Feature::SignExtension));
+}
@@ -0,0 +1,63 @@
+//! Tests for the byte order of `v128.const` immediates.
+
+use walrus::ir::{Instr, Value};
+use walrus::{GlobalKind, InitExpr, Module};
+
+/// The encoding of `v128.const` with the immediate bytes `00 01 02 ... 0f`.
+const ENCODED: [u8; 18] = [
+    0xfd, 0x0c, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+    0x0e, 0x0f,
+];
+
+/// The same immediate as a `Value::V128`: the first byte is the least
+/// significant.
+const VALUE: u128 = 0x0f0e0d0c_0b0a0908_07060504_03020100;
+
+const WAT: &str = r#"
+    (module
+      (global v128 (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15))
+      (func (export "f") (result v128)
+        v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15))
+"#;
+
+fn values(module: &Module) -> (u128, u128) {
+    let global = match module.globals.iter().next().unwrap().kind {
+        GlobalKind::Local(InitExpr::Value(Value::V128(n))) => n,
+        ref other => panic!("expected a v128 global, found {:?}", other),
+    };
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    let instr = match &func.block(func.entry_block())[0].0 {
+        Instr::Const(c) => match c.value {
+            Value::V128(n) => n,
+            other => panic!("expected a v128 constant, found {:?}", other),
+        },
+        other => panic!("expected a constant, found {:?}", other),
+    };
+    (global, instr)
+}
+
+fn count_encoded(wasm: &[u8]) -> usize {
+    wasm.windows(ENCODED.len())
+        .filter(|w| *w == &ENCODED[..])
+        .count()
+}
+
+#[test]
+fn v128_const_round_trips_byte_order() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    assert_eq!(count_encoded(&wasm), 2);
+
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(values(&module), (VALUE, VALUE));
+
+    let mut module = module;
+    let emitted = module.emit_wasm();
+    assert_eq!(count_encoded(&emitted), 2);
+
+    let module = Module::from_buffer(&emitted).unwrap();
+    assert_eq!(values(&module), (VALUE, VALUE));
+    assert_eq!(
+        Value::V128(VALUE).v128_lanes_i8x16(),
+        Some([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
+    );
+}
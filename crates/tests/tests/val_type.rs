@@ -0,0 +1,28 @@
+//! Tests for parsing and printing `ValType`s.
+
+use walrus::ValType;
+
+#[test]
+fn val_type_mnemonics() {
+    let types = [
+        (ValType::I32, "i32"),
+        (ValType::I64, "i64"),
+        (ValType::F32, "f32"),
+        (ValType::F64, "f64"),
+        (ValType::V128, "v128"),
+        (ValType::Externref, "externref"),
+        (ValType::Funcref, "funcref"),
+    ];
+    for (ty, mnemonic) in types.iter() {
+        assert_eq!(ty.to_string(), *mnemonic);
+        assert_eq!(mnemonic.parse::<ValType>().unwrap(), *ty);
+    }
+}
+
+#[test]
+fn unknown_val_type() {
+    let err = "anyref".parse::<ValType>().unwrap_err();
+    assert_eq!(err.to_string(), "unknown value type `anyref`");
+    assert!("I32".parse::<ValType>().is_err());
+    assert!("".parse::<ValType>().is_err());
+}
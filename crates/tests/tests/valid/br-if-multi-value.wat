@@ -0,0 +1,31 @@
+;; A `br_if` leaves its label's values on the stack when it falls through, and
+;; they keep the label's types even after unreachable code.
+(module
+  (func (export "block") (param i32) (result i32 i64)
+    (block $b (result i32 i64)
+      i32.const 1
+      i64.const 2
+      local.get 0
+      br_if $b
+      i64.const 3
+      i64.add))
+
+  (func (export "loop") (param i32) (result f32)
+    i32.const 1
+    i64.const 2
+    (loop $l (param i32 i64) (result f32)
+      local.get 0
+      br_if $l
+      i64.const 3
+      i64.add
+      drop
+      drop
+      f32.const 1))
+
+  (func (export "unreachable") (param i32) (result i32 i64)
+    (block $b (result i32 i64)
+      unreachable
+      local.get 0
+      br_if $b
+      i64.const 3
+      i64.add)))
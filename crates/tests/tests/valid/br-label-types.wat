@@ -0,0 +1,17 @@
+;; Branches to a loop carry the loop's parameters, and branches to a block
+;; carry the block's results.
+(module
+  (func (export "loop") (result f32)
+    i32.const 0
+    (loop $l (param i32) (result f32)
+      i32.const 1
+      br_if $l
+      drop
+      f32.const 1))
+
+  (func (export "block") (result f32)
+    i32.const 0
+    (block $b (param i32) (result f32)
+      drop
+      f32.const 1
+      br $b)))
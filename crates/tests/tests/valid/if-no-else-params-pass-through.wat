@@ -0,0 +1,10 @@
+;; An `if` without an `else` is fine when its parameters are its results, since
+;; the missing `else` passes them through.
+(module
+  (func (export "f") (param i32) (result i32)
+    i32.const 1
+    local.get 0
+    (if (param i32) (result i32)
+      (then
+        i32.const 2
+        i32.add))))
@@ -0,0 +1,20 @@
+;; A `select` in unreachable code may have operands of unknown type, since the
+;; operand stack is polymorphic there.
+(module
+  (func (export "no_operands") (result i32)
+    unreachable
+    select)
+  (func (export "condition_only") (result i64)
+    unreachable
+    i32.const 0
+    select)
+  (func (export "one_operand") (result f32)
+    unreachable
+    f32.const 1
+    i32.const 0
+    select)
+  (func (export "result_used") (result i32)
+    unreachable
+    select
+    i32.const 1
+    i32.add))
@@ -0,0 +1,111 @@
+//! Tests for `Module::validate_all` and `Module::validate_buffer`.
+
+use walrus::passes::validate::ValidationLocation;
+use walrus::{ExportItem, FunctionId, Module, ModuleConfig};
+
+fn exported(module: &Module, name: &str) -> FunctionId {
+    match module.exports.iter().find(|e| e.name == name).unwrap().item {
+        ExportItem::Function(f) => f,
+        _ => panic!("not a function export"),
+    }
+}
+
+#[test]
+fn reports_every_broken_function() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory 1)
+                (global $g i32 (i32.const 0))
+                (func (export "sets_immutable_global")
+                    i32.const 1
+                    global.set $g)
+                (func (export "fine") (result i32)
+                    global.get $g)
+                (func (export "over_aligned") (result i32)
+                    i32.const 0
+                    i32.load align=8))
+        "#,
+    )?;
+
+    // The module is rejected when it is parsed, with the first function's
+    // error.
+    assert!(Module::from_buffer(&wasm).is_err());
+
+    let module = ModuleConfig::new().strict_validate(false).parse(&wasm)?;
+    let errors = module.validate_all();
+    let locations = errors.iter().map(|e| e.location).collect::<Vec<_>>();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(locations.contains(&ValidationLocation::Function(exported(
+        &module,
+        "sets_immutable_global"
+    ))));
+    assert!(locations.contains(&ValidationLocation::Function(exported(
+        &module,
+        "over_aligned"
+    ))));
+    Ok(())
+}
+
+#[test]
+fn valid_module_has_no_errors() -> anyhow::Result<()> {
+    let wasm = wat::parse_str("(module (func (export \"f\")))")?;
+    let module = Module::from_buffer(&wasm)?;
+    assert!(module.validate_all().is_empty());
+    Ok(())
+}
+
+#[test]
+fn reports_every_function_that_fails_to_parse() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (func (export "adds_a_float") (result i32)
+                    i32.const 1
+                    f32.const 2
+                    i32.add)
+                (func (export "fine") (result i32)
+                    i32.const 1)
+                (func (export "returns_nothing") (result i32)))
+        "#,
+    )?;
+    assert!(ModuleConfig::new()
+        .strict_validate(false)
+        .parse(&wasm)
+        .is_err());
+
+    let (module, errors) = Module::validate_buffer(&wasm)?;
+    let locations = errors.iter().map(|e| e.location).collect::<Vec<_>>();
+    assert_eq!(
+        locations,
+        [
+            ValidationLocation::Function(exported(&module, "adds_a_float")),
+            ValidationLocation::Function(exported(&module, "returns_nothing")),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn validate_buffer_reports_other_problems_too() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (global $g i32 (i32.const 0))
+                (func (export "sets_immutable_global")
+                    i32.const 1
+                    global.set $g)
+                (func (export "returns_nothing") (result i32)))
+        "#,
+    )?;
+    let (module, errors) = Module::validate_buffer(&wasm)?;
+    let locations = errors.iter().map(|e| e.location).collect::<Vec<_>>();
+    assert_eq!(
+        locations,
+        [
+            ValidationLocation::Function(exported(&module, "returns_nothing")),
+            ValidationLocation::Function(exported(&module, "sets_immutable_global")),
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! Tests for the non-fatal warnings collected while parsing.
+
+use walrus::{Module, RawCustomSection, Warning};
+
+#[test]
+fn unknown_custom_section() {
+    let mut module = Module::default();
+    module.customs.add(RawCustomSection {
+        name: "my-section".to_string(),
+        data: vec![1, 2, 3],
+    });
+    let wasm = module.emit_wasm();
+
+    let (module, warnings) = Module::from_buffer_with_warnings(&wasm);
+    let module = module.unwrap();
+    assert_eq!(
+        warnings,
+        [Warning::UnknownCustomSection {
+            name: "my-section".to_string()
+        }]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "unknown custom section `my-section`"
+    );
+    assert!(module.customs.iter().any(|(_, s)| s.name() == "my-section"));
+}
+
+#[test]
+fn no_warnings() {
+    let wasm = wat::parse_str("(module (func (export \"f\")))").unwrap();
+    let (module, warnings) = Module::from_buffer_with_warnings(&wasm);
+    assert!(module.is_ok());
+    assert!(warnings.is_empty());
+}
@@ -5,7 +5,7 @@ use std::hash::Hash;
 use std::ops;
 
 /// A set of unique `T`s that are backed by an arena.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ArenaSet<T: Clone + Eq + Hash> {
     arena: TombstoneArena<T>,
     already_in_arena: HashMap<T, Id<T>>,
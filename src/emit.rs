@@ -3,12 +3,12 @@
 //! raw wasm structure's index spaces.
 
 use crate::encode::{Encoder, MAX_U32_LENGTH};
-use crate::ir::Local;
+use crate::ir::{InstrLocId, Local};
 use crate::map::{IdHashMap, IdHashSet};
 use crate::{CodeTransform, Global, GlobalId, Memory, MemoryId, Module, Table, TableId};
 use crate::{Data, DataId, Element, ElementId, Function, FunctionId};
 use crate::{Type, TypeId};
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 
 pub struct EmitContext<'a> {
     pub module: &'a Module,
@@ -16,11 +16,102 @@ pub struct EmitContext<'a> {
     pub encoder: Encoder<'a>,
     pub locals: IdHashMap<Function, IdHashSet<Local>>,
     pub code_transform: CodeTransform,
+    /// Whether to record where instructions end up in `code_transform`.
+    pub generate_code_transform: bool,
+    /// A hash of the emitted code section's payload, if one was emitted.
+    pub code_section_hash: Option<u64>,
+    pub layout: SectionLayout,
 }
 
 pub struct SubContext<'a, 'cx> {
     cx: &'cx mut EmitContext<'a>,
     write_size_to: usize,
+    /// The id of the top-level section this is emitting, if it is one, so
+    /// that its range can be recorded in the layout.
+    section: Option<u8>,
+}
+
+/// Where each section, and each function body within the code section, ended
+/// up in an emitted wasm module.
+///
+/// All ranges are byte offsets from the start of the emitted module, so they
+/// are in the same terms as the offsets `ModuleConfig::preserve_code_transform`
+/// maps instructions to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionLayout {
+    /// Each section's id and the range of bytes it takes up, including its id
+    /// and size, in the order they were emitted. Custom sections all have the
+    /// id `0`.
+    pub sections: Vec<(u8, Range<usize>)>,
+    /// Each function body in the code section and the range of bytes it takes
+    /// up, not including the body's size, in the order they were emitted.
+    pub functions: Vec<(FunctionId, Range<usize>)>,
+}
+
+impl SectionLayout {
+    /// Get the range of the first section emitted with the given id.
+    pub fn section(&self, id: u8) -> Option<Range<usize>> {
+        self.sections
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, range)| range.clone())
+    }
+
+    /// Get the range of the given function's body.
+    pub fn function(&self, id: FunctionId) -> Option<Range<usize>> {
+        self.functions
+            .iter()
+            .find(|(f, _)| *f == id)
+            .map(|(_, range)| range.clone())
+    }
+}
+
+/// Maps the instructions in an emitted module's code section to the locations
+/// they were parsed from, as returned by `Module::emit_wasm_with_source_map`.
+///
+/// Only instructions with a non-default `InstrLocId`, which are those parsed
+/// from a wasm module, are included. An instruction may have been emitted at
+/// several offsets, for example if its function was inlined, or at none if it
+/// was removed.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    // Sorted by output offset.
+    entries: Vec<(InstrLocId, usize)>,
+}
+
+impl SourceMap {
+    pub(crate) fn new(mut entries: CodeTransform) -> SourceMap {
+        entries.sort_by_key(|(_, offset)| *offset);
+        SourceMap { entries }
+    }
+
+    /// Get the offsets, from the start of the emitted module, that the
+    /// instruction parsed from `loc` was emitted at.
+    pub fn output_offsets(&self, loc: InstrLocId) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .filter(move |(l, _)| *l == loc)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Get the location of the instruction that was emitted at `offset`, or
+    /// of the closest instruction emitted before it if none was emitted
+    /// exactly there.
+    ///
+    /// Returns `None` if no instruction was emitted at or before `offset`.
+    pub fn input_location(&self, offset: usize) -> Option<InstrLocId> {
+        match self.entries.binary_search_by_key(&offset, |(_, o)| *o) {
+            Ok(i) => Some(self.entries[i].0),
+            Err(0) => None,
+            Err(i) => Some(self.entries[i - 1].0),
+        }
+    }
+
+    /// Iterate over each instruction's location and the offset it was emitted
+    /// at, in the order they appear in the emitted module.
+    pub fn iter(&self) -> impl Iterator<Item = (InstrLocId, usize)> + '_ {
+        self.entries.iter().cloned()
+    }
 }
 
 /// Anything that can be lowered to raw wasm structures.
@@ -114,11 +205,46 @@ impl IdsToIndices {
     pub(crate) fn set_data_index(&mut self, id: DataId, idx: u32) {
         self.data.insert(id, idx);
     }
+
+    /// Assign every item in `module` an index, in the order they are stored
+    /// in the module rather than the order they would be emitted in.
+    ///
+    /// This is enough to estimate the size of emitted code without emitting
+    /// the whole module, since it only affects how many bytes each index
+    /// takes up.
+    pub(crate) fn estimate(module: &Module) -> IdsToIndices {
+        let mut indices = IdsToIndices::default();
+        for table in module.tables.iter() {
+            indices.push_table(table.id());
+        }
+        for ty in module.types.iter() {
+            indices.push_type(ty.id());
+        }
+        for func in module.funcs.iter() {
+            indices.push_func(func.id());
+        }
+        for global in module.globals.iter() {
+            indices.push_global(global.id());
+        }
+        for memory in module.memories.iter() {
+            indices.push_memory(memory.id());
+        }
+        for elem in module.elements.iter() {
+            indices.push_element(elem.id());
+        }
+        for (i, data) in module.data.iter().enumerate() {
+            indices.set_data_index(data.id(), i as u32);
+        }
+        indices
+    }
 }
 
 impl<'a> EmitContext<'a> {
     pub fn start_section<'b>(&'b mut self, id: Section) -> SubContext<'a, 'b> {
-        self.subsection(id as u8)
+        let id = id as u8;
+        let mut cx = self.subsection(id);
+        cx.section = Some(id);
+        cx
     }
 
     pub fn subsection<'b>(&'b mut self, id: u8) -> SubContext<'a, 'b> {
@@ -127,6 +253,7 @@ impl<'a> EmitContext<'a> {
         SubContext {
             cx: self,
             write_size_to: start,
+            section: None,
         }
     }
 
@@ -169,6 +296,62 @@ impl Drop for SubContext<'_, '_> {
         let amt = self.cx.encoder.pos() - self.write_size_to - MAX_U32_LENGTH;
         assert!(amt <= u32::max_value() as usize);
         self.cx.encoder.u32_at(self.write_size_to, amt as u32);
+        if let Some(id) = self.section {
+            // The section's id is the byte before its size.
+            let range = self.write_size_to - 1..self.cx.encoder.pos();
+            self.cx.layout.sections.push((id, range));
+        }
+    }
+}
+
+/// Which stage of standardization to emit encodings for, for proposals whose
+/// opcodes changed along the way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FeatureLevel {
+    /// The encodings walrus has always emitted, which are the ones that the
+    /// version of `wasmparser` it parses with understands. Engines that
+    /// implemented SIMD before it was standardized expect these.
+    ///
+    /// SIMD instructions that were only added at standardization, such as
+    /// `f64x2.promote_low_f32x4`, have no encoding at this level, and
+    /// `Module::emit_wasm` panics on them.
+    #[default]
+    Legacy,
+    /// The encodings from the final, standardized proposals. Note that walrus
+    /// itself can't parse modules using these yet.
+    ///
+    /// For SIMD, the legacy encodings already use the final opcodes for every
+    /// instruction walrus supports except the `any_true`s, so those are all
+    /// that this level changes, besides allowing the instructions that only
+    /// have a standard encoding.
+    Standard,
+}
+
+/// SIMD opcodes that were renumbered when the proposal was standardized, as
+/// pairs of the legacy opcode and the standard one.
+///
+/// The proposal renumbered almost all of its opcodes before the version
+/// `wasmparser` parses, so of the instructions walrus supports, only these
+/// were renumbered after it.
+const RENUMBERED_SIMD_OPCODES: &[(u32, u32)] = &[
+    // `i8x16.any_true`, `i16x8.any_true`, and `i32x4.any_true` all became
+    // `v128.any_true`, since they compute the same thing.
+    (0x62, 0x53),
+    (0x82, 0x53),
+    (0xa2, 0x53),
+];
+
+impl FeatureLevel {
+    /// Get the opcode to emit after the `0xfd` prefix for the SIMD
+    /// instruction whose legacy opcode is `opcode`.
+    pub(crate) fn simd_opcode(self, opcode: u32) -> u32 {
+        match self {
+            FeatureLevel::Legacy => opcode,
+            FeatureLevel::Standard => RENUMBERED_SIMD_OPCODES
+                .iter()
+                .find(|(legacy, _)| *legacy == opcode)
+                .map_or(opcode, |(_, standard)| *standard),
+        }
     }
 }
 
@@ -1,45 +1,88 @@
-pub const MAX_U32_LENGTH: usize = 5;
-
+//! Encoding of the primitives that wasm binaries are made of.
+
+pub(crate) const MAX_U32_LENGTH: usize = 5;
+
+/// Writes the primitives of the wasm binary format, such as LEB128 integers
+/// and length-prefixed strings, to the end of a byte buffer.
+///
+/// This is the same encoder walrus uses to emit modules, which makes it handy
+/// for building the payload of a custom section.
+///
+/// # Example
+///
+/// Encoding a `name` section that only has a module name subsection:
+///
+/// ```
+/// use walrus::Encoder;
+///
+/// let mut subsection = Vec::new();
+/// Encoder::new(&mut subsection).str("my-module");
+///
+/// let mut data = Vec::new();
+/// let mut encoder = Encoder::new(&mut data);
+/// encoder.byte(0); // the module name subsection
+/// encoder.bytes(&subsection);
+/// assert_eq!(data, b"\x00\x0a\x09my-module");
+/// ```
 #[derive(Debug)]
 pub struct Encoder<'a> {
     dst: &'a mut Vec<u8>,
 }
 
 impl<'data> Encoder<'data> {
+    /// Create an encoder that appends to `dst`.
     pub fn new(dst: &'data mut Vec<u8>) -> Encoder<'data> {
         Encoder { dst }
     }
 
+    /// Write a single byte.
     pub fn byte(&mut self, byte: u8) {
         self.dst.push(byte);
     }
 
+    /// Write a byte vector: the length of `bytes` followed by `bytes`.
     pub fn bytes(&mut self, bytes: &[u8]) {
         self.usize(bytes.len());
         self.raw(bytes);
     }
 
+    /// Write a name: the length of `data` in bytes followed by its UTF-8
+    /// encoding.
     pub fn str(&mut self, data: &str) {
         self.bytes(data.as_bytes())
     }
 
+    /// Write a length or count as an unsigned LEB128 `u32`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amt` doesn't fit in a `u32`.
     pub fn usize(&mut self, amt: usize) {
         assert!(amt <= u32::max_value() as usize);
         self.u32(amt as u32)
     }
 
+    /// Write an unsigned LEB128 `u32`.
     pub fn u32(&mut self, amt: u32) {
         leb128::write::unsigned(&mut self.dst, amt.into()).unwrap();
     }
 
+    /// Write an unsigned LEB128 `u64`.
+    pub fn u64(&mut self, amt: u64) {
+        leb128::write::unsigned(&mut self.dst, amt).unwrap();
+    }
+
+    /// Write a signed LEB128 `i32`.
     pub fn i32(&mut self, val: i32) {
         leb128::write::signed(&mut self.dst, val.into()).unwrap();
     }
 
+    /// Write a signed LEB128 `i64`.
     pub fn i64(&mut self, val: i64) {
         leb128::write::signed(&mut self.dst, val).unwrap();
     }
 
+    /// Write the little-endian bits of an `f32`.
     pub fn f32(&mut self, val: f32) {
         let bits = val.to_bits();
         for i in 0..4 {
@@ -47,6 +90,7 @@ impl<'data> Encoder<'data> {
         }
     }
 
+    /// Write the little-endian bits of an `f64`.
     pub fn f64(&mut self, val: f64) {
         let bits = val.to_bits();
         for i in 0..8 {
@@ -54,13 +98,14 @@ impl<'data> Encoder<'data> {
         }
     }
 
+    /// Write `raw` as is, without a length prefix.
     pub fn raw(&mut self, raw: &[u8]) {
         self.dst.extend_from_slice(raw);
     }
 
     /// Reserves `bytes` bytes of space, returning the position at which the
     /// reservation starts
-    pub fn reserve(&mut self, bytes: usize) -> usize {
+    pub(crate) fn reserve(&mut self, bytes: usize) -> usize {
         let start = self.dst.len();
         for _ in 0..bytes {
             self.byte(0);
@@ -70,17 +115,30 @@ impl<'data> Encoder<'data> {
 
     /// Reserves space to write a uleb128 `u32`, returning the postition at
     /// hwich it can be written.
-    pub fn reserve_u32(&mut self) -> usize {
+    pub(crate) fn reserve_u32(&mut self) -> usize {
         self.reserve(MAX_U32_LENGTH)
     }
 
+    /// The number of bytes in the buffer, including any that were there
+    /// before this encoder was created.
     pub fn pos(&self) -> usize {
         self.dst.len()
     }
 
+    /// Get the bytes that have been written since position `pos`.
+    pub(crate) fn bytes_since(&self, pos: usize) -> &[u8] {
+        &self.dst[pos..]
+    }
+
+    /// Write an unsigned LEB128 `u32` padded to its maximum length.
+    pub(crate) fn u32_padded(&mut self, amt: u32) {
+        let pos = self.reserve_u32();
+        self.u32_at(pos, amt);
+    }
+
     // TODO: don't write this code here, use upstream once
     // gimli-rs/leb128#6 is implemented
-    pub fn u32_at(&mut self, pos: usize, mut amt: u32) {
+    pub(crate) fn u32_at(&mut self, pos: usize, mut amt: u32) {
         for i in 0..MAX_U32_LENGTH {
             let flag = if i == MAX_U32_LENGTH - 1 { 0 } else { 0x80 };
             self.dst[pos + i] = (amt as u8) & 0x7f | flag;
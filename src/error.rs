@@ -25,3 +25,108 @@ impl fmt::Display for ErrorKind {
 }
 
 impl std::error::Error for ErrorKind {}
+
+/// The kind of index space that an `IndexError` refers to.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum IndexKind {
+    /// The table index space.
+    Table,
+    /// The type index space.
+    Type,
+    /// The function index space.
+    Function,
+    /// The global index space.
+    Global,
+    /// The memory index space.
+    Memory,
+    /// The element segment index space.
+    Element,
+    /// The data segment index space.
+    Data,
+    /// The index space of a function's parameters and locals.
+    Local,
+}
+
+impl fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexKind::Table => "table".fmt(f),
+            IndexKind::Type => "type".fmt(f),
+            IndexKind::Function => "function".fmt(f),
+            IndexKind::Global => "global".fmt(f),
+            IndexKind::Memory => "memory".fmt(f),
+            IndexKind::Element => "element".fmt(f),
+            IndexKind::Data => "data".fmt(f),
+            IndexKind::Local => "local".fmt(f),
+        }
+    }
+}
+
+/// An index in the input wasm referred to an item that doesn't exist.
+///
+/// This is returned (wrapped in an `anyhow::Error`) from the `get_*` methods
+/// of `IndicesToIds`, and can be recovered with `Error::downcast_ref`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct IndexError {
+    /// The index space that was being indexed into.
+    pub kind: IndexKind,
+    /// The out-of-range index.
+    pub index: u32,
+    /// The number of items in the index space, i.e. one past the largest
+    /// valid index.
+    pub max: u32,
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            IndexKind::Local => write!(
+                f,
+                "local index {} out of range (function has {} locals)",
+                self.index, self.max
+            ),
+            kind => write!(
+                f,
+                "{} index {} out of range (max {})",
+                kind, self.index, self.max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+/// A non-fatal issue found while parsing a module.
+///
+/// Parsing carries on past these, but tools may want to tell their users
+/// about them. See `Module::from_buffer_with_warnings`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Warning {
+    /// A custom section that walrus doesn't know how to parse, which is kept
+    /// as a `RawCustomSection`.
+    UnknownCustomSection {
+        /// The custom section's name.
+        name: String,
+    },
+    /// A custom section that walrus knows how to parse, such as `name` or
+    /// `producers`, failed to parse and was ignored.
+    InvalidCustomSection {
+        /// The custom section's name.
+        name: String,
+        /// Why the custom section failed to parse.
+        message: String,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnknownCustomSection { name } => {
+                write!(f, "unknown custom section `{}`", name)
+            }
+            Warning::InvalidCustomSection { name, message } => {
+                write!(f, "failed to parse `{}` custom section: {}", name, message)
+            }
+        }
+    }
+}
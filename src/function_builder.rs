@@ -1,6 +1,7 @@
 use crate::ir::*;
 use crate::tombstone_arena::TombstoneArena;
 use crate::{FunctionId, LocalFunction, ModuleFunctions, ModuleTypes, TypeId, ValType};
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
 /// Build instances of `LocalFunction`.
@@ -12,12 +13,13 @@ use std::ops::{Deref, DerefMut};
 ///
 /// * For a bit more realistic example, see
 ///   [`examples/build-wasm-from-scratch.rs`](https://github.com/rustwasm/walrus/blob/master/examples/build-wasm-from-scratch.rs).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FunctionBuilder {
     pub(crate) arena: TombstoneArena<InstrSeq>,
     pub(crate) ty: TypeId,
     pub(crate) entry: Option<InstrSeqId>,
     pub(crate) name: Option<String>,
+    pub(crate) stack_checks: Option<Box<StackChecks>>,
 }
 
 impl FunctionBuilder {
@@ -26,10 +28,65 @@ impl FunctionBuilder {
         types: &mut ModuleTypes,
         params: &[ValType],
         results: &[ValType],
+    ) -> FunctionBuilder {
+        FunctionBuilder::with_entry(types, params, results, None)
+    }
+
+    /// Creates a new, empty function builder that checks the operand stack as
+    /// instructions are appended, for debugging code generators.
+    ///
+    /// The builder keeps a shadow operand stack for every instruction sequence
+    /// and panics, naming the offending instruction, as soon as an instruction
+    /// would pop more operands than are on the stack. It also panics when a
+    /// `block`, `loop`, or `if`/`else` is appended, or the builder is finished,
+    /// and the corresponding sequence doesn't end with exactly its results on
+    /// the stack.
+    ///
+    /// Only the number of operands is tracked, not their types. Called
+    /// functions and block types are looked up in `funcs` and `types` as they
+    /// are when the builder is created. After an instruction whose effect
+    /// can't be determined that way, or after instructions are spliced into or
+    /// otherwise edited in a sequence, that sequence is no longer checked.
+    ///
+    /// # Example
+    ///
+    /// ```should_panic
+    /// use walrus::ValType;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let mut builder = walrus::FunctionBuilder::with_stack_checks(
+    ///     &mut module.types,
+    ///     &module.funcs,
+    ///     &[],
+    ///     &[],
+    /// );
+    ///
+    /// // Panics: the block is declared to produce an `i32`, but doesn't.
+    /// builder.func_body().block(ValType::I32, |block| {
+    ///     block.i32_const(1).drop();
+    /// });
+    /// ```
+    pub fn with_stack_checks(
+        types: &mut ModuleTypes,
+        funcs: &ModuleFunctions,
+        params: &[ValType],
+        results: &[ValType],
+    ) -> FunctionBuilder {
+        FunctionBuilder::with_entry(types, params, results, Some(funcs))
+    }
+
+    fn with_entry(
+        types: &mut ModuleTypes,
+        params: &[ValType],
+        results: &[ValType],
+        checked_funcs: Option<&ModuleFunctions>,
     ) -> FunctionBuilder {
         let ty = types.add(params, results);
         let mut builder = FunctionBuilder::without_entry(ty);
         let entry_ty = types.add_entry_ty(results);
+        if let Some(funcs) = checked_funcs {
+            builder.stack_checks = Some(Box::new(StackChecks::new(types, funcs)));
+        }
         let entry = builder.dangling_instr_seq(entry_ty).id;
         builder.entry = Some(entry);
         builder
@@ -44,6 +101,7 @@ impl FunctionBuilder {
             ty,
             entry: None,
             name: None,
+            stack_checks: None,
         }
     }
 
@@ -130,6 +188,9 @@ impl FunctionBuilder {
     pub fn dangling_instr_seq(&mut self, ty: impl Into<InstrSeqType>) -> InstrSeqBuilder {
         let ty = ty.into();
         let id = self.arena.alloc_with_id(|id| InstrSeq::new(id, ty));
+        if let Some(checks) = &mut self.stack_checks {
+            checks.start_seq(id, ty);
+        }
         InstrSeqBuilder { id, builder: self }
     }
 
@@ -150,7 +211,10 @@ impl FunctionBuilder {
     /// let function_id = builder.finish(vec![], &mut module.funcs);
     /// # let _ = function_id;
     /// ```
-    pub fn finish(self, args: Vec<LocalId>, funcs: &mut ModuleFunctions) -> FunctionId {
+    pub fn finish(mut self, args: Vec<LocalId>, funcs: &mut ModuleFunctions) -> FunctionId {
+        if let Some(checks) = self.stack_checks.take() {
+            checks.end_seq(self.func_body_id());
+        }
         let func = LocalFunction::new(args, self);
         funcs.add_local(func)
     }
@@ -178,15 +242,22 @@ impl InstrSeqBuilder<'_> {
 
     /// Get this instruction sequence's instructions mutably.
     pub fn instrs_mut(&mut self) -> &mut Vec<(Instr, InstrLocId)> {
+        if let Some(checks) = &mut self.builder.stack_checks {
+            checks.forget(self.id);
+        }
         &mut self.builder.arena[self.id].instrs
     }
 
     /// Pushes a new instruction onto this builder's sequence.
     #[inline]
     pub fn instr(&mut self, instr: impl Into<Instr>) -> &mut Self {
+        let instr = instr.into();
+        if let Some(checks) = &mut self.builder.stack_checks {
+            checks.push_instr(self.id, &instr, &self.builder.arena);
+        }
         self.builder.arena[self.id]
             .instrs
-            .push((instr.into(), Default::default()));
+            .push((instr, Default::default()));
         self
     }
 
@@ -197,6 +268,9 @@ impl InstrSeqBuilder<'_> {
     /// Panics if `position > self.instrs.len()`.
     #[inline]
     pub fn instr_at(&mut self, position: usize, instr: impl Into<Instr>) -> &mut Self {
+        if let Some(checks) = &mut self.builder.stack_checks {
+            checks.forget(self.id);
+        }
         self.builder.arena[self.id]
             .instrs
             .insert(position, (instr.into(), Default::default()));
@@ -402,6 +476,48 @@ impl InstrSeqBuilder<'_> {
     ///         },
     ///     );
     /// ```
+    ///
+    /// Both arms are allocated with the given block type, so there is no need
+    /// to create the `consequent` and `alternative` sequences by hand. For
+    /// example, this builds a `max(a, b)` function:
+    ///
+    /// ```
+    /// use walrus::ir::BinaryOp;
+    /// use walrus::ValType;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let a = module.locals.add(ValType::I32);
+    /// let b = module.locals.add(ValType::I32);
+    ///
+    /// let mut builder = walrus::FunctionBuilder::new(
+    ///     &mut module.types,
+    ///     &[ValType::I32, ValType::I32],
+    ///     &[ValType::I32],
+    /// );
+    /// builder
+    ///     .func_body()
+    ///     // (if (result i32) (i32.gt_s (local.get $a) (local.get $b))
+    ///     //   (then (local.get $a))
+    ///     //   (else (local.get $b)))
+    ///     .local_get(a)
+    ///     .local_get(b)
+    ///     .binop(BinaryOp::I32GtS)
+    ///     .if_else(
+    ///         ValType::I32,
+    ///         |then| {
+    ///             then.local_get(a);
+    ///         },
+    ///         |else_| {
+    ///             else_.local_get(b);
+    ///         },
+    ///     );
+    /// let max = builder.finish(vec![a, b], &mut module.funcs);
+    /// module.exports.add("max", max);
+    ///
+    /// // The emitted module is valid.
+    /// let wasm = module.emit_wasm();
+    /// assert!(walrus::Module::from_buffer(&wasm).is_ok());
+    /// ```
     pub fn if_else(
         &mut self,
         ty: impl Into<InstrSeqType>,
@@ -506,3 +622,121 @@ impl DerefMut for InstrSeqBuilder<'_> {
         &mut *self.builder
     }
 }
+
+/// The shadow operand stacks kept by a builder made with
+/// `FunctionBuilder::with_stack_checks`.
+#[derive(Clone, Debug)]
+pub(crate) struct StackChecks {
+    /// The number of parameters and results of each type.
+    signatures: HashMap<TypeId, (usize, usize)>,
+    /// The type of each function.
+    funcs: HashMap<FunctionId, TypeId>,
+    /// The shadow stack of each instruction sequence, or `None` if we can't
+    /// keep track of it.
+    seqs: HashMap<InstrSeqId, Option<ShadowStack>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ShadowStack {
+    /// The number of operands on the stack.
+    height: u32,
+    /// The number of results the sequence must end with.
+    results: u32,
+    /// Are the following instructions unreachable?
+    unreachable: bool,
+}
+
+impl StackChecks {
+    fn new(types: &ModuleTypes, funcs: &ModuleFunctions) -> StackChecks {
+        StackChecks {
+            signatures: types
+                .iter()
+                .map(|ty| (ty.id(), (ty.params().len(), ty.results().len())))
+                .collect(),
+            funcs: funcs.iter().map(|f| (f.id(), f.ty())).collect(),
+            seqs: HashMap::new(),
+        }
+    }
+
+    fn signature(&self, ty: InstrSeqType) -> Option<(u32, u32)> {
+        match ty {
+            InstrSeqType::Simple(None) => Some((0, 0)),
+            InstrSeqType::Simple(Some(_)) => Some((0, 1)),
+            InstrSeqType::MultiValue(ty) => self
+                .signatures
+                .get(&ty)
+                .map(|(params, results)| (*params as u32, *results as u32)),
+        }
+    }
+
+    fn start_seq(&mut self, seq: InstrSeqId, ty: InstrSeqType) {
+        let stack = self.signature(ty).map(|(params, results)| ShadowStack {
+            height: params,
+            results,
+            unreachable: false,
+        });
+        self.seqs.insert(seq, stack);
+    }
+
+    fn forget(&mut self, seq: InstrSeqId) {
+        self.seqs.insert(seq, None);
+    }
+
+    /// Panic if `seq` doesn't end with exactly its results on the stack.
+    fn end_seq(&self, seq: InstrSeqId) {
+        if let Some(Some(stack)) = self.seqs.get(&seq) {
+            if !stack.unreachable && stack.height != stack.results {
+                panic!(
+                    "instruction sequence {:?} ends with {} values on the stack, \
+                     but its type expects {}",
+                    seq, stack.height, stack.results
+                );
+            }
+        }
+    }
+
+    /// Update `seq`'s shadow stack for appending `instr` to it, panicking if
+    /// that would underflow the stack.
+    fn push_instr(&mut self, seq: InstrSeqId, instr: &Instr, arena: &TombstoneArena<InstrSeq>) {
+        let effect = match instr {
+            Instr::Block(Block { seq: nested }) | Instr::Loop(Loop { seq: nested }) => {
+                self.end_seq(*nested);
+                self.signature(arena[*nested].ty)
+            }
+            Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) => {
+                self.end_seq(*consequent);
+                self.end_seq(*alternative);
+                self.signature(arena[*consequent].ty)
+                    .map(|(params, results)| (params + 1, results))
+            }
+            _ => instr.stack_effect_with(
+                |func| self.funcs.get(&func).cloned(),
+                |ty| self.signatures.get(&ty).cloned(),
+            ),
+        };
+
+        let stack = match self.seqs.get_mut(&seq) {
+            Some(Some(stack)) if !stack.unreachable => stack,
+            _ => return,
+        };
+        let (pops, pushes) = match effect {
+            Some(effect) => effect,
+            None => {
+                self.seqs.insert(seq, None);
+                return;
+            }
+        };
+        if stack.height < pops {
+            panic!(
+                "stack underflow in instruction sequence {:?}: `{:?}` pops {} values, \
+                 but only {} are on the stack",
+                seq, instr, pops, stack.height
+            );
+        }
+        stack.height = stack.height - pops + pushes;
+        stack.unreachable = instr.following_instructions_are_unreachable();
+    }
+}
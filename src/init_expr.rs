@@ -67,22 +67,10 @@ impl Emit for InitExpr {
     }
 }
 
+/// Convert the immediate of a `v128.const` to the `u128` of a `Value::V128`.
+///
+/// The immediate's bytes are little-endian, so its first byte is the least
+/// significant byte of the result. `Value::emit` does the inverse.
 pub(crate) fn v128_to_u128(value: &wasmparser::V128) -> u128 {
-    let n = value.bytes();
-    ((n[0] as u128) << 0)
-        | ((n[1] as u128) << 8)
-        | ((n[2] as u128) << 16)
-        | ((n[3] as u128) << 24)
-        | ((n[4] as u128) << 32)
-        | ((n[5] as u128) << 40)
-        | ((n[6] as u128) << 48)
-        | ((n[7] as u128) << 56)
-        | ((n[8] as u128) << 64)
-        | ((n[9] as u128) << 72)
-        | ((n[10] as u128) << 80)
-        | ((n[11] as u128) << 88)
-        | ((n[12] as u128) << 96)
-        | ((n[13] as u128) << 104)
-        | ((n[14] as u128) << 112)
-        | ((n[15] as u128) << 120)
+    u128::from_le_bytes(*value.bytes())
 }
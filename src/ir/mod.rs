@@ -9,10 +9,12 @@ pub use self::traversals::*;
 
 use crate::encode::Encoder;
 use crate::{
-    DataId, ElementId, FunctionId, GlobalId, LocalFunction, MemoryId, ModuleTypes, TableId, TypeId,
-    ValType,
+    DataId, ElementId, FunctionId, GlobalId, LocalFunction, MemoryId, Module, ModuleTypes, TableId,
+    TypeId, ValType,
 };
 use id_arena::Id;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
 use walrus_macro::walrus_instr;
@@ -97,6 +99,23 @@ impl InstrSeqType {
             _ => InstrSeqType::MultiValue(types.find(params, results)?),
         })
     }
+
+    /// Get the parameter types of an instruction sequence with this signature.
+    pub fn params<'a>(&'a self, types: &'a ModuleTypes) -> &'a [ValType] {
+        match self {
+            InstrSeqType::Simple(_) => &[],
+            InstrSeqType::MultiValue(ty) => types.params(*ty),
+        }
+    }
+
+    /// Get the result types of an instruction sequence with this signature.
+    pub fn results<'a>(&'a self, types: &'a ModuleTypes) -> &'a [ValType] {
+        match self {
+            InstrSeqType::Simple(None) => &[],
+            InstrSeqType::Simple(Some(ty)) => std::slice::from_ref(ty),
+            InstrSeqType::MultiValue(ty) => types.results(*ty),
+        }
+    }
 }
 
 impl From<Option<ValType>> for InstrSeqType {
@@ -121,7 +140,7 @@ impl From<TypeId> for InstrSeqType {
 }
 
 /// A symbolic original wasm operator source location.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct InstrLocId(u32);
 
 const DEFAULT_INSTR_LOC_ID: u32 = 0xffff_ffff;
@@ -152,8 +171,14 @@ impl Default for InstrLocId {
     }
 }
 
+/// A map from instruction sequences to some data about them, e.g. for passes
+/// to annotate blocks with profile weights or loop trip counts.
+///
+/// See `LocalFunction::metadata` for attaching these to a function.
+pub type SeqMap<T> = BTreeMap<InstrSeqId, T>;
+
 /// A sequence of instructions.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct InstrSeq {
     id: InstrSeqId,
 
@@ -267,6 +292,9 @@ pub enum Instr {
         /// The type signature of the function we're calling
         ty: TypeId,
         /// The table which `func` below is indexing into
+        ///
+        /// This is always encoded as a table index immediate, which for table
+        /// 0 is the same `0x00` byte as the MVP's reserved byte.
         table: TableId,
     },
 
@@ -604,9 +632,65 @@ pub enum Value {
     /// A constant 64-bit float
     F64(f64),
     /// A constant 128-bit vector register
+    ///
+    /// The value is the 16 bytes of the `v128.const` immediate interpreted as
+    /// a little-endian integer: the first byte in the binary encoding is the
+    /// least significant byte, and lane 0 of any shape occupies the least
+    /// significant bits. See also the `v128_lanes_*` and `v128_from_*`
+    /// methods.
     V128(u128),
 }
 
+macro_rules! v128_lanes {
+    ( $( $get:ident, $from:ident, $lane:ident, $shape:expr, $count:expr; )* ) => {
+        impl Value {
+            $(
+                #[doc = concat!(
+                    "View a `V128` value as ",
+                    $shape,
+                    " lanes, with lane 0 in the least significant bits.\n\n",
+                    "Returns `None` if this is not a `V128` value."
+                )]
+                pub fn $get(&self) -> Option<[$lane; $count]> {
+                    let bytes = match self {
+                        Value::V128(n) => n.to_le_bytes(),
+                        _ => return None,
+                    };
+                    let mut lanes = [0 as $lane; $count];
+                    let size = 16 / $count;
+                    for (lane, chunk) in lanes.iter_mut().zip(bytes.chunks(size)) {
+                        *lane = $lane::from_le_bytes(chunk.try_into().unwrap());
+                    }
+                    Some(lanes)
+                }
+
+                #[doc = concat!(
+                    "Construct a `V128` value from ",
+                    $shape,
+                    " lanes, with lane 0 in the least significant bits."
+                )]
+                pub fn $from(lanes: [$lane; $count]) -> Value {
+                    let mut bytes = [0; 16];
+                    let size = 16 / $count;
+                    for (lane, chunk) in lanes.iter().zip(bytes.chunks_mut(size)) {
+                        chunk.copy_from_slice(&lane.to_le_bytes());
+                    }
+                    Value::V128(u128::from_le_bytes(bytes))
+                }
+            )*
+        }
+    };
+}
+
+v128_lanes! {
+    v128_lanes_i8x16, v128_from_i8x16, i8, "`i8x16`", 16;
+    v128_lanes_i16x8, v128_from_i16x8, i16, "`i16x8`", 8;
+    v128_lanes_i32x4, v128_from_i32x4, i32, "`i32x4`", 4;
+    v128_lanes_i64x2, v128_from_i64x2, i64, "`i64x2`", 2;
+    v128_lanes_f32x4, v128_from_f32x4, f32, "`f32x4`", 4;
+    v128_lanes_f64x2, v128_from_f64x2, f64, "`f64x2`", 2;
+}
+
 impl Value {
     pub(crate) fn emit(&self, encoder: &mut Encoder) {
         match *self {
@@ -628,9 +712,7 @@ impl Value {
             }
             Value::V128(n) => {
                 encoder.raw(&[0xfd, 0x0c]); // v128.const
-                for i in 0..16 {
-                    encoder.byte((n >> (i * 8)) as u8);
-                }
+                encoder.raw(&n.to_le_bytes());
             }
         }
     }
@@ -861,6 +943,23 @@ pub enum BinaryOp {
     I32x4MaxU,
 }
 
+impl BinaryOp {
+    /// Can this operation trap?
+    pub(crate) fn can_trap(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::I32DivS
+                | BinaryOp::I32DivU
+                | BinaryOp::I32RemS
+                | BinaryOp::I32RemU
+                | BinaryOp::I64DivS
+                | BinaryOp::I64DivU
+                | BinaryOp::I64RemS
+                | BinaryOp::I64RemU
+        )
+    }
+}
+
 /// Possible unary operations in wasm
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Debug)]
@@ -967,6 +1066,12 @@ pub enum UnaryOp {
     I32x4TruncSatF32x4U,
     F32x4ConvertI32x4S,
     F32x4ConvertI32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
 
     I32TruncSSatF32,
     I32TruncUSatF32,
@@ -987,6 +1092,37 @@ pub enum UnaryOp {
     I32x4WidenHighI16x8U,
 }
 
+impl UnaryOp {
+    /// Can this operation trap?
+    pub(crate) fn can_trap(&self) -> bool {
+        matches!(
+            self,
+            UnaryOp::I32TruncSF32
+                | UnaryOp::I32TruncUF32
+                | UnaryOp::I32TruncSF64
+                | UnaryOp::I32TruncUF64
+                | UnaryOp::I64TruncSF32
+                | UnaryOp::I64TruncUF32
+                | UnaryOp::I64TruncSF64
+                | UnaryOp::I64TruncUF64
+        )
+    }
+
+    /// Was this operation only added when its proposal was standardized, so
+    /// that it has no `FeatureLevel::Legacy` encoding?
+    pub(crate) fn is_standard_only(&self) -> bool {
+        matches!(
+            self,
+            UnaryOp::I32x4TruncSatF64x2SZero
+                | UnaryOp::I32x4TruncSatF64x2UZero
+                | UnaryOp::F64x2ConvertLowI32x4S
+                | UnaryOp::F64x2ConvertLowI32x4U
+                | UnaryOp::F32x4DemoteF64x2Zero
+                | UnaryOp::F64x2PromoteLowF32x4
+        )
+    }
+}
+
 /// The different kinds of load instructions that are part of a `Load` IR node
 #[derive(Debug, Copy, Clone)]
 #[allow(missing_docs)]
@@ -1116,6 +1252,40 @@ impl StoreKind {
     }
 }
 
+/// An immediate operand of an instruction, as returned by `Instr::immediates`.
+#[derive(Debug, Copy, Clone)]
+pub enum Immediate {
+    /// The body of a `block`, `loop`, or `if`/`else`, or the target of a
+    /// branch.
+    Seq(InstrSeqId),
+    /// A referenced function.
+    Function(FunctionId),
+    /// A referenced type.
+    Type(TypeId),
+    /// A referenced table.
+    Table(TableId),
+    /// A referenced local.
+    Local(LocalId),
+    /// A referenced global.
+    Global(GlobalId),
+    /// A referenced memory.
+    Memory(MemoryId),
+    /// A referenced data segment.
+    Data(DataId),
+    /// A referenced element segment.
+    Element(ElementId),
+    /// A constant value.
+    Value(Value),
+    /// The alignment and offset of a memory access.
+    MemArg(MemArg),
+    /// The lane index of a lane extraction or replacement.
+    Lane(u8),
+    /// The lane indices of an `i8x16.shuffle`.
+    ShuffleIndices(ShuffleIndices),
+    /// The type of a typed `select`, or of a reference.
+    ValType(ValType),
+}
+
 /// Arguments to memory operations, containing a constant offset from a dynamic
 /// address as well as a predicted alignment.
 #[derive(Debug, Copy, Clone)]
@@ -1223,6 +1393,308 @@ impl Instr {
             | Instr::Drop(..) => false,
         }
     }
+
+    /// Is this instruction a branch to a label: a `br`, `br_if`, or
+    /// `br_table`?
+    ///
+    /// `return` is not considered a branch.
+    pub fn is_branch(&self) -> bool {
+        matches!(self, Instr::Br(..) | Instr::BrIf(..) | Instr::BrTable(..))
+    }
+
+    /// Does this instruction always end its basic block, never continuing to
+    /// the next instruction: a `br`, `br_table`, `return`, or `unreachable`?
+    ///
+    /// This is the same as `following_instructions_are_unreachable`.
+    pub fn is_unconditional_terminator(&self) -> bool {
+        self.following_instructions_are_unreachable()
+    }
+
+    /// Might this instruction have any effect besides popping its operands
+    /// and pushing its results?
+    ///
+    /// Returns `true` for instructions that write to locals, globals, memory,
+    /// or tables, that call functions, that branch, or that might trap, such
+    /// as loads and integer division. Blocks, loops, and ifs are always
+    /// considered to have side effects, without looking at their bodies.
+    /// Returns `false` for pure computations like `local.get`, constants, and
+    /// most arithmetic, which can be freely removed when their results are
+    /// unused.
+    pub fn has_side_effects(&self) -> bool {
+        match self {
+            Instr::Binop(Binop { op }) => op.can_trap(),
+            Instr::Unop(Unop { op }) => op.can_trap(),
+
+            Instr::LocalGet(..)
+            | Instr::GlobalGet(..)
+            | Instr::Const(..)
+            | Instr::Select(..)
+            | Instr::Drop(..)
+            | Instr::MemorySize(..)
+            | Instr::TableSize(..)
+            | Instr::RefNull(..)
+            | Instr::RefIsNull(..)
+            | Instr::RefFunc(..)
+            | Instr::V128Bitselect(..)
+            | Instr::V128Swizzle(..)
+            | Instr::V128Shuffle(..) => false,
+
+            // No `_` arm to make sure that we properly update this function as
+            // we add support for new instructions.
+            Instr::Block(..)
+            | Instr::Loop(..)
+            | Instr::IfElse(..)
+            | Instr::Br(..)
+            | Instr::BrIf(..)
+            | Instr::BrTable(..)
+            | Instr::Return(..)
+            | Instr::Unreachable(..)
+            | Instr::Call(..)
+            | Instr::CallIndirect(..)
+            | Instr::LocalSet(..)
+            | Instr::LocalTee(..)
+            | Instr::GlobalSet(..)
+            | Instr::MemoryGrow(..)
+            | Instr::MemoryInit(..)
+            | Instr::DataDrop(..)
+            | Instr::MemoryCopy(..)
+            | Instr::MemoryFill(..)
+            | Instr::Load(..)
+            | Instr::Store(..)
+            | Instr::AtomicRmw(..)
+            | Instr::Cmpxchg(..)
+            | Instr::AtomicNotify(..)
+            | Instr::AtomicWait(..)
+            | Instr::AtomicFence(..)
+            | Instr::TableGet(..)
+            | Instr::TableSet(..)
+            | Instr::TableGrow(..)
+            | Instr::TableFill(..)
+            | Instr::TableInit(..)
+            | Instr::TableCopy(..)
+            | Instr::ElemDrop(..)
+            | Instr::LoadSimd(..) => true,
+        }
+    }
+
+    /// Is this instruction free of any effect that could be observed from
+    /// outside of the function it is in, if that function has no loops?
+    ///
+    /// This is `!has_side_effects()`, except that writes to locals, blocks,
+    /// ifs, and branches are allowed too, since locals are private to the
+    /// function and without loops every branch goes forwards.
+    pub(crate) fn is_pure_without_loops(&self) -> bool {
+        match self {
+            Instr::LocalSet(..)
+            | Instr::LocalTee(..)
+            | Instr::Block(..)
+            | Instr::IfElse(..)
+            | Instr::Br(..)
+            | Instr::BrIf(..)
+            | Instr::BrTable(..)
+            | Instr::Return(..) => true,
+            _ => !self.has_side_effects(),
+        }
+    }
+
+    /// Get this instruction's immediate operands, in the order they are
+    /// encoded in the binary format. For example, `table.copy` gives the
+    /// destination table before the source table.
+    ///
+    /// Immediates that only select which operation to perform, such as the
+    /// operator of a `Binop` or the kind of a `Load`, are not included, but
+    /// lane indices of lane operations are. Branch targets and the bodies of
+    /// blocks are given as instruction sequences rather than relative
+    /// depths.
+    pub fn immediates(&self) -> Vec<Immediate> {
+        use self::Immediate as I;
+
+        match self {
+            Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => vec![I::Seq(*seq)],
+            Instr::Br(Br { block }) | Instr::BrIf(BrIf { block }) => vec![I::Seq(*block)],
+            Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) => vec![I::Seq(*consequent), I::Seq(*alternative)],
+            Instr::BrTable(BrTable { blocks, default }) => blocks
+                .iter()
+                .chain(Some(default))
+                .map(|b| I::Seq(*b))
+                .collect(),
+
+            Instr::Call(Call { func }) | Instr::RefFunc(RefFunc { func }) => {
+                vec![I::Function(*func)]
+            }
+            Instr::CallIndirect(CallIndirect { ty, table }) => {
+                vec![I::Type(*ty), I::Table(*table)]
+            }
+            Instr::LocalGet(LocalGet { local })
+            | Instr::LocalSet(LocalSet { local })
+            | Instr::LocalTee(LocalTee { local }) => vec![I::Local(*local)],
+            Instr::GlobalGet(GlobalGet { global }) | Instr::GlobalSet(GlobalSet { global }) => {
+                vec![I::Global(*global)]
+            }
+            Instr::Const(Const { value }) => vec![I::Value(*value)],
+
+            Instr::Binop(Binop { op }) => match op {
+                BinaryOp::I8x16ReplaceLane { idx }
+                | BinaryOp::I16x8ReplaceLane { idx }
+                | BinaryOp::I32x4ReplaceLane { idx }
+                | BinaryOp::I64x2ReplaceLane { idx }
+                | BinaryOp::F32x4ReplaceLane { idx }
+                | BinaryOp::F64x2ReplaceLane { idx } => vec![I::Lane(*idx)],
+                _ => vec![],
+            },
+            Instr::Unop(Unop { op }) => match op {
+                UnaryOp::I8x16ExtractLaneS { idx }
+                | UnaryOp::I8x16ExtractLaneU { idx }
+                | UnaryOp::I16x8ExtractLaneS { idx }
+                | UnaryOp::I16x8ExtractLaneU { idx }
+                | UnaryOp::I32x4ExtractLane { idx }
+                | UnaryOp::I64x2ExtractLane { idx }
+                | UnaryOp::F32x4ExtractLane { idx }
+                | UnaryOp::F64x2ExtractLane { idx } => vec![I::Lane(*idx)],
+                _ => vec![],
+            },
+            Instr::Select(Select { ty }) => ty.iter().map(|ty| I::ValType(*ty)).collect(),
+            Instr::RefNull(RefNull { ty }) | Instr::RefIsNull(RefIsNull { ty }) => {
+                vec![I::ValType(*ty)]
+            }
+            Instr::V128Shuffle(V128Shuffle { indices }) => vec![I::ShuffleIndices(*indices)],
+
+            Instr::MemorySize(MemorySize { memory })
+            | Instr::MemoryGrow(MemoryGrow { memory })
+            | Instr::MemoryFill(MemoryFill { memory }) => vec![I::Memory(*memory)],
+            Instr::MemoryInit(MemoryInit { memory, data }) => {
+                vec![I::Data(*data), I::Memory(*memory)]
+            }
+            Instr::DataDrop(DataDrop { data }) => vec![I::Data(*data)],
+            Instr::MemoryCopy(MemoryCopy { src, dst }) => vec![I::Memory(*dst), I::Memory(*src)],
+            Instr::Load(Load { memory, arg, .. })
+            | Instr::Store(Store { memory, arg, .. })
+            | Instr::AtomicRmw(AtomicRmw { memory, arg, .. })
+            | Instr::Cmpxchg(Cmpxchg { memory, arg, .. })
+            | Instr::AtomicNotify(AtomicNotify { memory, arg })
+            | Instr::AtomicWait(AtomicWait { memory, arg, .. })
+            | Instr::LoadSimd(LoadSimd { memory, arg, .. }) => {
+                vec![I::Memory(*memory), I::MemArg(*arg)]
+            }
+
+            Instr::TableGet(TableGet { table })
+            | Instr::TableSet(TableSet { table })
+            | Instr::TableGrow(TableGrow { table })
+            | Instr::TableSize(TableSize { table })
+            | Instr::TableFill(TableFill { table }) => vec![I::Table(*table)],
+            Instr::TableInit(TableInit { table, elem }) => {
+                vec![I::Element(*elem), I::Table(*table)]
+            }
+            Instr::ElemDrop(ElemDrop { elem }) => vec![I::Element(*elem)],
+            Instr::TableCopy(TableCopy { src, dst }) => vec![I::Table(*dst), I::Table(*src)],
+
+            Instr::Unreachable(..)
+            | Instr::Drop(..)
+            | Instr::Return(..)
+            | Instr::AtomicFence(..)
+            | Instr::V128Bitselect(..)
+            | Instr::V128Swizzle(..) => vec![],
+        }
+    }
+
+    /// The number of operands this instruction pops off the stack and the
+    /// number of results it pushes back on, respectively.
+    ///
+    /// The effect of `block`, `loop`, and `if`/`else` instructions depends on
+    /// the type of their instruction sequences, which live in the enclosing
+    /// function, so only the `if` condition is counted for them here. Likewise
+    /// the operands that `br`, `br_table`, and `return` transfer to their
+    /// target are not counted, since the rest of the sequence is unreachable
+    /// after them anyways.
+    ///
+    /// This lets code generators that keep their own shadow stack know what
+    /// an instruction will do to it before building it.
+    pub fn stack_effect(&self, module: &Module) -> (u32, u32) {
+        self.stack_effect_with(
+            |func| Some(module.funcs.get(func).ty()),
+            |ty| {
+                let (params, results) = module.types.params_results(ty);
+                Some((params.len(), results.len()))
+            },
+        )
+        .unwrap()
+    }
+
+    /// Like `stack_effect`, but looking up the type of called functions with
+    /// `func_ty`, and the number of parameters and results of a type with
+    /// `signature`.
+    ///
+    /// Returns `None` if either lookup does.
+    pub(crate) fn stack_effect_with(
+        &self,
+        func_ty: impl Fn(FunctionId) -> Option<TypeId>,
+        signature: impl Fn(TypeId) -> Option<(usize, usize)>,
+    ) -> Option<(u32, u32)> {
+        let (pops, pushes) = match self {
+            Instr::Call(Call { func }) => signature(func_ty(*func)?)?,
+            Instr::CallIndirect(CallIndirect { ty, .. }) => {
+                let (params, results) = signature(*ty)?;
+                (params + 1, results)
+            }
+
+            Instr::Block(..)
+            | Instr::Loop(..)
+            | Instr::Br(..)
+            | Instr::Return(..)
+            | Instr::Unreachable(..)
+            | Instr::DataDrop(..)
+            | Instr::ElemDrop(..)
+            | Instr::AtomicFence(..) => (0, 0),
+
+            Instr::IfElse(..)
+            | Instr::BrIf(..)
+            | Instr::BrTable(..)
+            | Instr::LocalSet(..)
+            | Instr::GlobalSet(..)
+            | Instr::Drop(..) => (1, 0),
+
+            Instr::LocalGet(..)
+            | Instr::GlobalGet(..)
+            | Instr::Const(..)
+            | Instr::MemorySize(..)
+            | Instr::TableSize(..)
+            | Instr::RefNull(..)
+            | Instr::RefFunc(..) => (0, 1),
+
+            Instr::LocalTee(..)
+            | Instr::Unop(..)
+            | Instr::MemoryGrow(..)
+            | Instr::Load(..)
+            | Instr::LoadSimd(..)
+            | Instr::TableGet(..)
+            | Instr::RefIsNull(..) => (1, 1),
+
+            Instr::Store(..) | Instr::TableSet(..) => (2, 0),
+
+            Instr::Binop(..)
+            | Instr::AtomicRmw(..)
+            | Instr::AtomicNotify(..)
+            | Instr::TableGrow(..)
+            | Instr::V128Swizzle(..)
+            | Instr::V128Shuffle(..) => (2, 1),
+
+            Instr::MemoryInit(..)
+            | Instr::MemoryCopy(..)
+            | Instr::MemoryFill(..)
+            | Instr::TableFill(..)
+            | Instr::TableInit(..)
+            | Instr::TableCopy(..) => (3, 0),
+
+            Instr::Select(..)
+            | Instr::Cmpxchg(..)
+            | Instr::AtomicWait(..)
+            | Instr::V128Bitselect(..) => (3, 1),
+        };
+        Some((pops as u32, pushes as u32))
+    }
 }
 
 /// Anything that can be visited by a `Visitor`.
@@ -123,6 +123,116 @@ pub fn dfs_in_order<'instr>(
     }
 }
 
+/// Perform an intra-procedural, depth-first, post-order traversal of the IR.
+///
+/// * *Intra-procedural*: Only traverses IR within a function. Does not cross
+///   function boundaries (although it will report edges to other functions via
+///   `visit_function_id` calls on the visitor).
+///
+/// * *Depth-first, post-order*: Visits instruction sequences in a bottom-up
+///   manner, where every instruction sequence nested within a parent sequence
+///   is visited before the parent sequence itself. See
+///   [Wikipedia][post-order] for details. This is the order that backward
+///   analyses, such as dead store elimination, typically want.
+///
+/// Each instruction sequence is visited as a whole: `start_instr_seq`, then
+/// each of its instructions in order, then `end_instr_seq`. Nested sequences
+/// are visited in the order they are defined.
+///
+/// The traversals begins at the `start` instruction sequence and goes from
+/// there. To traverse everything in a function, pass `func.entry_block()` as
+/// `start`.
+///
+/// This implementation is iterative &mdash; not recursive &mdash; and so it
+/// will not blow the call stack on deeply nested Wasm (although it may still
+/// OOM).
+///
+/// [post-order]: https://en.wikipedia.org/wiki/Tree_traversal#Post-order_(LRN)
+pub fn dfs_post_order<'instr>(
+    visitor: &mut impl Visitor<'instr>,
+    func: &'instr LocalFunction,
+    start: InstrSeqId,
+) {
+    // The stack of instruction sequences we still need to visit, and whether
+    // their nested sequences have already been visited.
+    let mut stack = vec![(start, false)];
+
+    while let Some((seq_id, children_visited)) = stack.pop() {
+        let seq = func.block(seq_id);
+
+        if !children_visited {
+            // Come back to this sequence after all of its nested sequences,
+            // which are pushed in reverse so that they are popped in order.
+            stack.push((seq_id, true));
+            for (instr, _) in seq.instrs.iter().rev() {
+                match instr {
+                    Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
+                        stack.push((*seq, false));
+                    }
+                    Instr::IfElse(IfElse {
+                        consequent,
+                        alternative,
+                    }) => {
+                        stack.push((*alternative, false));
+                        stack.push((*consequent, false));
+                    }
+                    _ => {}
+                }
+            }
+            continue;
+        }
+
+        visitor.start_instr_seq(seq);
+        seq.visit(visitor);
+        for (instr, loc) in seq.instrs.iter() {
+            log::trace!("dfs_post_order: visit_instr({:?})", instr);
+            visitor.visit_instr(instr, loc);
+            instr.visit(visitor);
+        }
+        visitor.end_instr_seq(seq);
+    }
+}
+
+/// A visitor for instructions across every local function in a module.
+///
+/// This is driven by `Module::visit_all_instrs`, which performs a
+/// `dfs_in_order` traversal of each local function and reports which function
+/// each instruction belongs to.
+///
+/// Every method has a default, provided implementation that does nothing.
+#[allow(unused_variables)]
+pub trait ModuleVisitor<'instr> {
+    /// Called before the traversal starts visiting the instructions of the
+    /// local function `func`.
+    #[inline]
+    fn start_function(&mut self, func: FunctionId, local: &'instr LocalFunction) {
+        // ...
+    }
+
+    /// Visit an `Instr` within the local function `func`.
+    #[inline]
+    fn visit_instr(
+        &mut self,
+        func: FunctionId,
+        instr: &'instr Instr,
+        instr_loc: &'instr InstrLocId,
+    ) {
+        // ...
+    }
+}
+
+/// Adapts a `ModuleVisitor` to a `Visitor` for a single function.
+pub(crate) struct FunctionVisitor<'a, V> {
+    pub(crate) func: FunctionId,
+    pub(crate) visitor: &'a mut V,
+}
+
+impl<'instr, V: ModuleVisitor<'instr>> Visitor<'instr> for FunctionVisitor<'_, V> {
+    fn visit_instr(&mut self, instr: &'instr Instr, instr_loc: &'instr InstrLocId) {
+        self.visitor.visit_instr(self.func, instr, instr_loc);
+    }
+}
+
 /// Perform an intra-procedural, depth-first, pre-order, mutable traversal of
 /// the IR.
 ///
@@ -343,6 +453,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dfs_post_order() {
+        let mut module = crate::Module::default();
+        let func = make_test_func(&mut module);
+
+        let mut visitor = TestVisitor::default();
+        crate::ir::dfs_post_order(&mut visitor, func, func.entry_block());
+
+        let mut expected = vec![];
+        // consequent
+        expected.extend(vec!["start", "3", "drop", "end"]);
+        // alternative
+        expected.extend(vec!["start", "4", "drop", "end"]);
+        // block
+        expected.extend(vec!["start", "2", "drop", "if-else", "5", "drop", "end"]);
+        // function entry
+        expected.extend(vec!["start", "1", "drop", "block", "6", "drop", "end"]);
+
+        assert_eq!(
+            visitor.visits,
+            expected.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn dfs_pre_order_mut() {
         let mut module = crate::Module::default();
@@ -382,4 +516,41 @@ mod tests {
             expected.iter().map(|s| s.to_string()).collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn visit_instr_locations() {
+        struct AssignLocs(u32);
+
+        impl VisitorMut for AssignLocs {
+            fn visit_instr_mut(&mut self, _: &mut Instr, loc: &mut InstrLocId) {
+                *loc = InstrLocId::new(self.0);
+                self.0 += 1;
+            }
+        }
+
+        #[derive(Default)]
+        struct ReadConstLocs(Vec<(i32, u32)>);
+
+        impl<'a> Visitor<'a> for ReadConstLocs {
+            fn visit_instr(&mut self, instr: &'a Instr, loc: &'a InstrLocId) {
+                if let Instr::Const(Const {
+                    value: Value::I32(x),
+                }) = instr
+                {
+                    self.0.push((*x, loc.data()));
+                }
+            }
+        }
+
+        let mut module = crate::Module::default();
+        let func = make_test_func(&mut module);
+        crate::ir::dfs_pre_order_mut(&mut AssignLocs(0), func, func.entry_block());
+
+        let mut visitor = ReadConstLocs::default();
+        crate::ir::dfs_in_order(&mut visitor, func, func.entry_block());
+        assert_eq!(
+            visitor.0,
+            vec![(1, 0), (2, 5), (3, 10), (4, 12), (5, 8), (6, 3)]
+        );
+    }
 }
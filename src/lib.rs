@@ -32,8 +32,9 @@ pub mod passes;
 mod tombstone_arena;
 mod ty;
 
-pub use crate::emit::IdsToIndices;
-pub use crate::error::{ErrorKind, Result};
+pub use crate::emit::{FeatureLevel, IdsToIndices, SectionLayout, SourceMap};
+pub use crate::encode::Encoder;
+pub use crate::error::{ErrorKind, IndexError, IndexKind, Result, Warning};
 pub use crate::function_builder::{FunctionBuilder, InstrSeqBuilder};
 pub use crate::init_expr::InitExpr;
 pub use crate::ir::{Local, LocalId};
@@ -1,9 +1,11 @@
-use crate::error::Result;
+use crate::emit::FeatureLevel;
+use crate::error::{Result, Warning};
 use crate::ir::InstrLocId;
 use crate::module::Module;
 use crate::parse::IndicesToIds;
 use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Configuration for a `Module` which currently affects parsing.
 #[derive(Default)]
@@ -15,9 +17,20 @@ pub struct ModuleConfig {
     pub(crate) skip_producers_section: bool,
     pub(crate) skip_name_section: bool,
     pub(crate) preserve_code_transform: bool,
+    pub(crate) relocatable: bool,
+    pub(crate) fold_float_constants: bool,
+    pub(crate) gc_passthrough: bool,
+    pub(crate) feature_level: FeatureLevel,
+    pub(crate) strip_unreachable: bool,
+    pub(crate) max_memories: Option<usize>,
+    pub(crate) max_tables: Option<usize>,
+    pub(crate) max_memory_pages: Option<u32>,
+    pub(crate) max_locals_per_function: Option<u32>,
+    pub(crate) max_operand_stack_depth: Option<u32>,
     pub(crate) on_parse:
         Option<Box<dyn Fn(&mut Module, &IndicesToIds) -> Result<()> + Sync + Send + 'static>>,
     pub(crate) on_instr_loc: Option<Box<dyn Fn(&usize) -> InstrLocId + Sync + Send + 'static>>,
+    pub(crate) on_dwarf_invalidation: Option<Arc<dyn Fn(&str) -> bool + Sync + Send + 'static>>,
 }
 
 impl Clone for ModuleConfig {
@@ -32,6 +45,19 @@ impl Clone for ModuleConfig {
             skip_producers_section: self.skip_producers_section,
             skip_name_section: self.skip_name_section,
             preserve_code_transform: self.preserve_code_transform,
+            relocatable: self.relocatable,
+            fold_float_constants: self.fold_float_constants,
+            gc_passthrough: self.gc_passthrough,
+            feature_level: self.feature_level,
+            strip_unreachable: self.strip_unreachable,
+            max_memories: self.max_memories,
+            max_tables: self.max_tables,
+            max_memory_pages: self.max_memory_pages,
+            max_locals_per_function: self.max_locals_per_function,
+            max_operand_stack_depth: self.max_operand_stack_depth,
+            // This is consulted when emitting, so it needs to stick around in
+            // the config cloned into a parsed module.
+            on_dwarf_invalidation: self.on_dwarf_invalidation.clone(),
 
             // ... and this is left empty.
             on_parse: None,
@@ -52,8 +78,19 @@ impl fmt::Debug for ModuleConfig {
             ref skip_producers_section,
             ref skip_name_section,
             ref preserve_code_transform,
+            ref relocatable,
+            ref fold_float_constants,
+            ref gc_passthrough,
+            ref feature_level,
+            ref strip_unreachable,
+            ref max_memories,
+            ref max_tables,
+            ref max_memory_pages,
+            ref max_locals_per_function,
+            ref max_operand_stack_depth,
             ref on_parse,
             ref on_instr_loc,
+            ref on_dwarf_invalidation,
         } = self;
 
         f.debug_struct("ModuleConfig")
@@ -67,8 +104,22 @@ impl fmt::Debug for ModuleConfig {
             .field("skip_producers_section", skip_producers_section)
             .field("skip_name_section", skip_name_section)
             .field("preserve_code_transform", preserve_code_transform)
+            .field("relocatable", relocatable)
+            .field("fold_float_constants", fold_float_constants)
+            .field("gc_passthrough", gc_passthrough)
+            .field("feature_level", feature_level)
+            .field("strip_unreachable", strip_unreachable)
+            .field("max_memories", max_memories)
+            .field("max_tables", max_tables)
+            .field("max_memory_pages", max_memory_pages)
+            .field("max_locals_per_function", max_locals_per_function)
+            .field("max_operand_stack_depth", max_operand_stack_depth)
             .field("on_parse", &on_parse.as_ref().map(|_| ".."))
             .field("on_instr_loc", &on_instr_loc.as_ref().map(|_| ".."))
+            .field(
+                "on_dwarf_invalidation",
+                &on_dwarf_invalidation.as_ref().map(|_| ".."),
+            )
             .finish()
     }
 }
@@ -85,6 +136,10 @@ impl ModuleConfig {
     /// By default this flag is `false`. Note that any emitted DWARF is
     /// currently wildly incorrect and buggy, and is also larger than the wasm
     /// itself!
+    ///
+    /// When this flag is `false`, `.debug_*` custom sections from the input
+    /// are still emitted unchanged as long as the emitted code section is
+    /// identical to the parsed one. See also `on_dwarf_invalidation`.
     pub fn generate_dwarf(&mut self, generate: bool) -> &mut ModuleConfig {
         self.generate_dwarf = generate;
         self
@@ -157,6 +212,58 @@ impl ModuleConfig {
         self
     }
 
+    /// Sets the maximum number of memories, imported or defined, that a module
+    /// may have to pass validation.
+    ///
+    /// This is checked as part of strict validation, see `strict_validate`.
+    /// By default there is no limit beyond the wasm specification's.
+    pub fn max_memories(&mut self, max: usize) -> &mut ModuleConfig {
+        self.max_memories = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of tables, imported or defined, that a module
+    /// may have to pass validation.
+    ///
+    /// This is checked as part of strict validation, see `strict_validate`.
+    /// By default there is no limit beyond the wasm specification's.
+    pub fn max_tables(&mut self, max: usize) -> &mut ModuleConfig {
+        self.max_tables = Some(max);
+        self
+    }
+
+    /// Sets the maximum initial size, in pages, of any memory in a module for
+    /// it to pass validation.
+    ///
+    /// This is checked as part of strict validation, see `strict_validate`.
+    /// By default there is no limit beyond the wasm specification's.
+    pub fn max_memory_pages(&mut self, max: u32) -> &mut ModuleConfig {
+        self.max_memory_pages = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of locals, not counting parameters, that a
+    /// function may declare.
+    ///
+    /// Unlike the other limits this is checked while parsing, before any of
+    /// the locals are created, so that a module declaring an absurd number of
+    /// locals is rejected cheaply. By default there is no limit beyond the
+    /// wasm specification's.
+    pub fn max_locals_per_function(&mut self, max: u32) -> &mut ModuleConfig {
+        self.max_locals_per_function = Some(max);
+        self
+    }
+
+    /// Sets the maximum depth that a function's operand stack may reach.
+    ///
+    /// This is checked while parsing function bodies, and a module with a
+    /// function that pushes more values than this is rejected. By default
+    /// there is no limit.
+    pub fn max_operand_stack_depth(&mut self, max: u32) -> &mut ModuleConfig {
+        self.max_operand_stack_depth = Some(max);
+        self
+    }
+
     /// Provide a function that is invoked after successfully parsing a module,
     /// and gets access to data structures that only exist at parse time, such
     /// as the map from indices in the original Wasm to the new walrus IDs.
@@ -192,6 +299,25 @@ impl ModuleConfig {
         self
     }
 
+    /// Provide a function that decides whether to keep a `.debug_*` custom
+    /// section when emitting a module whose code section has changed since it
+    /// was parsed.
+    ///
+    /// The function is given the custom section's name, and returns `true` to
+    /// emit the section anyways, despite the code offsets in it being stale.
+    /// Without this function such sections are dropped with a warning. This is
+    /// not consulted when `generate_dwarf` is enabled.
+    ///
+    /// Unlike `on_parse`, this function is kept when cloning a `ModuleConfig`,
+    /// so modules parsed with this config will use it when emitted.
+    pub fn on_dwarf_invalidation<F>(&mut self, f: F) -> &mut ModuleConfig
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.on_dwarf_invalidation = Some(Arc::new(f) as _);
+        self
+    }
+
     /// Sets a flag to whether code transform is preverved during parsing.
     ///
     /// By default this flag is `false`.
@@ -200,10 +326,89 @@ impl ModuleConfig {
         self
     }
 
+    /// Sets a flag to whether the code section is emitted so that it can be
+    /// relocated by a linker, like the code in `.o` object files.
+    ///
+    /// When enabled, the function, type, and global indices referenced by
+    /// instructions are always encoded as 5-byte padded LEB128, so that a
+    /// linker can patch them in place. Note that no relocation custom
+    /// sections are emitted yet.
+    ///
+    /// By default this flag is `false`.
+    pub fn relocatable(&mut self, relocatable: bool) -> &mut ModuleConfig {
+        self.relocatable = relocatable;
+        self
+    }
+
+    /// Sets a flag to whether `Module::fold_constants` may fold floating
+    /// point operations whose result is NaN.
+    ///
+    /// Folding operations with other results is always exact, since wasm
+    /// rounds like Rust does. A NaN result though is only specified to be some
+    /// arithmetic NaN, whose bits the folded constant would fix, so such
+    /// operations are left for the engine to compute unless this is enabled.
+    ///
+    /// By default this flag is `false`.
+    pub fn fold_float_constants(&mut self, fold: bool) -> &mut ModuleConfig {
+        self.fold_float_constants = fold;
+        self
+    }
+
+    /// Sets a flag to whether GC `struct` and `array` type definitions are
+    /// preserved as opaque types instead of being rejected.
+    ///
+    /// walrus doesn't understand the GC proposal, but with this enabled a
+    /// module that defines GC types can still be parsed and emitted again, with
+    /// those types kept byte-for-byte. See `Type::is_opaque`. Only types whose
+    /// fields don't refer to other types by index can be preserved, and GC
+    /// instructions and reference types in function signatures are still
+    /// rejected.
+    ///
+    /// By default this flag is `false`.
+    pub fn gc_passthrough(&mut self, passthrough: bool) -> &mut ModuleConfig {
+        self.gc_passthrough = passthrough;
+        self
+    }
+
+    /// Sets which stage of standardization to emit encodings for, for
+    /// proposals whose opcodes were renumbered before they were finished,
+    /// such as SIMD.
+    ///
+    /// This lets the emitted module target engines that only decode one or
+    /// the other. By default this is `FeatureLevel::Legacy`.
+    pub fn feature_level(&mut self, level: FeatureLevel) -> &mut ModuleConfig {
+        self.feature_level = level;
+        self
+    }
+
+    /// Sets whether instructions that follow an unconditional branch,
+    /// `return`, or `unreachable` in their block are left out when emitting
+    /// functions.
+    ///
+    /// This only affects the emitted wasm; the functions in the `Module`
+    /// itself are left as they are. See `LocalFunction::dead_code_ranges`.
+    ///
+    /// By default this flag is `false`.
+    pub fn strip_unreachable(&mut self, strip: bool) -> &mut ModuleConfig {
+        self.strip_unreachable = strip;
+        self
+    }
+
     /// Parses an in-memory WebAssembly file into a `Module` using this
     /// configuration.
     pub fn parse(&self, wasm: &[u8]) -> Result<Module> {
-        Module::parse(wasm, self)
+        Module::parse(wasm, self, &mut Vec::new())
+    }
+
+    /// Parses an in-memory WebAssembly file into a `Module` using this
+    /// configuration, also returning any non-fatal issues found along the
+    /// way.
+    ///
+    /// The warnings are returned even if parsing fails.
+    pub fn parse_with_warnings(&self, wasm: &[u8]) -> (Result<Module>, Vec<Warning>) {
+        let mut warnings = Vec::new();
+        let module = Module::parse(wasm, self, &mut warnings);
+        (module, warnings)
     }
 
     /// Parses a WebAssembly file into a `Module` using this configuration.
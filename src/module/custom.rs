@@ -4,6 +4,7 @@ use crate::passes::Roots;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::CodeTransform;
 use crate::IdsToIndices;
+use crate::Module;
 use std::any::Any;
 use std::borrow::Cow;
 use std::fmt::{self, Debug};
@@ -59,6 +60,12 @@ pub trait CustomSection: WalrusAny + Debug + Send + Sync {
     fn apply_code_transform(&mut self, transform: &CodeTransform) {
         let _ = transform;
     }
+
+    /// Clone this custom section, for when its `Module` is cloned.
+    ///
+    /// For a section that implements `Clone`, this is usually just
+    /// `Box::new(self.clone())`.
+    fn clone_section(&self) -> Box<dyn CustomSection>;
 }
 
 /// A wrapper trait around `any` but implemented for all types that already
@@ -85,6 +92,12 @@ impl<T: Any + Send + Sync> WalrusAny for T {
     }
 }
 
+impl Clone for Box<dyn CustomSection> {
+    fn clone(&self) -> Box<dyn CustomSection> {
+        self.clone_section()
+    }
+}
+
 impl dyn CustomSection {
     /// Convert this custom section to `Box<Any>` to do dynamic downcasting
     pub fn into_any(self: Box<Self>) -> Box<dyn Any + Send + 'static> {
@@ -120,6 +133,10 @@ impl CustomSection for RawCustomSection {
     fn data(&self, _: &IdsToIndices) -> Cow<[u8]> {
         self.data.as_slice().into()
     }
+
+    fn clone_section(&self) -> Box<dyn CustomSection> {
+        Box::new(self.clone())
+    }
 }
 
 /// A common trait for custom section identifiers.
@@ -285,7 +302,7 @@ impl Tombstone for Option<Box<dyn CustomSection>> {
 /// * Use `my_module.customs.add(my_custom_section)` to add the custom section
 ///   back into the module, so `walrus` can emit the processed/updated version
 ///   of the custom section.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleCustomSections {
     arena: TombstoneArena<Option<Box<dyn CustomSection>>>,
 }
@@ -428,3 +445,30 @@ impl ModuleCustomSections {
             .next()
     }
 }
+
+impl Module {
+    /// Get the payload of the raw custom section named `name`, if there is
+    /// one.
+    ///
+    /// Only `RawCustomSection`s are considered, since other custom sections'
+    /// payloads aren't known until they're emitted. If there are multiple raw
+    /// custom sections with this name, this returns the first one.
+    pub fn custom_section(&self, name: &str) -> Option<&[u8]> {
+        self.customs
+            .iter()
+            .filter(|(_, s)| s.name() == name)
+            .filter_map(|(_, s)| s.as_any().downcast_ref::<RawCustomSection>())
+            .map(|s| &s.data[..])
+            .next()
+    }
+
+    /// Remove the raw custom section named `name`, returning its payload, so
+    /// that it is not emitted.
+    ///
+    /// This is handy for stripping large sections like `.debug_info`. Like
+    /// `custom_section` it only considers `RawCustomSection`s, and removes
+    /// just the first one with this name.
+    pub fn remove_custom_section(&mut self, name: &str) -> Option<Vec<u8>> {
+        self.customs.remove_raw(name).map(|s| s.data)
+    }
+}
@@ -18,7 +18,7 @@ pub type DataId = Id<Data>;
 /// memory (or memories) via the `memory.init` instruction (passive data
 /// segments). See the `kind` member and `DataKind` type for more details on the
 /// active/passive distinction.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Data {
     id: DataId,
     /// What kind of data segment is this? Passive or active?
@@ -28,7 +28,7 @@ pub struct Data {
 }
 
 /// The kind of data segment: passive or active.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum DataKind {
     /// An active data segment that is automatically initialized at some address
     /// in a static memory.
@@ -84,7 +84,7 @@ impl Data {
 
 /// All passive data sections of a wasm module, used to initialize memories via
 /// various instructions.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleData {
     arena: TombstoneArena<Data>,
 }
@@ -113,6 +113,11 @@ impl ModuleData {
         self.arena.iter().map(|(_, f)| f)
     }
 
+    /// Get a mutable reference to this module's data segments.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Data> {
+        self.arena.iter_mut().map(|(_, f)| f)
+    }
+
     /// Add a data segment
     pub fn add(&mut self, kind: DataKind, value: Vec<u8>) -> DataId {
         let id = self.arena.next_id();
@@ -160,6 +165,19 @@ impl ModuleData {
 }
 
 impl Module {
+    /// Get the active data segments that initialize the given memory, in the
+    /// order they are emitted.
+    pub fn data_segments_for(&self, memory: MemoryId) -> Vec<DataId> {
+        self.data
+            .iter()
+            .filter(|d| match &d.kind {
+                DataKind::Active(active) => active.memory == memory,
+                DataKind::Passive => false,
+            })
+            .map(|d| d.id())
+            .collect()
+    }
+
     /// Called when we see the data section section to create an id for all data
     /// indices
     ///
@@ -190,7 +208,12 @@ impl Module {
         log::debug!("parse data section");
         if let Some(count) = data_count {
             if count != section.get_count() {
-                bail!("data count section mismatches actual data section");
+                bail!(
+                    "data count section mismatches actual data section: \
+                     declared {} data segments, found {}",
+                    count,
+                    section.get_count()
+                );
             }
         }
         for (i, segment) in section.into_iter().enumerate() {
@@ -0,0 +1,148 @@
+//! Handling of the wasm `dylink.0` section
+//!
+//! Specified upstream at
+//! https://github.com/WebAssembly/tool-conventions/blob/master/DynamicLinking.md
+
+use crate::emit::{Emit, EmitContext};
+use crate::error::Result;
+use crate::module::Module;
+use anyhow::bail;
+
+const MEM_INFO: u8 = 1;
+const NEEDED: u8 = 2;
+const EXPORT_INFO: u8 = 3;
+const IMPORT_INFO: u8 = 4;
+
+/// Representation of the wasm custom section `dylink.0`, which describes a
+/// module that is a shared library for dynamic linking.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DylinkInfo {
+    /// The memory and table space this library needs, if specified.
+    pub mem_info: Option<DylinkMemInfo>,
+    /// The shared libraries this library depends on.
+    pub needed: Vec<String>,
+    /// Extra information about exports, as pairs of an export's name and its
+    /// symbol flags.
+    pub export_info: Vec<(String, u32)>,
+    /// Extra information about imports, as triples of an import's module,
+    /// field name, and symbol flags.
+    pub import_info: Vec<(String, String, u32)>,
+    /// Subsections that aren't understood, as their id and raw payload. These
+    /// are emitted verbatim after the others.
+    pub unknown: Vec<(u8, Vec<u8>)>,
+}
+
+/// The `WASM_DYLINK_MEM_INFO` subsection of the `dylink.0` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DylinkMemInfo {
+    /// The size of the library's static data, in bytes.
+    pub memory_size: u32,
+    /// The alignment of the library's static data, as a power of two.
+    pub memory_alignment: u32,
+    /// The number of table slots the library needs.
+    pub table_size: u32,
+    /// The alignment of the library's table slots, as a power of two.
+    pub table_alignment: u32,
+}
+
+impl Module {
+    /// Parse a `dylink.0` section from the custom section payload specified.
+    pub(crate) fn parse_dylink_section(&mut self, data: &[u8]) -> Result<()> {
+        log::debug!("parse dylink.0 section");
+
+        let mut info = DylinkInfo::default();
+        let mut reader = wasmparser::BinaryReader::new(data);
+        while !reader.eof() {
+            let id = reader.read_u8()? as u8;
+            let len = reader.read_var_u32()?;
+            let payload = reader.read_bytes(len as usize)?;
+            let mut sub = wasmparser::BinaryReader::new(payload);
+            match id {
+                MEM_INFO => {
+                    info.mem_info = Some(DylinkMemInfo {
+                        memory_size: sub.read_var_u32()?,
+                        memory_alignment: sub.read_var_u32()?,
+                        table_size: sub.read_var_u32()?,
+                        table_alignment: sub.read_var_u32()?,
+                    });
+                }
+                NEEDED => {
+                    for _ in 0..sub.read_var_u32()? {
+                        info.needed.push(sub.read_string()?.to_string());
+                    }
+                }
+                EXPORT_INFO => {
+                    for _ in 0..sub.read_var_u32()? {
+                        let name = sub.read_string()?.to_string();
+                        let flags = sub.read_var_u32()?;
+                        info.export_info.push((name, flags));
+                    }
+                }
+                IMPORT_INFO => {
+                    for _ in 0..sub.read_var_u32()? {
+                        let module = sub.read_string()?.to_string();
+                        let field = sub.read_string()?.to_string();
+                        let flags = sub.read_var_u32()?;
+                        info.import_info.push((module, field, flags));
+                    }
+                }
+                _ => {
+                    info.unknown.push((id, payload.to_vec()));
+                    continue;
+                }
+            }
+            if !sub.eof() {
+                bail!("trailing bytes at the end of dylink.0 subsection {}", id);
+            }
+        }
+
+        self.dylink = Some(info);
+        Ok(())
+    }
+}
+
+impl Emit for DylinkInfo {
+    fn emit(&self, cx: &mut EmitContext) {
+        log::debug!("emit dylink.0 section");
+        let mut cx = cx.custom_section("dylink.0");
+
+        if let Some(mem_info) = &self.mem_info {
+            let mut cx = cx.subsection(MEM_INFO);
+            cx.encoder.u32(mem_info.memory_size);
+            cx.encoder.u32(mem_info.memory_alignment);
+            cx.encoder.u32(mem_info.table_size);
+            cx.encoder.u32(mem_info.table_alignment);
+        }
+
+        if !self.needed.is_empty() {
+            let mut cx = cx.subsection(NEEDED);
+            cx.encoder.usize(self.needed.len());
+            for name in self.needed.iter() {
+                cx.encoder.str(name);
+            }
+        }
+
+        if !self.export_info.is_empty() {
+            let mut cx = cx.subsection(EXPORT_INFO);
+            cx.encoder.usize(self.export_info.len());
+            for (name, flags) in self.export_info.iter() {
+                cx.encoder.str(name);
+                cx.encoder.u32(*flags);
+            }
+        }
+
+        if !self.import_info.is_empty() {
+            let mut cx = cx.subsection(IMPORT_INFO);
+            cx.encoder.usize(self.import_info.len());
+            for (module, field, flags) in self.import_info.iter() {
+                cx.encoder.str(module);
+                cx.encoder.str(field);
+                cx.encoder.u32(*flags);
+            }
+        }
+
+        for (id, payload) in self.unknown.iter() {
+            cx.subsection(*id).encoder.raw(payload);
+        }
+    }
+}
@@ -10,7 +10,7 @@ use anyhow::{bail, Context};
 pub type ElementId = Id<Element>;
 
 /// A passive segment which contains a list of functions
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Element {
     id: Id<Element>,
 
@@ -20,7 +20,8 @@ pub struct Element {
     /// The type of elements in this segment
     pub ty: ValType,
 
-    /// The function members of this passive elements segment.
+    /// The function members of this passive elements segment, where `None`
+    /// is a null reference.
     pub members: Vec<Option<FunctionId>>,
 }
 
@@ -37,6 +38,24 @@ impl Element {
     pub fn id(&self) -> Id<Element> {
         self.id
     }
+
+    /// Get whether this segment is passive, declared, or active, along with
+    /// the table and offset it initializes if it is active.
+    pub fn kind(&self) -> &ElementKind {
+        &self.kind
+    }
+
+    /// Get the functions this segment contains, where `None` is a null
+    /// reference.
+    pub fn items(&self) -> &[Option<FunctionId>] {
+        &self.members
+    }
+
+    /// Replace the functions this segment contains, where `None` is a null
+    /// reference.
+    pub fn set_items(&mut self, items: Vec<Option<FunctionId>>) {
+        self.members = items;
+    }
 }
 
 impl Tombstone for Element {
@@ -47,7 +66,7 @@ impl Tombstone for Element {
 
 /// All element segments of a wasm module, used to initialize `anyfunc` tables,
 /// used as function pointers.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleElements {
     arena: TombstoneArena<Element>,
 }
@@ -101,6 +120,19 @@ impl ModuleElements {
 }
 
 impl Module {
+    /// Get the active element segments that initialize the given table, in
+    /// the order they are emitted.
+    pub fn element_segments_for(&self, table: TableId) -> Vec<ElementId> {
+        self.elements
+            .iter()
+            .filter(|e| match &e.kind {
+                ElementKind::Active { table: t, .. } => *t == table,
+                ElementKind::Passive | ElementKind::Declared => false,
+            })
+            .map(|e| e.id())
+            .collect()
+    }
+
     /// Parses a raw was section into a fully-formed `ModuleElements` instance.
     pub(crate) fn parse_elements(
         &mut self,
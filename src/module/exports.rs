@@ -4,6 +4,7 @@ use crate::emit::{Emit, EmitContext, Section};
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::{FunctionId, GlobalId, MemoryId, Module, Result, TableId};
+use std::collections::HashSet;
 
 /// The id of an export.
 pub type ExportId = Id<Export>;
@@ -45,7 +46,7 @@ pub enum ExportItem {
 }
 
 /// The set of exports in a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleExports {
     /// The arena containing this module's exports.
     arena: TombstoneArena<Export>,
@@ -153,6 +154,44 @@ impl Module {
     }
 }
 
+impl Module {
+    /// Remove every export whose name isn't in `names`.
+    ///
+    /// If `gc` is true, the `gc` pass is then run to remove everything that is
+    /// no longer reachable from the remaining exports.
+    pub fn retain_exports(&mut self, names: &HashSet<String>, gc: bool) {
+        let removed = self
+            .exports
+            .iter()
+            .filter(|e| !names.contains(&e.name))
+            .map(|e| e.id())
+            .collect::<Vec<_>>();
+        for id in removed {
+            self.exports.delete(id);
+        }
+        if gc {
+            crate::passes::gc::run(self);
+        }
+    }
+
+    /// Get the name and id of every exported mutable global, in the order
+    /// they were exported.
+    ///
+    /// Exporting a mutable global requires the mutable-globals proposal, and
+    /// hosts must bind such globals by reference rather than by value.
+    pub fn exported_mutable_globals(&self) -> Vec<(String, GlobalId)> {
+        self.exports
+            .iter()
+            .filter_map(|export| match export.item {
+                ExportItem::Global(id) if self.globals.get(id).mutable => {
+                    Some((export.name.clone(), id))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 impl Emit for ModuleExports {
     fn emit(&self, cx: &mut EmitContext) {
         log::debug!("emit export section");
@@ -222,7 +261,8 @@ impl From<TableId> for ExportItem {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{FunctionBuilder, Module};
+    use crate::ir::Value;
+    use crate::{FunctionBuilder, InitExpr, Module, ValType};
     use id_arena::Arena;
 
     /// this function always returns the same ID
@@ -249,6 +289,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn retain_exports() {
+        let mut module = Module::default();
+        let mut funcs = Vec::new();
+        for name in ["keep", "drop", "also_drop"].iter() {
+            let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+            builder.func_body().i32_const(1234).drop();
+            let id = builder.finish(vec![], &mut module.funcs);
+            module.exports.add(name, id);
+            funcs.push(id);
+        }
+
+        let names = ["keep".to_string()].iter().cloned().collect();
+        module.retain_exports(&names, false);
+        let exports = module.exports.iter().map(|e| &e.name).collect::<Vec<_>>();
+        assert_eq!(exports, ["keep"]);
+        assert_eq!(module.funcs.iter().count(), 3);
+
+        module.retain_exports(&names, true);
+        let remaining = module.funcs.iter().map(|f| f.id()).collect::<Vec<_>>();
+        assert_eq!(remaining, [funcs[0]]);
+    }
+
+    #[test]
+    fn exported_mutable_globals() {
+        let mut module = Module::default();
+        let init = InitExpr::Value(Value::I32(0));
+        let constant = module.globals.add_local(ValType::I32, false, init);
+        let counter = module.globals.add_local(ValType::I32, true, init);
+        let hidden = module
+            .globals
+            .add_local(ValType::I64, true, InitExpr::Value(Value::I64(0)));
+        module.exports.add("constant", constant);
+        module.exports.add("counter", counter);
+
+        assert_eq!(
+            module.exported_mutable_globals(),
+            [("counter".to_string(), counter)]
+        );
+        assert!(module.exports.get_exported_global(hidden).is_none());
+    }
+
     #[test]
     fn get_exported_func_should_return_none_for_unknown_function_id() {
         let module = Module::default();
@@ -105,8 +105,10 @@ impl<'a> ValidationContext<'a> {
         }
     }
 
-    pub fn push_operand(&mut self, op: Option<ValType>) {
+    pub fn push_operand(&mut self, op: Option<ValType>) -> Result<()> {
+        self.check_operand_stack_depth(1)?;
         impl_push_operand(&mut self.operands, op);
+        Ok(())
     }
 
     pub fn pop_operand(&mut self) -> Result<Option<ValType>> {
@@ -117,8 +119,21 @@ impl<'a> ValidationContext<'a> {
         impl_pop_operand_expected(&mut self.operands, &mut self.controls, expected)
     }
 
-    pub fn push_operands(&mut self, types: &[ValType]) {
+    pub fn push_operands(&mut self, types: &[ValType]) -> Result<()> {
+        self.check_operand_stack_depth(types.len())?;
         impl_push_operands(&mut self.operands, types);
+        Ok(())
+    }
+
+    /// Bail if pushing `n` more operands would exceed the configured maximum
+    /// operand stack depth.
+    fn check_operand_stack_depth(&self, n: usize) -> Result<()> {
+        if let Some(max) = self.module.config.max_operand_stack_depth {
+            if self.operands.len() + n > max as usize {
+                anyhow::bail!("operand stack depth exceeds the maximum of {}", max);
+            }
+        }
+        Ok(())
     }
 
     pub fn pop_operands(&mut self, expected: &[ValType]) -> Result<()> {
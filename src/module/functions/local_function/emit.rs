@@ -1,18 +1,21 @@
-use crate::emit::IdsToIndices;
+use crate::emit::{FeatureLevel, IdsToIndices};
 use crate::encode::Encoder;
 use crate::ir::*;
 use crate::map::IdHashMap;
 use crate::module::functions::LocalFunction;
 use crate::module::memories::MemoryId;
+use crate::module::Module;
 
 pub(crate) fn run(
     func: &LocalFunction,
+    module: &Module,
     indices: &IdsToIndices,
     local_indices: &IdHashMap<Local, u32>,
     encoder: &mut Encoder,
     map: Option<&mut Vec<(InstrLocId, usize)>>,
 ) {
     let v = &mut Emit {
+        module,
         indices,
         blocks: vec![],
         block_kinds: vec![BlockKind::FunctionEntry],
@@ -26,7 +29,106 @@ pub(crate) fn run(
     debug_assert!(v.block_kinds.is_empty());
 }
 
+/// Encode `func` like `run` does, and return how many bytes each instruction
+/// took up, in the same order as `dfs_in_order`.
+///
+/// The bytes that start and end a nested sequence, such as its block type and
+/// `end`, count towards the `block`, `loop`, or `if`/`else` instruction that
+/// it belongs to.
+pub(crate) fn instr_sizes(
+    func: &LocalFunction,
+    module: &Module,
+    indices: &IdsToIndices,
+    local_indices: &IdHashMap<Local, u32>,
+) -> Vec<((InstrSeqId, usize), usize)> {
+    let mut wasm = Vec::new();
+    let mut encoder = Encoder::new(&mut wasm);
+    let v = &mut Sizes {
+        emit: Emit {
+            module,
+            indices,
+            blocks: vec![],
+            block_kinds: vec![BlockKind::FunctionEntry],
+            encoder: &mut encoder,
+            local_indices,
+            map: None,
+        },
+        owners: IdHashMap::default(),
+        seqs: vec![],
+        sizes: vec![],
+    };
+    dfs_in_order(v, func, func.entry_block());
+    std::mem::take(&mut v.sizes)
+}
+
+/// Wraps `Emit` to measure the bytes it emits for each instruction.
+struct Sizes<'a, 'b> {
+    emit: Emit<'a, 'b>,
+
+    // The index in `sizes` of the instruction that each nested sequence
+    // belongs to.
+    owners: IdHashMap<InstrSeq, usize>,
+
+    // The sequences we are in, and how many of their instructions we have
+    // visited so far.
+    seqs: Vec<(InstrSeqId, usize)>,
+
+    sizes: Vec<((InstrSeqId, usize), usize)>,
+}
+
+impl Sizes<'_, '_> {
+    fn attribute(&mut self, seq: InstrSeqId, start: usize) {
+        let len = self.emit.encoder.pos() - start;
+        if let Some(i) = self.owners.get(&seq) {
+            self.sizes[*i].1 += len;
+        }
+    }
+}
+
+impl<'instr> Visitor<'instr> for Sizes<'_, '_> {
+    fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
+        let start = self.emit.encoder.pos();
+        self.emit.start_instr_seq(seq);
+        self.attribute(seq.id(), start);
+        self.seqs.push((seq.id(), 0));
+    }
+
+    fn end_instr_seq(&mut self, seq: &'instr InstrSeq) {
+        let start = self.emit.encoder.pos();
+        self.emit.end_instr_seq(seq);
+        self.attribute(seq.id(), start);
+        self.seqs.pop();
+    }
+
+    fn visit_instr(&mut self, instr: &'instr Instr, instr_loc: &'instr InstrLocId) {
+        let (seq, index) = self.seqs.last_mut().unwrap();
+        let loc = (*seq, *index);
+        *index += 1;
+
+        let start = self.emit.encoder.pos();
+        self.emit.visit_instr(instr, instr_loc);
+        let i = self.sizes.len();
+        self.sizes.push((loc, self.emit.encoder.pos() - start));
+        match instr {
+            Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
+                self.owners.insert(*seq, i);
+            }
+            Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) => {
+                self.owners.insert(*consequent, i);
+                self.owners.insert(*alternative, i);
+            }
+            _ => {}
+        }
+    }
+}
+
 struct Emit<'a, 'b> {
+    // Needed so we can look up the signatures of multi-value blocks.
+    module: &'a Module,
+
     // Needed so we can map locals to their indices.
     indices: &'a IdsToIndices,
     local_indices: &'a IdHashMap<Local, u32>,
@@ -531,6 +633,12 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
                     I32x4TruncSatF32x4U => self.simd(0xf9),
                     F32x4ConvertI32x4S => self.simd(0xfa),
                     F32x4ConvertI32x4U => self.simd(0xfb),
+                    I32x4TruncSatF64x2SZero => self.standard_simd(e.op, 0xfc),
+                    I32x4TruncSatF64x2UZero => self.standard_simd(e.op, 0xfd),
+                    F64x2ConvertLowI32x4S => self.standard_simd(e.op, 0xfe),
+                    F64x2ConvertLowI32x4U => self.standard_simd(e.op, 0xff),
+                    F32x4DemoteF64x2Zero => self.standard_simd(e.op, 0x5e),
+                    F64x2PromoteLowF32x4 => self.standard_simd(e.op, 0x5f),
 
                     I32TruncSSatF32 => self.encoder.raw(&[0xfc, 0x00]),
                     I32TruncUSatF32 => self.encoder.raw(&[0xfc, 0x01]),
@@ -575,14 +683,14 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
             Call(e) => {
                 let idx = self.indices.get_func_index(e.func);
                 self.encoder.byte(0x10); // call
-                self.encoder.u32(idx);
+                self.relocatable_index(idx);
             }
 
             CallIndirect(e) => {
                 let idx = self.indices.get_type_index(e.ty);
                 let table = self.indices.get_table_index(e.table);
                 self.encoder.byte(0x11); // call_indirect
-                self.encoder.u32(idx);
+                self.relocatable_index(idx);
                 self.encoder.u32(table);
             }
 
@@ -607,13 +715,13 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
             GlobalGet(e) => {
                 let idx = self.indices.get_global_index(e.global);
                 self.encoder.byte(0x23); // global.get
-                self.encoder.u32(idx);
+                self.relocatable_index(idx);
             }
 
             GlobalSet(e) => {
                 let idx = self.indices.get_global_index(e.global);
                 self.encoder.byte(0x24); // global.set
-                self.encoder.u32(idx);
+                self.relocatable_index(idx);
             }
 
             Load(e) => {
@@ -809,7 +917,7 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
             RefFunc(e) => {
                 self.encoder.byte(0xd2);
                 let idx = self.indices.get_func_index(e.func);
-                self.encoder.u32(idx);
+                self.relocatable_index(idx);
             }
 
             V128Bitselect(_) => {
@@ -866,6 +974,18 @@ impl Emit<'_, '_> {
         match ty {
             InstrSeqType::Simple(None) => self.encoder.byte(0x40),
             InstrSeqType::Simple(Some(ty)) => ty.emit(self.encoder),
+            // A multi-value type may still have a signature that fits one of
+            // the shorter encodings above, in which case use that instead.
+            InstrSeqType::MultiValue(ty)
+                if self.module.types.params(ty).is_empty()
+                    && self.module.types.results(ty).len() <= 1 =>
+            {
+                match self.module.types.results(ty).first() {
+                    Some(ty) => ty.emit(self.encoder),
+                    None => self.encoder.byte(0x40),
+                }
+            }
+            // Otherwise this is a type index, encoded as a positive `s33`.
             InstrSeqType::MultiValue(ty) => {
                 let index = self.indices.get_type_index(ty);
                 assert!(index < std::i32::MAX as u32);
@@ -875,12 +995,45 @@ impl Emit<'_, '_> {
     }
 
     fn memarg(&mut self, id: MemoryId, arg: &MemArg) {
-        assert_eq!(self.indices.get_memory_index(id), 0);
-        self.encoder.u32(arg.align.trailing_zeros());
+        let idx = self.indices.get_memory_index(id);
+        let align = arg.align.trailing_zeros();
+        if idx == 0 {
+            self.encoder.u32(align);
+        } else {
+            // With the multi-memory proposal, bit 6 of the alignment flags
+            // signals that an explicit memory index follows.
+            self.encoder.u32(align | 0x40);
+            self.encoder.u32(idx);
+        }
         self.encoder.u32(arg.offset);
     }
 
+    /// Encode an index that a linker may need to relocate, padding it to a
+    /// fixed width if the module is relocatable.
+    fn relocatable_index(&mut self, idx: u32) {
+        if self.module.config.relocatable {
+            self.encoder.u32_padded(idx);
+        } else {
+            self.encoder.u32(idx);
+        }
+    }
+
+    /// Encode a SIMD instruction given its legacy opcode, which is
+    /// renumbered if the module is emitted for a different feature level.
     fn simd(&mut self, opcode: u32) {
+        self.encoder.byte(0xfd);
+        self.encoder
+            .u32(self.module.config.feature_level.simd_opcode(opcode));
+    }
+
+    /// Encode a SIMD instruction that only has a standard opcode, panicking if
+    /// the module is emitted for `FeatureLevel::Legacy`.
+    fn standard_simd(&mut self, op: UnaryOp, opcode: u32) {
+        assert!(
+            self.module.config.feature_level == FeatureLevel::Standard,
+            "`{:?}` has no legacy encoding; emit with `FeatureLevel::Standard`",
+            op
+        );
         self.encoder.byte(0xfd);
         self.encoder.u32(opcode);
     }
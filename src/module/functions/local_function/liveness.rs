@@ -0,0 +1,145 @@
+//! Which locals are live at each instruction of a function.
+
+use crate::ir::*;
+use crate::map::IdHashSet;
+use crate::LocalFunction;
+use std::collections::HashMap;
+
+/// The locals that are live before each instruction of a function, as
+/// returned by `LocalFunction::liveness`.
+///
+/// A local is live at a point if some path from that point reads it with
+/// `local.get` before writing to it. Every path through structured control
+/// flow is assumed to be possible, so this may consider a local live when it
+/// never actually is read again, but never the other way around.
+#[derive(Clone, Debug, Default)]
+pub struct Liveness {
+    // For each sequence, the locals live before each of its instructions, and
+    // lastly those live at its end.
+    seqs: SeqMap<Vec<IdHashSet<Local>>>,
+}
+
+impl Liveness {
+    /// Get the locals that are live right before the instruction at `index`
+    /// in `seq` executes, or at the end of `seq` if `index` is its length.
+    ///
+    /// Returns `None` if `seq` isn't reachable from the function's entry, or
+    /// `index` is out of bounds.
+    pub fn live_before(&self, seq: InstrSeqId, index: usize) -> Option<&IdHashSet<Local>> {
+        self.seqs.get(&seq)?.get(index)
+    }
+
+    /// Is `local` live right before the instruction at `index` in `seq`?
+    pub fn is_live(&self, local: LocalId, seq: InstrSeqId, index: usize) -> bool {
+        matches!(self.live_before(seq, index), Some(live) if live.contains(&local))
+    }
+
+    /// The most locals that are live at once anywhere in the function.
+    pub fn max_live(&self) -> usize {
+        self.seqs
+            .values()
+            .flat_map(|live| live.iter())
+            .map(|live| live.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl LocalFunction {
+    /// Compute which locals are live before each instruction in this function,
+    /// for example to estimate register pressure or to find locals whose
+    /// lifetimes don't overlap and so could share a slot.
+    pub fn liveness(&self) -> Liveness {
+        let mut analysis = Analysis {
+            func: self,
+            labels: HashMap::new(),
+            liveness: Liveness::default(),
+        };
+        let entry = self.entry_block();
+        // Branching to the function's entry returns from it.
+        analysis.labels.insert(entry, IdHashSet::default());
+        analysis.seq(entry, IdHashSet::default());
+        analysis.liveness
+    }
+}
+
+struct Analysis<'a> {
+    func: &'a LocalFunction,
+    // The locals that are live when branching to each sequence: those live
+    // after a block or if/else, or at the start of a loop.
+    labels: HashMap<InstrSeqId, IdHashSet<Local>>,
+    liveness: Liveness,
+}
+
+impl Analysis<'_> {
+    /// Compute the locals live at the start of `seq`, given those live at its
+    /// end.
+    fn seq(&mut self, seq: InstrSeqId, live_out: IdHashSet<Local>) -> IdHashSet<Local> {
+        let func = self.func;
+        let instrs = &func.block(seq).instrs;
+        let mut live = vec![IdHashSet::default(); instrs.len() + 1];
+        live[instrs.len()] = live_out;
+        for (i, (instr, _)) in instrs.iter().enumerate().rev() {
+            live[i] = self.instr(instr, live[i + 1].clone());
+        }
+        let live_in = live[0].clone();
+        self.liveness.seqs.insert(seq, live);
+        live_in
+    }
+
+    /// Compute the locals live before `instr`, given those live after it.
+    fn instr(&mut self, instr: &Instr, mut live: IdHashSet<Local>) -> IdHashSet<Local> {
+        match instr {
+            Instr::LocalGet(LocalGet { local }) => {
+                live.insert(*local);
+            }
+            Instr::LocalSet(LocalSet { local }) | Instr::LocalTee(LocalTee { local }) => {
+                live.remove(local);
+            }
+
+            Instr::Block(Block { seq }) => {
+                self.labels.insert(*seq, live.clone());
+                live = self.seq(*seq, live);
+            }
+            Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) => {
+                self.labels.insert(*consequent, live.clone());
+                self.labels.insert(*alternative, live.clone());
+                let mut consequent = self.seq(*consequent, live.clone());
+                consequent.extend(self.seq(*alternative, live));
+                live = consequent;
+            }
+            Instr::Loop(Loop { seq }) => {
+                // Branches back to the loop may make more locals live at its
+                // start, so iterate until that stops changing.
+                self.labels.insert(*seq, IdHashSet::default());
+                loop {
+                    let live_in = self.seq(*seq, live.clone());
+                    if self.labels[seq] == live_in {
+                        live = live_in;
+                        break;
+                    }
+                    self.labels.insert(*seq, live_in);
+                }
+            }
+
+            Instr::Br(Br { block }) => live = self.label(*block),
+            Instr::BrIf(BrIf { block }) => live.extend(self.label(*block)),
+            Instr::BrTable(BrTable { blocks, default }) => {
+                live = self.label(*default);
+                for block in blocks.iter() {
+                    live.extend(self.label(*block));
+                }
+            }
+            Instr::Return(_) | Instr::Unreachable(_) => live.clear(),
+            _ => {}
+        }
+        live
+    }
+
+    fn label(&self, block: InstrSeqId) -> IdHashSet<Local> {
+        self.labels.get(&block).cloned().unwrap_or_default()
+    }
+}
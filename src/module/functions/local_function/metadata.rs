@@ -0,0 +1,71 @@
+//! Per-instruction-sequence metadata that passes can attach to a function.
+
+use crate::ir::SeqMap;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A `SeqMap<T>` whose `T` has been erased, so that maps of different types
+/// can be stored together and still be cloned along with their function.
+trait AnySeqMap: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn AnySeqMap>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Clone + Send + Sync + 'static> AnySeqMap for SeqMap<T> {
+    fn clone_box(&self) -> Box<dyn AnySeqMap> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A store of `SeqMap<T>`s, at most one for each type `T`.
+#[derive(Default)]
+pub(crate) struct Metadata {
+    maps: HashMap<TypeId, Box<dyn AnySeqMap>>,
+}
+
+impl Metadata {
+    pub(crate) fn get<T: 'static>(&self) -> Option<&SeqMap<T>> {
+        self.maps
+            .get(&TypeId::of::<SeqMap<T>>())
+            .map(|m| m.as_any().downcast_ref().unwrap())
+    }
+
+    pub(crate) fn get_mut<T: Clone + Send + Sync + 'static>(&mut self) -> &mut SeqMap<T> {
+        self.maps
+            .entry(TypeId::of::<SeqMap<T>>())
+            .or_insert_with(|| Box::new(SeqMap::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+impl Clone for Metadata {
+    fn clone(&self) -> Metadata {
+        Metadata {
+            maps: self
+                .maps
+                .iter()
+                .map(|(ty, map)| (*ty, map.clone_box()))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Metadata")
+            .field("maps", &self.maps.len())
+            .finish()
+    }
+}
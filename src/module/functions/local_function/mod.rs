@@ -2,26 +2,37 @@
 
 mod context;
 mod emit;
+mod liveness;
+mod metadata;
 
 use self::context::ValidationContext;
+pub use self::liveness::Liveness;
+use self::metadata::Metadata;
 use crate::emit::IdsToIndices;
 use crate::encode::Encoder;
 use crate::ir::*;
 use crate::map::{IdHashMap, IdHashSet};
 use crate::parse::IndicesToIds;
-use crate::{Data, DataId, FunctionBuilder, FunctionId, Module, Result, TypeId, ValType};
+use crate::{
+    Data, DataId, FunctionBuilder, FunctionId, Global, InstrSeqBuilder, Memory, MemoryId, Module,
+    ModuleTypes, Result, Table, TypeId, ValType,
+};
 use anyhow::{bail, Context};
 use std::collections::BTreeMap;
+use std::ops::Range;
 use wasmparser::Operator;
 
 /// A function defined locally within the wasm module.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct LocalFunction {
     /// All of this function's instructions, contained in the arena.
     builder: FunctionBuilder,
 
     /// Arguments to this function, and the locals that they're assigned to.
     pub args: Vec<LocalId>,
+
+    /// Metadata attached to this function's instruction sequences by passes.
+    metadata: Metadata,
     //
     // TODO: provenance: (InstrSeqId, usize) -> offset in code section of the
     // original instruction. This will be necessary for preserving debug info.
@@ -30,7 +41,11 @@ pub struct LocalFunction {
 impl LocalFunction {
     /// Creates a new definition of a local function from its components.
     pub(crate) fn new(args: Vec<LocalId>, builder: FunctionBuilder) -> LocalFunction {
-        LocalFunction { args, builder }
+        LocalFunction {
+            args,
+            builder,
+            metadata: Metadata::default(),
+        }
     }
 
     /// Construct a new `LocalFunction`.
@@ -49,6 +64,7 @@ impl LocalFunction {
         let mut func = LocalFunction {
             builder: FunctionBuilder::without_entry(ty),
             args,
+            metadata: Metadata::default(),
         };
 
         let result: Vec<_> = module.types.get(ty).results().iter().cloned().collect();
@@ -75,7 +91,29 @@ impl LocalFunction {
             validate_instruction(&mut ctx, inst, loc)?;
         }
         if !ctx.controls.is_empty() {
-            bail!("function failed to end with `end`");
+            // Name the unclosed blocks, innermost first, to help diagnose
+            // truncated bodies.
+            let unclosed = ctx
+                .controls
+                .iter()
+                .rev()
+                .filter_map(|frame| match frame.kind {
+                    BlockKind::Block => Some("block"),
+                    BlockKind::Loop => Some("loop"),
+                    BlockKind::If => Some("if"),
+                    BlockKind::Else => Some("else"),
+                    BlockKind::FunctionEntry => None,
+                })
+                .collect::<Vec<_>>();
+            if unclosed.is_empty() {
+                bail!("function failed to end with `end`");
+            }
+            bail!(
+                "function failed to end with `end`: {} control frame(s) left unclosed, \
+                 innermost first: {}",
+                unclosed.len(),
+                unclosed.join(", ")
+            );
         }
 
         debug_assert_eq!(ctx.operands.len(), result_len);
@@ -124,6 +162,33 @@ impl LocalFunction {
         &mut self.builder
     }
 
+    /// Get the metadata of type `T` attached to this function's instruction
+    /// sequences, if any has been attached.
+    ///
+    /// Metadata is never emitted, but is kept when the function is cloned. It
+    /// is not updated by any transformations of the function, so passes that
+    /// use it should not rely on it surviving other passes.
+    pub fn metadata<T: 'static>(&self) -> Option<&SeqMap<T>> {
+        self.metadata.get()
+    }
+
+    /// Get a mutable reference to the metadata of type `T` attached to this
+    /// function's instruction sequences, creating an empty map if there is
+    /// none yet.
+    pub fn metadata_mut<T: Clone + Send + Sync + 'static>(&mut self) -> &mut SeqMap<T> {
+        self.metadata.get_mut()
+    }
+
+    /// Append instructions to the end of this function's entry block, just
+    /// before its implicit return.
+    ///
+    /// The function's results are whatever is on the stack at the end of the
+    /// entry block, so the appended instructions must leave the stack holding
+    /// the function's result types for it to remain valid.
+    pub fn append_to_entry(&mut self, f: impl FnOnce(&mut InstrSeqBuilder)) {
+        f(&mut self.builder.func_body());
+    }
+
     /// Get the size of this function, in number of instructions.
     pub fn size(&self) -> u64 {
         let mut v = SizeVisitor::default();
@@ -142,6 +207,264 @@ impl LocalFunction {
         }
     }
 
+    /// Get the size of this function's body in bytes, as it would be encoded
+    /// in the code section, not counting the size prefix of the body.
+    ///
+    /// The size is computed without emitting the whole module, so it may be
+    /// off by a few bytes when the indices of the items referenced by this
+    /// function take up a different number of bytes once emitted.
+    pub fn encoded_len(&self, module: &Module) -> usize {
+        self.encoded_len_with(module, &IdsToIndices::estimate(module))
+    }
+
+    pub(crate) fn encoded_len_with(&self, module: &Module, indices: &IdsToIndices) -> usize {
+        let mut wasm = Vec::new();
+        let mut encoder = Encoder::new(&mut wasm);
+        let (_, local_indices) = self.emit_locals(module, &mut encoder);
+        self.emit_instructions(module, indices, &local_indices, &mut encoder, None);
+        wasm.len()
+    }
+
+    /// Get the size in bytes of each instruction in this function as it would
+    /// be encoded, along with its sequence and position within it, in the
+    /// same order as `dfs_in_order`.
+    ///
+    /// The size of a `block`, `loop`, or `if`/`else` includes its block type
+    /// and the `else` and `end` that close it, but not its nested
+    /// instructions. As with `encoded_len`, the sizes of instructions that
+    /// reference other items may be off by a few bytes.
+    pub fn instr_sizes(&self, module: &Module) -> Vec<((InstrSeqId, usize), usize)> {
+        let indices = IdsToIndices::estimate(module);
+        let mut locals = Vec::new();
+        let (_, local_indices) = self.emit_locals(module, &mut Encoder::new(&mut locals));
+        emit::instr_sizes(self, module, &indices, &local_indices)
+    }
+
+    /// Fold over every instruction in this function, in the same order as
+    /// `dfs_in_order`.
+    ///
+    /// This is a convenience for simple analyses that would otherwise need a
+    /// one-off `Visitor` implementation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use walrus::ir::Instr;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+    /// builder.func_body().i32_const(1).drop();
+    /// let callee = builder.finish(vec![], &mut module.funcs);
+    ///
+    /// let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+    /// builder
+    ///     .func_body()
+    ///     .call(callee)
+    ///     .block(None, |block| {
+    ///         block.call(callee);
+    ///     });
+    /// let caller = builder.finish(vec![], &mut module.funcs);
+    ///
+    /// let caller = module.funcs.get(caller).kind.unwrap_local();
+    /// let calls = caller.fold(0, |n, instr| match instr {
+    ///     Instr::Call(_) => n + 1,
+    ///     _ => n,
+    /// });
+    /// assert_eq!(calls, 2);
+    /// ```
+    pub fn fold<T>(&self, init: T, f: impl FnMut(T, &Instr) -> T) -> T {
+        let mut folder = Fold { acc: Some(init), f };
+        dfs_in_order(&mut folder, self, self.entry_block());
+        return folder.acc.unwrap();
+
+        struct Fold<T, F> {
+            acc: Option<T>,
+            f: F,
+        }
+
+        impl<'instr, T, F> Visitor<'instr> for Fold<T, F>
+        where
+            F: FnMut(T, &Instr) -> T,
+        {
+            fn visit_instr(&mut self, instr: &'instr Instr, _: &'instr InstrLocId) {
+                let acc = self.acc.take().unwrap();
+                self.acc = Some((self.f)(acc, instr));
+            }
+        }
+    }
+
+    /// Compute the maximum height that the operand stack reaches while
+    /// executing this function.
+    ///
+    /// Both arms of an `if`/`else` are considered and the larger of the two is
+    /// used. Instructions that follow an unconditional branch, `return`, or
+    /// `unreachable` within a sequence are never executed, so they are not
+    /// counted.
+    pub fn max_stack_depth(&self, module: &Module) -> u32 {
+        let mut max = 0;
+
+        // Each sequence's stack usage only depends on the stack height when it
+        // is entered, so we can process them in any order.
+        let mut worklist = vec![(self.entry_block(), 0u32)];
+        while let Some((seq, mut height)) = worklist.pop() {
+            max = max.max(height);
+            for (instr, _) in self.block(seq).instrs.iter() {
+                let seqs = match instr {
+                    Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => vec![*seq],
+                    Instr::IfElse(IfElse {
+                        consequent,
+                        alternative,
+                    }) => {
+                        height = height.saturating_sub(1);
+                        vec![*consequent, *alternative]
+                    }
+                    _ => {
+                        let (pops, pushes) = instr.stack_effect(module);
+                        height = height.saturating_sub(pops) + pushes;
+                        max = max.max(height);
+                        if instr.following_instructions_are_unreachable() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                // A nested sequence starts with its parameters already on the
+                // stack, and replaces them with its results when it is done.
+                let ty = self.block(seqs[0]).ty;
+                for seq in seqs {
+                    worklist.push((seq, height));
+                }
+                let params = ty.params(&module.types).len() as u32;
+                let results = ty.results(&module.types).len() as u32;
+                height = height.saturating_sub(params) + results;
+                max = max.max(height);
+            }
+        }
+
+        max
+    }
+
+    /// Find all instructions in this function that can never execute because
+    /// they follow an unconditional branch, `return`, or `unreachable` within
+    /// their instruction sequence.
+    ///
+    /// Returns each affected sequence along with the range of its unreachable
+    /// instructions. Sequences that are only referenced from dead code are not
+    /// reported separately, since they are removed along with it.
+    pub fn dead_code_ranges(&self) -> Vec<(InstrSeqId, Range<usize>)> {
+        let mut ranges = Vec::new();
+        let mut worklist = vec![self.entry_block()];
+        while let Some(seq) = worklist.pop() {
+            let instrs = &self.block(seq).instrs;
+            let live = instrs
+                .iter()
+                .position(|(instr, _)| instr.following_instructions_are_unreachable())
+                .map_or(instrs.len(), |i| i + 1);
+            if live < instrs.len() {
+                ranges.push((seq, live..instrs.len()));
+            }
+            for (instr, _) in instrs[..live].iter() {
+                match instr {
+                    Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => worklist.push(*seq),
+                    Instr::IfElse(IfElse {
+                        consequent,
+                        alternative,
+                    }) => {
+                        worklist.push(*consequent);
+                        worklist.push(*alternative);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ranges
+    }
+
+    /// Remove all the unreachable instructions reported by
+    /// `dead_code_ranges`.
+    ///
+    /// Returns whether any instructions were removed.
+    pub fn remove_dead_code(&mut self) -> bool {
+        let ranges = self.dead_code_ranges();
+        for (seq, range) in ranges.iter() {
+            self.block_mut(*seq).instrs.truncate(range.start);
+        }
+        !ranges.is_empty()
+    }
+
+    /// Move each `local.get` down to just before the instruction that
+    /// consumes its value, to shorten the value's live range.
+    ///
+    /// This is done conservatively within each instruction sequence: a
+    /// `local.get` is only moved past instructions that together leave the
+    /// stack as they found it without touching the value, and that don't
+    /// write the local. Control flow instructions and calls, whose stack
+    /// effect depends on the types of other functions, are never moved past.
+    pub fn sink_local_gets(&mut self, types: &ModuleTypes) {
+        let seqs = self
+            .builder
+            .arena
+            .iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for seq in seqs {
+            let instrs = &mut self.builder.arena[seq].instrs;
+            let mut i = 0;
+            while i < instrs.len() {
+                if let Some(to) = sink_target(instrs, i, types) {
+                    let get = instrs.remove(i);
+                    instrs.insert(to - 1, get);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    /// Find the self-recursive tail calls in this function, given that its id
+    /// is `self_id`.
+    ///
+    /// A tail call is a `call` of `self_id` that is immediately followed by a
+    /// `return` or a branch to the entry block, or that is the last
+    /// instruction of the entry block. Each one is reported by its sequence
+    /// and its position in it, in the same order as `dfs_in_order`.
+    pub fn self_tail_calls(&self, self_id: FunctionId) -> Vec<(InstrSeqId, usize)> {
+        let mut visitor = TailCalls {
+            func: self_id,
+            entry: self.entry_block(),
+            calls: Vec::new(),
+        };
+        dfs_in_order(&mut visitor, self, self.entry_block());
+        return visitor.calls;
+
+        struct TailCalls {
+            func: FunctionId,
+            entry: InstrSeqId,
+            calls: Vec<(InstrSeqId, usize)>,
+        }
+
+        impl<'a> Visitor<'a> for TailCalls {
+            fn start_instr_seq(&mut self, seq: &'a InstrSeq) {
+                for (i, (instr, _)) in seq.instrs.iter().enumerate() {
+                    match instr {
+                        Instr::Call(Call { func }) if *func == self.func => {}
+                        _ => continue,
+                    }
+                    let is_tail = match seq.instrs.get(i + 1) {
+                        Some((Instr::Return(_), _)) => true,
+                        Some((Instr::Br(Br { block }), _)) => *block == self.entry,
+                        Some(_) => false,
+                        None => seq.id() == self.entry,
+                    };
+                    if is_tail {
+                        self.calls.push((seq.id(), i));
+                    }
+                }
+            }
+        }
+    }
+
     /// Is this function's body a [constant
     /// instruction](https://webassembly.github.io/spec/core/valid/instructions.html#constant-instructions)?
     pub fn is_const(&self) -> bool {
@@ -151,6 +474,18 @@ impl LocalFunction {
             .all(|(e, _)| e.is_const())
     }
 
+    /// Collect the memories, tables, and globals that this function's own
+    /// instructions read or write.
+    ///
+    /// Functions called by this function are not taken into account. Atomic
+    /// read-modify-write instructions, and `memory.copy` and `table.copy`
+    /// within the same memory or table, count as both reads and writes.
+    pub fn resource_use(&self) -> ResourceUse {
+        let mut visitor = ResourceUse::default();
+        dfs_in_order(&mut visitor, self, self.entry_block());
+        visitor
+    }
+
     /// Collect the set of data segments that are used in this function via
     /// `memory.init` or `data.drop` instructions.
     pub fn used_data_segments(&self) -> IdHashSet<Data> {
@@ -170,7 +505,26 @@ impl LocalFunction {
         }
     }
 
-    fn used_locals(&self) -> IdHashSet<Local> {
+    /// Collect the immediate of every `const` instruction in this function,
+    /// in the same order as `dfs_in_order`.
+    pub fn constants(&self) -> Vec<Value> {
+        let mut visitor = Constants::default();
+        dfs_in_order(&mut visitor, self, self.entry_block());
+        return visitor.values;
+
+        #[derive(Default)]
+        struct Constants {
+            values: Vec<Value>,
+        }
+
+        impl<'a> Visitor<'a> for Constants {
+            fn visit_const(&mut self, instr: &Const) {
+                self.values.push(instr.value);
+            }
+        }
+    }
+
+    pub(crate) fn used_locals(&self) -> IdHashSet<Local> {
         let mut locals = Used::default();
         dfs_in_order(&mut locals, self, self.entry_block());
         return locals.locals;
@@ -246,15 +600,66 @@ impl LocalFunction {
     /// Emit this function's instruction sequence.
     pub(crate) fn emit_instructions(
         &self,
+        module: &Module,
         indices: &IdsToIndices,
         local_indices: &IdHashMap<Local, u32>,
         dst: &mut Encoder,
         map: Option<&mut Vec<(InstrLocId, usize)>>,
     ) {
-        emit::run(self, indices, local_indices, dst, map)
+        emit::run(self, module, indices, local_indices, dst, map)
     }
 }
 
+/// If `instrs[i]` is a `local.get` that can be moved further down, the index
+/// of the instruction to move it in front of.
+///
+/// That is the last point before the instruction consuming its value where
+/// the stack is back to the height it had right after the `local.get`, so
+/// that the value keeps its position relative to the other operands.
+fn sink_target(instrs: &[(Instr, InstrLocId)], i: usize, types: &ModuleTypes) -> Option<usize> {
+    let local = match &instrs[i].0 {
+        Instr::LocalGet(LocalGet { local }) => *local,
+        _ => return None,
+    };
+    // The height of the stack above the value pushed by the `local.get`.
+    let mut height = 0;
+    let mut target = i + 1;
+    for (j, (instr, _)) in instrs.iter().enumerate().skip(i + 1) {
+        match instr {
+            Instr::Block(_)
+            | Instr::Loop(_)
+            | Instr::IfElse(_)
+            | Instr::Br(_)
+            | Instr::BrIf(_)
+            | Instr::BrTable(_)
+            | Instr::Return(_)
+            | Instr::Unreachable(_) => return None,
+            Instr::LocalSet(LocalSet { local: l }) | Instr::LocalTee(LocalTee { local: l })
+                if *l == local =>
+            {
+                return None
+            }
+            _ => {}
+        }
+        let (pops, pushes) = instr.stack_effect_with(
+            |_| None,
+            |ty| {
+                let (params, results) = types.params_results(ty);
+                Some((params.len(), results.len()))
+            },
+        )?;
+        if pops > height {
+            // This instruction consumes the value.
+            return if target > i + 1 { Some(target) } else { None };
+        }
+        height = height - pops + pushes;
+        if height == 0 {
+            target = j + 1;
+        }
+    }
+    None
+}
+
 fn block_result_tys(
     ctx: &ValidationContext,
     ty: wasmparser::TypeOrFuncType,
@@ -262,7 +667,7 @@ fn block_result_tys(
     match ty {
         wasmparser::TypeOrFuncType::Type(ty) => ValType::from_wasmparser_type(ty).map(Into::into),
         wasmparser::TypeOrFuncType::FuncType(idx) => {
-            let ty = ctx.indices.get_type(idx)?;
+            let ty = ctx.module.types.get_func_type(ctx.indices, idx)?;
             Ok(ctx.module.types.results(ty).into())
         }
     }
@@ -275,7 +680,7 @@ fn block_param_tys(
     match ty {
         wasmparser::TypeOrFuncType::Type(_) => Ok([][..].into()),
         wasmparser::TypeOrFuncType::FuncType(idx) => {
-            let ty = ctx.indices.get_type(idx)?;
+            let ty = ctx.module.types.get_func_type(ctx.indices, idx)?;
             Ok(ctx.module.types.params(ty).into())
         }
     }
@@ -291,22 +696,22 @@ fn validate_instruction<'context>(
 
     log::trace!("validate instruction: {:?}", inst);
 
-    let const_ = |ctx: &mut ValidationContext, ty, value| {
+    let const_ = |ctx: &mut ValidationContext, ty, value| -> Result<()> {
         ctx.alloc_instr(Const { value }, loc);
-        ctx.push_operand(Some(ty));
+        ctx.push_operand(Some(ty))
     };
 
     let one_op = |ctx: &mut ValidationContext, input, output, op| -> Result<()> {
         ctx.pop_operand_expected(Some(input))?;
         ctx.alloc_instr(Unop { op }, loc);
-        ctx.push_operand(Some(output));
+        ctx.push_operand(Some(output))?;
         Ok(())
     };
     let two_ops = |ctx: &mut ValidationContext, lhs, rhs, output, op| -> Result<()> {
         ctx.pop_operand_expected(Some(rhs))?;
         ctx.pop_operand_expected(Some(lhs))?;
         ctx.alloc_instr(Binop { op }, loc);
-        ctx.push_operand(Some(output));
+        ctx.push_operand(Some(output))?;
         Ok(())
     };
 
@@ -325,12 +730,21 @@ fn validate_instruction<'context>(
         })
     };
 
+    // With multi-memory, the byte that used to be reserved for `memory.size`
+    // and `memory.grow` is the index of the memory they operate on.
+    let reserved_memory = |ctx: &mut ValidationContext, reserved: u32| -> Result<MemoryId> {
+        if reserved != 0 && ctx.module.config.only_stable_features {
+            bail!("reserved byte isn't zero");
+        }
+        ctx.indices.get_memory(reserved)
+    };
+
     let load = |ctx: &mut ValidationContext, arg, ty, kind| -> Result<()> {
         ctx.pop_operand_expected(Some(I32))?;
         let memory = ctx.indices.get_memory(0)?;
         let arg = mem_arg(&arg)?;
         ctx.alloc_instr(Load { arg, kind, memory }, loc);
-        ctx.push_operand(Some(ty));
+        ctx.push_operand(Some(ty))?;
         Ok(())
     };
 
@@ -357,7 +771,7 @@ fn validate_instruction<'context>(
             },
             loc,
         );
-        ctx.push_operand(Some(ty));
+        ctx.push_operand(Some(ty))?;
         Ok(())
     };
 
@@ -368,7 +782,7 @@ fn validate_instruction<'context>(
         let memory = ctx.indices.get_memory(0)?;
         let arg = mem_arg(&arg)?;
         ctx.alloc_instr(Cmpxchg { arg, memory, width }, loc);
-        ctx.push_operand(Some(ty));
+        ctx.push_operand(Some(ty))?;
         Ok(())
     };
 
@@ -377,7 +791,7 @@ fn validate_instruction<'context>(
         let memory = ctx.indices.get_memory(0)?;
         let arg = mem_arg(&arg)?;
         ctx.alloc_instr(LoadSimd { memory, arg, kind }, loc);
-        ctx.push_operand(Some(V128));
+        ctx.push_operand(Some(V128))?;
         Ok(())
     };
     match inst {
@@ -390,12 +804,13 @@ fn validate_instruction<'context>(
             let fun_ty = ctx.module.types.get(ty_id);
             ctx.pop_operands(fun_ty.params())?;
             ctx.alloc_instr(Call { func }, loc);
-            ctx.push_operands(fun_ty.results());
+            ctx.push_operands(fun_ty.results())?;
         }
         Operator::CallIndirect { index, table_index } => {
             let type_id = ctx
-                .indices
-                .get_type(index)
+                .module
+                .types
+                .get_func_type(ctx.indices, index)
                 .context("invalid call_indirect")?;
             let ty = ctx.module.types.get(type_id);
             let table = ctx
@@ -405,13 +820,13 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(I32))?;
             ctx.pop_operands(ty.params())?;
             ctx.alloc_instr(CallIndirect { table, ty: type_id }, loc);
-            ctx.push_operands(ty.results());
+            ctx.push_operands(ty.results())?;
         }
         Operator::LocalGet { local_index } => {
             let local = ctx.indices.get_local(ctx.func_id, local_index)?;
             let ty = ctx.module.locals.get(local).ty();
             ctx.alloc_instr(LocalGet { local }, loc);
-            ctx.push_operand(Some(ty));
+            ctx.push_operand(Some(ty))?;
         }
         Operator::LocalSet { local_index } => {
             let local = ctx.indices.get_local(ctx.func_id, local_index)?;
@@ -424,7 +839,7 @@ fn validate_instruction<'context>(
             let ty = ctx.module.locals.get(local).ty();
             ctx.pop_operand_expected(Some(ty))?;
             ctx.alloc_instr(LocalTee { local }, loc);
-            ctx.push_operand(Some(ty));
+            ctx.push_operand(Some(ty))?;
         }
         Operator::GlobalGet { global_index } => {
             let global = ctx
@@ -433,7 +848,7 @@ fn validate_instruction<'context>(
                 .context("invalid global.get")?;
             let ty = ctx.module.globals.get(global).ty;
             ctx.alloc_instr(GlobalGet { global }, loc);
-            ctx.push_operand(Some(ty));
+            ctx.push_operand(Some(ty))?;
         }
         Operator::GlobalSet { global_index } => {
             let global = ctx
@@ -444,17 +859,17 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(ty))?;
             ctx.alloc_instr(GlobalSet { global }, loc);
         }
-        Operator::I32Const { value } => const_(ctx, I32, Value::I32(value)),
-        Operator::I64Const { value } => const_(ctx, I64, Value::I64(value)),
+        Operator::I32Const { value } => const_(ctx, I32, Value::I32(value))?,
+        Operator::I64Const { value } => const_(ctx, I64, Value::I64(value))?,
         Operator::F32Const { value } => {
-            const_(ctx, F32, Value::F32(f32::from_bits(value.bits())));
+            const_(ctx, F32, Value::F32(f32::from_bits(value.bits())))?;
         }
         Operator::F64Const { value } => {
-            const_(ctx, F64, Value::F64(f64::from_bits(value.bits())));
+            const_(ctx, F64, Value::F64(f64::from_bits(value.bits())))?;
         }
         Operator::V128Const { value } => {
             let val = crate::init_expr::v128_to_u128(&value);
-            const_(ctx, V128, Value::V128(val));
+            const_(ctx, V128, Value::V128(val))?;
         }
         Operator::I32Eqz => testop(ctx, I32, UnaryOp::I32Eqz)?,
         Operator::I32Eq => relop(ctx, I32, BinaryOp::I32Eq)?,
@@ -613,7 +1028,7 @@ fn validate_instruction<'context>(
             }
             let t2 = ctx.pop_operand_expected(t1)?;
             ctx.alloc_instr(Select { ty: None }, loc);
-            ctx.push_operand(t2);
+            ctx.push_operand(t2)?;
         }
         Operator::TypedSelect { ty } => {
             let ty = ValType::parse(&ty)?;
@@ -621,7 +1036,7 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(ty))?;
             ctx.pop_operand_expected(Some(ty))?;
             ctx.alloc_instr(Select { ty: Some(ty) }, loc);
-            ctx.push_operand(Some(ty));
+            ctx.push_operand(Some(ty))?;
         }
         Operator::Return => {
             let fn_ty = ctx.module.funcs.get(ctx.func_id).ty();
@@ -703,7 +1118,7 @@ fn validate_instruction<'context>(
                 _ => {}
             }
 
-            ctx.push_operands(&frame.end_types);
+            ctx.push_operands(&frame.end_types)?;
         }
         Operator::Else => {
             let (frame, _consequent) = ctx.pop_control()?;
@@ -742,7 +1157,7 @@ fn validate_instruction<'context>(
 
             let block = ctx.control(n)?.block;
             ctx.alloc_instr(BrIf { block }, loc);
-            ctx.push_operands(&expected);
+            ctx.push_operands(&expected)?;
         }
 
         Operator::BrTable { table } => {
@@ -788,21 +1203,15 @@ fn validate_instruction<'context>(
         }
 
         Operator::MemorySize { reserved } => {
-            if reserved != 0 {
-                bail!("reserved byte isn't zero");
-            }
-            let memory = ctx.indices.get_memory(0)?;
+            let memory = reserved_memory(ctx, reserved)?;
             ctx.alloc_instr(MemorySize { memory }, loc);
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::MemoryGrow { reserved } => {
-            if reserved != 0 {
-                bail!("reserved byte isn't zero");
-            }
             ctx.pop_operand_expected(Some(I32))?;
-            let memory = ctx.indices.get_memory(0)?;
+            let memory = reserved_memory(ctx, reserved)?;
             ctx.alloc_instr(MemoryGrow { memory }, loc);
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::MemoryInit { segment } => {
             ctx.pop_operand_expected(Some(I32))?;
@@ -1136,7 +1545,7 @@ fn validate_instruction<'context>(
                 },
                 loc,
             );
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::I32AtomicWait { ref memarg } | Operator::I64AtomicWait { ref memarg } => {
             let (ty, sixty_four) = match inst {
@@ -1155,7 +1564,7 @@ fn validate_instruction<'context>(
                 },
                 loc,
             );
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
 
         Operator::TableGet { table } => {
@@ -1163,7 +1572,7 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(I32))?;
             ctx.alloc_instr(TableGet { table }, loc);
             let result = ctx.module.tables.get(table).element_ty;
-            ctx.push_operand(Some(result));
+            ctx.push_operand(Some(result))?;
         }
         Operator::TableSet { table } => {
             let table = ctx.indices.get_table(table)?;
@@ -1178,12 +1587,12 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(I32))?;
             ctx.pop_operand_expected(Some(expected_ty))?;
             ctx.alloc_instr(TableGrow { table }, loc);
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::TableSize { table } => {
             let table = ctx.indices.get_table(table)?;
             ctx.alloc_instr(TableSize { table }, loc);
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::TableFill { table } => {
             let table = ctx.indices.get_table(table)?;
@@ -1196,13 +1605,13 @@ fn validate_instruction<'context>(
         Operator::RefNull { ty } => {
             let ty = ValType::parse(&ty)?;
             ctx.alloc_instr(RefNull { ty }, loc);
-            ctx.push_operand(Some(ty));
+            ctx.push_operand(Some(ty))?;
         }
         Operator::RefIsNull { ty } => {
             let ty = ValType::parse(&ty)?;
             ctx.pop_operand_expected(Some(ty))?;
             ctx.alloc_instr(RefIsNull { ty }, loc);
-            ctx.push_operand(Some(I32));
+            ctx.push_operand(Some(I32))?;
         }
         Operator::RefFunc { function_index } => {
             let func = ctx
@@ -1210,21 +1619,21 @@ fn validate_instruction<'context>(
                 .get_func(function_index)
                 .context("invalid call")?;
             ctx.alloc_instr(RefFunc { func }, loc);
-            ctx.push_operand(Some(Funcref));
+            ctx.push_operand(Some(Funcref))?;
         }
 
         Operator::V8x16Swizzle => {
             ctx.pop_operand_expected(Some(V128))?;
             ctx.pop_operand_expected(Some(V128))?;
             ctx.alloc_instr(V128Swizzle {}, loc);
-            ctx.push_operand(Some(V128));
+            ctx.push_operand(Some(V128))?;
         }
 
         Operator::V8x16Shuffle { lanes } => {
             ctx.pop_operand_expected(Some(V128))?;
             ctx.pop_operand_expected(Some(V128))?;
             ctx.alloc_instr(V128Shuffle { indices: lanes }, loc);
-            ctx.push_operand(Some(V128));
+            ctx.push_operand(Some(V128))?;
         }
 
         Operator::I8x16Splat => one_op(ctx, I32, V128, UnaryOp::I8x16Splat)?,
@@ -1330,7 +1739,7 @@ fn validate_instruction<'context>(
             ctx.pop_operand_expected(Some(V128))?;
             ctx.pop_operand_expected(Some(V128))?;
             ctx.alloc_instr(V128Bitselect {}, loc);
-            ctx.push_operand(Some(V128));
+            ctx.push_operand(Some(V128))?;
         }
 
         Operator::I8x16Abs => unop(ctx, V128, UnaryOp::I8x16Abs)?,
@@ -1470,8 +1879,8 @@ fn validate_instruction<'context>(
             let dst = ctx.indices.get_table(dst_table)?;
             let src_ty = ctx.module.tables.get(src).element_ty;
             let dst_ty = ctx.module.tables.get(dst).element_ty;
-            if src_ty != dst_ty {
-                bail!("type mismatch: cannot copy between tables of different types");
+            if !src_ty.is_subtype_of(dst_ty) {
+                bail!("type mismatch: cannot copy between tables of incompatible types");
             }
             ctx.pop_operand_expected(Some(I32))?;
             ctx.pop_operand_expected(Some(I32))?;
@@ -1484,8 +1893,8 @@ fn validate_instruction<'context>(
             let table = ctx.indices.get_table(table)?;
             let elem_ty = ctx.module.elements.get(elem).ty;
             let table_ty = ctx.module.tables.get(table).element_ty;
-            if elem_ty != table_ty {
-                bail!("type mismatch: cannot initialize table of different type");
+            if !elem_ty.is_subtype_of(table_ty) {
+                bail!("type mismatch: cannot initialize table of incompatible type");
             }
             ctx.pop_operand_expected(Some(I32))?;
             ctx.pop_operand_expected(Some(I32))?;
@@ -1500,3 +1909,85 @@ fn validate_instruction<'context>(
     }
     Ok(())
 }
+
+/// The memories, tables, and globals that a function reads or writes, as
+/// returned by `LocalFunction::resource_use`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUse {
+    /// The memories that are read from.
+    pub memories_read: IdHashSet<Memory>,
+    /// The memories that are written to or grown.
+    pub memories_written: IdHashSet<Memory>,
+    /// The tables that are read from, including by `call_indirect`.
+    pub tables_read: IdHashSet<Table>,
+    /// The tables that are written to or grown.
+    pub tables_written: IdHashSet<Table>,
+    /// The globals that are read.
+    pub globals_read: IdHashSet<Global>,
+    /// The globals that are written.
+    pub globals_written: IdHashSet<Global>,
+}
+
+impl ResourceUse {
+    /// Does this not use any memories, tables, or globals at all?
+    pub fn is_empty(&self) -> bool {
+        self.memories_read.is_empty()
+            && self.memories_written.is_empty()
+            && self.tables_read.is_empty()
+            && self.tables_written.is_empty()
+            && self.globals_read.is_empty()
+            && self.globals_written.is_empty()
+    }
+}
+
+impl<'a> Visitor<'a> for ResourceUse {
+    fn visit_instr(&mut self, instr: &'a Instr, _: &'a InstrLocId) {
+        match instr {
+            Instr::MemorySize(MemorySize { memory })
+            | Instr::Load(Load { memory, .. })
+            | Instr::LoadSimd(LoadSimd { memory, .. })
+            | Instr::AtomicWait(AtomicWait { memory, .. }) => {
+                self.memories_read.insert(*memory);
+            }
+            Instr::MemoryGrow(MemoryGrow { memory })
+            | Instr::MemoryInit(MemoryInit { memory, .. })
+            | Instr::MemoryFill(MemoryFill { memory })
+            | Instr::Store(Store { memory, .. })
+            | Instr::AtomicNotify(AtomicNotify { memory, .. }) => {
+                self.memories_written.insert(*memory);
+            }
+            Instr::AtomicRmw(AtomicRmw { memory, .. }) | Instr::Cmpxchg(Cmpxchg { memory, .. }) => {
+                self.memories_read.insert(*memory);
+                self.memories_written.insert(*memory);
+            }
+            Instr::MemoryCopy(MemoryCopy { src, dst }) => {
+                self.memories_read.insert(*src);
+                self.memories_written.insert(*dst);
+            }
+
+            Instr::TableGet(TableGet { table })
+            | Instr::TableSize(TableSize { table })
+            | Instr::CallIndirect(CallIndirect { table, .. }) => {
+                self.tables_read.insert(*table);
+            }
+            Instr::TableSet(TableSet { table })
+            | Instr::TableGrow(TableGrow { table })
+            | Instr::TableFill(TableFill { table })
+            | Instr::TableInit(TableInit { table, .. }) => {
+                self.tables_written.insert(*table);
+            }
+            Instr::TableCopy(TableCopy { src, dst }) => {
+                self.tables_read.insert(*src);
+                self.tables_written.insert(*dst);
+            }
+
+            Instr::GlobalGet(GlobalGet { global }) => {
+                self.globals_read.insert(*global);
+            }
+            Instr::GlobalSet(GlobalSet { global }) => {
+                self.globals_written.insert(*global);
+            }
+            _ => {}
+        }
+    }
+}
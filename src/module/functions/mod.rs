@@ -5,20 +5,34 @@ mod local_function;
 use crate::emit::{Emit, EmitContext, Section};
 use crate::encode::Encoder;
 use crate::error::Result;
-use crate::ir::InstrLocId;
+use crate::function_builder::FunctionBuilder;
+use crate::ir::{
+    dfs_in_order, CallIndirect, FunctionVisitor, Instr, InstrLocId, InstrSeqId, ModuleVisitor,
+    Visitor,
+};
+use crate::map::{IdHashMap, IdHashSet};
+use crate::module::exports::{ExportId, ExportItem};
+use crate::module::hints::FunctionHint;
 use crate::module::imports::ImportId;
 use crate::module::Module;
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::ty::TypeId;
 use crate::ty::ValType;
+use crate::ty::{Type, TypeId};
+use crate::{ElementId, TableId};
 use anyhow::bail;
 use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
-pub use self::local_function::LocalFunction;
+pub use self::local_function::{Liveness, LocalFunction, ResourceUse};
 
 /// A function identifier.
 pub type FunctionId = Id<Function>;
@@ -26,7 +40,7 @@ pub type FunctionId = Id<Function>;
 /// A wasm function.
 ///
 /// Either defined locally or externally and then imported; see `FunctionKind`.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Function {
     // NB: Not public so that it can't get out of sync with the arena that this
     // function lives within.
@@ -72,7 +86,7 @@ impl Function {
 }
 
 /// The local- or external-specific bits of a function.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FunctionKind {
     /// An externally defined, imported wasm function.
     Import(ImportedFunction),
@@ -126,7 +140,7 @@ impl FunctionKind {
 }
 
 /// An externally defined, imported function.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ImportedFunction {
     /// The import that brings this function into the module.
     pub import: ImportId,
@@ -139,6 +153,33 @@ pub struct ImportedFunction {
 pub struct ModuleFunctions {
     /// The arena containing this module's functions.
     arena: TombstoneArena<Function>,
+
+    /// A user-specified order to emit local functions in, if any. See
+    /// `Module::set_function_order`.
+    order: Option<Vec<FunctionId>>,
+
+    /// Layout hints for functions, emitted in the `walrus.hints` custom
+    /// section. See `Module::set_function_hint`.
+    pub(crate) hints: IdHashMap<Function, FunctionHint>,
+
+    /// Lazily built index from names to the first function with that name.
+    ///
+    /// Function names can only change through a `&mut Function`, so this is
+    /// cleared by every method that hands one out, and rebuilt on the next
+    /// lookup.
+    names: Mutex<Option<HashMap<String, FunctionId>>>,
+}
+
+impl Clone for ModuleFunctions {
+    fn clone(&self) -> ModuleFunctions {
+        ModuleFunctions {
+            arena: self.arena.clone(),
+            order: self.order.clone(),
+            hints: self.hints.clone(),
+            // The name index is rebuilt lazily on the next lookup.
+            names: Mutex::new(None),
+        }
+    }
 }
 
 impl ModuleFunctions {
@@ -149,6 +190,7 @@ impl ModuleFunctions {
 
     /// Create a new externally defined, imported function.
     pub fn add_import(&mut self, ty: TypeId, import: ImportId) -> FunctionId {
+        self.invalidate_names();
         self.arena.alloc_with_id(|id| Function {
             id,
             kind: FunctionKind::Import(ImportedFunction { import, ty }),
@@ -159,6 +201,7 @@ impl ModuleFunctions {
     /// Create a new internally defined function
     pub fn add_local(&mut self, func: LocalFunction) -> FunctionId {
         let func_name = func.builder().name.clone();
+        self.invalidate_names();
         self.arena.alloc_with_id(|id| Function {
             id,
             kind: FunctionKind::Local(func),
@@ -173,6 +216,7 @@ impl ModuleFunctions {
 
     /// Gets a reference to a function given its id
     pub fn get_mut(&mut self, id: FunctionId) -> &mut Function {
+        self.invalidate_names();
         &mut self.arena[id]
     }
 
@@ -183,14 +227,25 @@ impl ModuleFunctions {
     ///
     /// Note that function names are *not* guaranteed to be unique. This will
     /// return the first function in the module with the given name.
+    ///
+    /// The first lookup builds an index of all function names, which is reused
+    /// until a function is added, removed, or mutably borrowed.
     pub fn by_name(&self, name: &str) -> Option<FunctionId> {
-        self.arena.iter().find_map(|(id, f)| {
-            if f.name.as_ref().map(|s| s.as_str()) == Some(name) {
-                Some(id)
-            } else {
-                None
+        let mut names = self.names.lock().unwrap();
+        let names = names.get_or_insert_with(|| {
+            let mut names = HashMap::new();
+            for (id, f) in self.arena.iter() {
+                if let Some(name) = &f.name {
+                    names.entry(name.clone()).or_insert(id);
+                }
             }
-        })
+            names
+        });
+        names.get(name).cloned()
+    }
+
+    fn invalidate_names(&mut self) {
+        *self.names.get_mut().unwrap() = None;
     }
 
     /// Removes a function from this module.
@@ -199,6 +254,8 @@ impl ModuleFunctions {
     /// function are also removed, eg `call` expressions, exports, table
     /// elements, etc.
     pub fn delete(&mut self, id: FunctionId) {
+        self.invalidate_names();
+        self.hints.remove(&id);
         self.arena.delete(id);
     }
 
@@ -236,6 +293,7 @@ impl ModuleFunctions {
 
     /// Get a mutable reference to this module's functions.
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Function> {
+        self.invalidate_names();
         self.arena.iter_mut().map(|(_, f)| f)
     }
 
@@ -244,6 +302,7 @@ impl ModuleFunctions {
     /// Requires the `parallel` feature of this crate to be enabled.
     #[cfg(feature = "parallel")]
     pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = &mut Function> {
+        self.invalidate_names();
         self.arena.par_iter_mut().map(|(_, f)| f)
     }
 
@@ -297,6 +356,375 @@ impl ModuleFunctions {
 }
 
 impl Module {
+    /// Get a function ID by its name in the "name" custom section.
+    ///
+    /// This is the same as `ModuleFunctions::by_name`: names are not
+    /// guaranteed to be unique, and the first function with the given name is
+    /// returned.
+    pub fn function_by_name(&self, name: &str) -> Option<FunctionId> {
+        self.funcs.by_name(name)
+    }
+
+    /// Get the parameter types of the given function.
+    pub fn func_params(&self, id: FunctionId) -> &[ValType] {
+        self.types.get(self.funcs.get(id).ty()).params()
+    }
+
+    /// Get the result types of the given function.
+    pub fn func_results(&self, id: FunctionId) -> &[ValType] {
+        self.types.get(self.funcs.get(id).ty()).results()
+    }
+
+    /// Get the number of parameters and results of the given function.
+    pub fn func_arity(&self, id: FunctionId) -> (usize, usize) {
+        let ty = self.types.get(self.funcs.get(id).ty());
+        (ty.params().len(), ty.results().len())
+    }
+
+    /// Add a local function of type `ty` whose body is just `unreachable`.
+    ///
+    /// This is handy as a placeholder for a function whose real body isn't
+    /// available, since it is valid for any signature.
+    pub fn add_stub_function(&mut self, ty: TypeId) -> FunctionId {
+        let func = self.stub_body(ty);
+        self.funcs.add_local(func)
+    }
+
+    /// Give every function whose body was never parsed, because parsing
+    /// stopped early, a body that is just `unreachable`.
+    pub(crate) fn stub_uninitialized_functions(&mut self) {
+        let uninitialized = self
+            .funcs
+            .iter()
+            .filter_map(|f| match f.kind {
+                FunctionKind::Uninitialized(ty) => Some((f.id(), ty)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (id, ty) in uninitialized {
+            let func = self.stub_body(ty);
+            self.funcs.get_mut(id).kind = FunctionKind::Local(func);
+        }
+    }
+
+    fn stub_body(&mut self, ty: TypeId) -> LocalFunction {
+        let (params, results) = self.types.params_results(ty);
+        let (params, results) = (params.to_vec(), results.to_vec());
+        let args = params.iter().map(|ty| self.locals.add(*ty)).collect();
+        let mut builder = FunctionBuilder::new(&mut self.types, &params, &results);
+        builder.func_body().unreachable();
+        LocalFunction::new(args, builder)
+    }
+
+    /// Replace the body of the local function `id` with `new`.
+    ///
+    /// The function keeps its id and name, so calls and references to it, as
+    /// well as its exports and element segment entries, now use the new body.
+    /// The locals used by `new` must belong to this module.
+    ///
+    /// Returns an error if `id` isn't a local function, or if `new`'s type or
+    /// arguments don't match the function's type.
+    pub fn replace_function_body(&mut self, id: FunctionId, new: LocalFunction) -> Result<()> {
+        let func = self.funcs.get(id);
+        if !matches!(func.kind, FunctionKind::Local(_)) {
+            bail!("can only replace the body of a local function");
+        }
+
+        let (params, results) = self.types.params_results(func.ty());
+        let (new_params, new_results) = self.types.params_results(new.ty());
+        if params != new_params || results != new_results {
+            bail!(
+                "the new body's type {:?} -> {:?} doesn't match the function's type {:?} -> {:?}",
+                new_params,
+                new_results,
+                params,
+                results
+            );
+        }
+        let args = new
+            .args
+            .iter()
+            .map(|arg| self.locals.get(*arg).ty())
+            .collect::<Vec<_>>();
+        if args != params {
+            bail!(
+                "the new body's arguments {:?} don't match the function's parameters {:?}",
+                args,
+                params
+            );
+        }
+
+        self.funcs.get_mut(id).kind = FunctionKind::Local(new);
+        Ok(())
+    }
+
+    /// Get the ID of the function exported under the given name, if any.
+    pub fn function_by_export(&self, name: &str) -> Option<FunctionId> {
+        self.exports.iter().find_map(|e| match e.item {
+            ExportItem::Function(f) if e.name == name => Some(f),
+            _ => None,
+        })
+    }
+
+    /// Visit every instruction in every local function of this module.
+    ///
+    /// Each local function is traversed with `dfs_in_order` from its entry
+    /// block, and `visitor` is told which function each instruction belongs
+    /// to.
+    pub fn visit_all_instrs<'a>(&'a self, visitor: &mut impl ModuleVisitor<'a>) {
+        for (id, func) in self.funcs.iter_local() {
+            visitor.start_function(id, func);
+            let mut visitor = FunctionVisitor { func: id, visitor };
+            dfs_in_order(&mut visitor, func, func.entry_block());
+        }
+    }
+
+    /// Count how many times each kind of instruction occurs across all local
+    /// functions, keyed by `Instr::name`.
+    pub fn opcode_histogram(&self) -> BTreeMap<&'static str, u64> {
+        struct Histogram(BTreeMap<&'static str, u64>);
+
+        impl<'instr> Visitor<'instr> for Histogram {
+            fn visit_instr(&mut self, instr: &'instr Instr, _: &'instr InstrLocId) {
+                *self.0.entry(instr.name()).or_insert(0) += 1;
+            }
+        }
+
+        let mut histogram = Histogram(BTreeMap::new());
+        for (_id, func) in self.funcs.iter_local() {
+            dfs_in_order(&mut histogram, func, func.entry_block());
+        }
+        histogram.0
+    }
+
+    /// Collect the memories, tables, and globals that each local function's
+    /// own instructions read or write.
+    ///
+    /// See `LocalFunction::resource_use` for details.
+    pub fn function_resource_usage(&self) -> IdHashMap<Function, ResourceUse> {
+        self.funcs
+            .iter_local()
+            .map(|(id, func)| (id, func.resource_use()))
+            .collect()
+    }
+
+    /// Is the given function a pure leaf function, which can be freely
+    /// duplicated, reordered, or removed when its results are unused?
+    ///
+    /// A pure leaf function is a local function that makes no calls, never
+    /// writes to memories, tables, or globals, and can neither trap nor loop
+    /// forever. That is, besides its own locals, blocks, ifs, and forward
+    /// branches, it only uses instructions for which
+    /// `Instr::has_side_effects` is `false`. Imported functions are never pure
+    /// leaf functions.
+    pub fn is_pure_leaf(&self, id: FunctionId) -> bool {
+        struct PureLeaf(bool);
+
+        impl<'instr> Visitor<'instr> for PureLeaf {
+            fn visit_instr(&mut self, instr: &'instr Instr, _: &'instr InstrLocId) {
+                self.0 &= instr.is_pure_without_loops();
+            }
+        }
+
+        let func = match &self.funcs.get(id).kind {
+            FunctionKind::Local(func) => func,
+            _ => return false,
+        };
+        let mut visitor = PureLeaf(true);
+        dfs_in_order(&mut visitor, func, func.entry_block());
+        visitor.0
+    }
+
+    /// Collect the types that a function's body needs: its own type, the
+    /// types of its `call_indirect`s, and the multi-value types of its blocks,
+    /// loops, and ifs.
+    ///
+    /// This is every type that has to be copied along with the function when
+    /// moving it into another module. For an imported function this is just
+    /// its own type.
+    pub fn function_type_closure(&self, id: FunctionId) -> IdHashSet<Type> {
+        #[derive(Default)]
+        struct Types {
+            types: IdHashSet<Type>,
+        }
+
+        impl<'instr> Visitor<'instr> for Types {
+            fn visit_type_id(&mut self, ty: &TypeId) {
+                self.types.insert(*ty);
+            }
+        }
+
+        let func = self.funcs.get(id);
+        let mut types = Types::default();
+        types.types.insert(func.ty());
+        if let FunctionKind::Local(func) = &func.kind {
+            dfs_in_order(&mut types, func, func.entry_block());
+        }
+
+        // The entry block's type is an internal placeholder for the function's
+        // results, not a type of its own.
+        let mut types = types.types;
+        types.retain(|ty| !self.types.get(*ty).is_for_function_entry());
+        types
+    }
+
+    /// Find every `call_indirect` of the type `ty`, as the local function,
+    /// instruction sequence, and index within that sequence of each.
+    pub fn find_indirect_calls(&self, ty: TypeId) -> Vec<(FunctionId, InstrSeqId, usize)> {
+        let mut calls = Vec::new();
+        for (id, func) in self.funcs.iter_local() {
+            for (seq_id, seq) in func.builder().arena.iter() {
+                for (i, (instr, _)) in seq.instrs.iter().enumerate() {
+                    match instr {
+                        Instr::CallIndirect(call) if call.ty == ty => calls.push((id, seq_id, i)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    /// Change every `call_indirect` of the type `from` to use the type `to`
+    /// instead, returning how many were changed.
+    ///
+    /// Returns an error, without changing anything, if `to` has a different
+    /// number of parameters or results than `from`, since the operand stack
+    /// around each call would no longer balance. The types of the parameters
+    /// and results aren't checked: when they differ, the code around each
+    /// call, which can be found with `find_indirect_calls` beforehand, has to
+    /// be updated to match.
+    pub fn rewrite_indirect_calls(&mut self, from: TypeId, to: TypeId) -> Result<usize> {
+        let (params, results) = self.types.params_results(from);
+        let (new_params, new_results) = self.types.params_results(to);
+        if params.len() != new_params.len() || results.len() != new_results.len() {
+            bail!(
+                "can't rewrite indirect calls of type {:?} -> {:?} to {:?} -> {:?}, \
+                 since that changes their stack effect",
+                params,
+                results,
+                new_params,
+                new_results
+            );
+        }
+
+        let calls = self.find_indirect_calls(from);
+        for (func, seq, i) in calls.iter() {
+            let func = self.funcs.get_mut(*func).kind.unwrap_local_mut();
+            if let Instr::CallIndirect(call) = &mut func.block_mut(*seq).instrs[*i].0 {
+                call.ty = to;
+            }
+        }
+        Ok(calls.len())
+    }
+
+    /// Find the functions reachable from the given exports.
+    ///
+    /// This is the transitive closure over functions that are called or
+    /// referenced with `ref.func`, starting from exported functions and the
+    /// functions in the element segments of exported tables. When a reachable
+    /// function contains a `call_indirect`, the functions in the element
+    /// segments of its table are reachable too, as are the members of element
+    /// segments used by `table.init`.
+    ///
+    /// Unlike the `gc` pass this doesn't remove anything, and only the given
+    /// exports are roots: the start function isn't reachable unless it is
+    /// reachable from one of them.
+    pub fn reachable_functions(&self, roots: &[ExportId]) -> IdHashSet<Function> {
+        #[derive(Default)]
+        struct Reachable {
+            funcs: Vec<FunctionId>,
+            tables: Vec<TableId>,
+            elements: Vec<ElementId>,
+        }
+
+        impl<'instr> Visitor<'instr> for Reachable {
+            fn visit_function_id(&mut self, func: &FunctionId) {
+                self.funcs.push(*func);
+            }
+
+            fn visit_call_indirect(&mut self, call: &CallIndirect) {
+                self.tables.push(call.table);
+            }
+
+            fn visit_element_id(&mut self, element: &ElementId) {
+                self.elements.push(*element);
+            }
+        }
+
+        let mut reachable = Reachable::default();
+        for root in roots {
+            match self.exports.get(*root).item {
+                ExportItem::Function(f) => reachable.funcs.push(f),
+                ExportItem::Table(t) => reachable.tables.push(t),
+                ExportItem::Memory(_) | ExportItem::Global(_) => {}
+            }
+        }
+
+        let mut funcs = IdHashSet::default();
+        let mut elements = IdHashSet::default();
+        loop {
+            if let Some(f) = reachable.funcs.pop() {
+                if !funcs.insert(f) {
+                    continue;
+                }
+                if let FunctionKind::Local(func) = &self.funcs.get(f).kind {
+                    dfs_in_order(&mut reachable, func, func.entry_block());
+                }
+            } else if let Some(t) = reachable.tables.pop() {
+                let segments = &self.tables.get(t).elem_segments;
+                reachable.elements.extend(segments.iter().cloned());
+            } else if let Some(e) = reachable.elements.pop() {
+                if elements.insert(e) {
+                    let members = &self.elements.get(e).members;
+                    reachable.funcs.extend(members.iter().filter_map(|f| *f));
+                }
+            } else {
+                break;
+            }
+        }
+        funcs
+    }
+
+    /// Set the order in which local functions are laid out in the code
+    /// section when this module is emitted.
+    ///
+    /// By default local functions are emitted from largest to smallest. The
+    /// given `order` must be a permutation of all local functions currently in
+    /// this module, otherwise an error is returned and the existing order is
+    /// left unchanged.
+    ///
+    /// Local functions that are added after the order is set are emitted after
+    /// all ordered functions, in the default order.
+    pub fn set_function_order(&mut self, order: Vec<FunctionId>) -> Result<()> {
+        let locals = self
+            .funcs
+            .iter_local()
+            .map(|(id, _)| id)
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        for id in order.iter() {
+            if !locals.contains(id) {
+                bail!(
+                    "function {:?} in function order is not a local function",
+                    id
+                );
+            }
+            if !seen.insert(*id) {
+                bail!("function {:?} appears more than once in function order", id);
+            }
+        }
+        if seen.len() != locals.len() {
+            bail!(
+                "function order has {} functions but the module has {} local functions",
+                seen.len(),
+                locals.len()
+            );
+        }
+        self.funcs.order = Some(order);
+        Ok(())
+    }
+
     /// Declare local functions after seeing the `function` section of a wasm
     /// executable.
     pub(crate) fn declare_local_functions(
@@ -306,7 +734,7 @@ impl Module {
     ) -> Result<()> {
         log::debug!("parse function section");
         for func in section {
-            let ty = ids.get_type(func?)?;
+            let ty = self.types.get_func_type(ids, func?)?;
             let id = self
                 .funcs
                 .arena
@@ -327,6 +755,7 @@ impl Module {
         function_section_count: u32,
         indices: &mut IndicesToIds,
         on_instr_pos: Option<&(dyn Fn(&usize) -> InstrLocId + Sync + Send + 'static)>,
+        mut function_errors: Option<&mut Vec<(FunctionId, anyhow::Error)>>,
     ) -> Result<()> {
         log::debug!("parse code section");
         let amt = section.get_count();
@@ -380,6 +809,16 @@ impl Module {
                     None => bail!("can't have more than 2^32 locals"),
                 };
             }
+            if let Some(max) = self.config.max_locals_per_function {
+                if total > max {
+                    bail!(
+                        "function {} declares {} locals, but at most {} are allowed",
+                        index,
+                        total,
+                        max
+                    );
+                }
+            }
 
             // Now that we know we have a reasonable amount of locals, put them in
             // our map.
@@ -412,9 +851,18 @@ impl Module {
             .collect::<Vec<_>>();
 
         // After all the function bodies are collected and finished push them
-        // into our function arena.
+        // into our function arena. If we're collecting errors, functions that
+        // failed to parse get a stub body instead.
         for (id, func) in results {
-            let func = func?;
+            let func = match (func, function_errors.as_deref_mut()) {
+                (Ok(func), _) => func,
+                (Err(e), Some(errors)) => {
+                    errors.push((id, e));
+                    let ty = self.funcs.get(id).ty();
+                    self.stub_body(ty)
+                }
+                (Err(e), None) => return Err(e),
+            };
             self.funcs.arena[id].kind = FunctionKind::Local(func);
         }
 
@@ -443,6 +891,18 @@ fn used_local_functions<'a>(cx: &mut EmitContext<'a>) -> Vec<(FunctionId, &'a Lo
     // longer to compile.
     functions.sort_by_key(|(id, _, size)| (cmp::Reverse(*size), *id));
 
+    // If the user asked for a specific order, that takes precedence. Any
+    // functions not mentioned in it (because they were added afterwards) keep
+    // their relative order from above and go at the end.
+    if let Some(order) = &cx.module.funcs.order {
+        let positions = order
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+        functions.sort_by_key(|(id, _, _)| positions.get(id).cloned().unwrap_or(usize::MAX));
+    }
+
     functions
 }
 
@@ -468,9 +928,11 @@ impl Emit for ModuleFunctions {
         }
 
         let mut cx = cx.start_section(Section::Code);
+        let start = cx.encoder.pos();
         cx.encoder.usize(functions.len());
 
-        let generate_map = cx.module.config.preserve_code_transform;
+        let generate_map = cx.generate_code_transform;
+        let strip_unreachable = cx.module.config.strip_unreachable;
 
         // Functions can typically take awhile to serialize, so serialize
         // everything in parallel. Afterwards we'll actually place all the
@@ -482,8 +944,26 @@ impl Emit for ModuleFunctions {
                 let mut encoder = Encoder::new(&mut wasm);
                 let mut map = if generate_map { Some(Vec::new()) } else { None };
 
+                // Strip unreachable code from a copy of the function, so that
+                // the module itself is left untouched.
+                let stripped;
+                let func = if strip_unreachable && !func.dead_code_ranges().is_empty() {
+                    let mut copy = func.clone();
+                    copy.remove_dead_code();
+                    stripped = copy;
+                    &stripped
+                } else {
+                    func
+                };
+
                 let (used_locals, local_indices) = func.emit_locals(cx.module, &mut encoder);
-                func.emit_instructions(cx.indices, &local_indices, &mut encoder, map.as_mut());
+                func.emit_instructions(
+                    cx.module,
+                    cx.indices,
+                    &local_indices,
+                    &mut encoder,
+                    map.as_mut(),
+                );
                 (wasm, id, used_locals, local_indices, map)
             })
             .collect::<Vec<_>>();
@@ -493,11 +973,23 @@ impl Emit for ModuleFunctions {
             cx.encoder.usize(wasm.len());
             let code_offset = cx.encoder.pos();
             cx.encoder.raw(&wasm);
+            let code_end = cx.encoder.pos();
+            cx.layout.functions.push((id, code_offset..code_end));
             if let Some(map) = map {
                 collect_non_default_code_offsets(&mut cx.code_transform, code_offset, map);
             }
             cx.indices.locals.insert(id, local_indices);
             cx.locals.insert(id, used_locals);
         }
+
+        cx.code_section_hash = Some(code_section_hash(cx.encoder.bytes_since(start)));
     }
 }
+
+/// Hash the payload of a code section, so that we can tell whether the code
+/// section we emit is identical to the one we parsed.
+pub(crate) fn code_section_hash(payload: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    payload.hash(&mut hasher);
+    hasher.finish()
+}
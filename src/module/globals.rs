@@ -8,7 +8,7 @@ use crate::{ImportId, InitExpr, Module, Result, ValType};
 pub type GlobalId = Id<Global>;
 
 /// A wasm global.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Global {
     // NB: Not public so that it can't get out of sync with the arena this is
     // contained within.
@@ -27,7 +27,7 @@ pub struct Global {
 impl Tombstone for Global {}
 
 /// The different kinds of globals a wasm module can have
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum GlobalKind {
     /// An imported global without a known initializer
     Import(ImportId),
@@ -50,7 +50,7 @@ impl Emit for Global {
 }
 
 /// The set of globals in each function in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleGlobals {
     /// The arena where the globals are stored.
     arena: TombstoneArena<Global>,
@@ -100,6 +100,11 @@ impl ModuleGlobals {
     pub fn iter(&self) -> impl Iterator<Item = &Global> {
         self.arena.iter().map(|(_, f)| f)
     }
+
+    /// Get a mutable reference to this module's globals.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Global> {
+        self.arena.iter_mut().map(|(_, f)| f)
+    }
 }
 
 impl Module {
@@ -0,0 +1,127 @@
+//! Layout hints for functions, recorded in the `walrus.hints` custom section.
+//!
+//! The section is a vector of entries, each a function index followed by one
+//! byte for the hint: `0` for cold and `1` for hot.
+
+use crate::emit::EmitContext;
+use crate::error::Result;
+use crate::module::functions::FunctionId;
+use crate::module::Module;
+use crate::parse::IndicesToIds;
+use anyhow::bail;
+use std::cmp;
+
+/// How often a function is expected to run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FunctionHint {
+    /// The function is rarely executed, e.g. error handling paths.
+    Cold,
+    /// The function is frequently executed.
+    Hot,
+}
+
+impl FunctionHint {
+    fn parse(byte: u8) -> Result<FunctionHint> {
+        Ok(match byte {
+            0 => FunctionHint::Cold,
+            1 => FunctionHint::Hot,
+            _ => bail!("invalid function hint {:#x}", byte),
+        })
+    }
+
+    fn byte(&self) -> u8 {
+        match self {
+            FunctionHint::Cold => 0,
+            FunctionHint::Hot => 1,
+        }
+    }
+}
+
+impl Module {
+    /// Mark the given function as hot or cold.
+    ///
+    /// Hints are recorded in the `walrus.hints` custom section when this
+    /// module is emitted, and read back when it is parsed again. They only
+    /// affect the layout of the code section once
+    /// `Module::order_functions_by_hint` is called.
+    pub fn set_function_hint(&mut self, id: FunctionId, hint: FunctionHint) {
+        self.funcs.hints.insert(id, hint);
+    }
+
+    /// Get the hint for the given function, if it has one.
+    pub fn function_hint(&self, id: FunctionId) -> Option<FunctionHint> {
+        self.funcs.hints.get(&id).cloned()
+    }
+
+    /// Remove the hint for the given function, if it has one.
+    pub fn remove_function_hint(&mut self, id: FunctionId) {
+        self.funcs.hints.remove(&id);
+    }
+
+    /// Set the function order so that hot functions are clustered at the
+    /// start of the code section and cold ones at its end.
+    ///
+    /// Within each group, functions keep the default order of largest to
+    /// smallest. This replaces any order set with `Module::set_function_order`.
+    pub fn order_functions_by_hint(&mut self) -> Result<()> {
+        let mut functions = self
+            .funcs
+            .iter_local()
+            .map(|(id, f)| {
+                let group = match self.function_hint(id) {
+                    Some(FunctionHint::Hot) => 0,
+                    None => 1,
+                    Some(FunctionHint::Cold) => 2,
+                };
+                (group, cmp::Reverse(f.size()), id)
+            })
+            .collect::<Vec<_>>();
+        functions.sort();
+        self.set_function_order(functions.into_iter().map(|(_, _, id)| id).collect())
+    }
+
+    /// Parse a `walrus.hints` section from the custom section payload
+    /// specified.
+    pub(crate) fn parse_hints_section(&mut self, data: &[u8], ids: &IndicesToIds) -> Result<()> {
+        log::debug!("parse walrus.hints section");
+
+        let mut reader = wasmparser::BinaryReader::new(data);
+        let mut hints = Vec::new();
+        for _ in 0..reader.read_var_u32()? {
+            let id = ids.get_func(reader.read_var_u32()?)?;
+            let hint = FunctionHint::parse(reader.read_u8()? as u8)?;
+            hints.push((id, hint));
+        }
+        if !reader.eof() {
+            bail!("trailing bytes at the end of the walrus.hints section");
+        }
+
+        self.funcs.hints.extend(hints);
+        Ok(())
+    }
+}
+
+pub(crate) fn emit_hints_section(cx: &mut EmitContext) {
+    log::debug!("emit walrus.hints section");
+    let hints = &cx.module.funcs.hints;
+    let mut hints = cx
+        .module
+        .funcs
+        .iter()
+        .filter_map(|f| {
+            let hint = *hints.get(&f.id())?;
+            Some((cx.indices.get_func_index(f.id()), hint))
+        })
+        .collect::<Vec<_>>();
+    if hints.is_empty() {
+        return;
+    }
+    hints.sort_by_key(|p| p.0); // sort by index
+
+    let mut cx = cx.custom_section("walrus.hints");
+    cx.encoder.usize(hints.len());
+    for (index, hint) in hints {
+        cx.encoder.u32(index);
+        cx.encoder.byte(hint.byte());
+    }
+}
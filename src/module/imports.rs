@@ -49,7 +49,7 @@ pub enum ImportKind {
 }
 
 /// The set of imports in a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleImports {
     arena: TombstoneArena<Import>,
 }
@@ -116,7 +116,7 @@ impl Module {
             let entry = entry?;
             match entry.ty {
                 wasmparser::ImportSectionEntryType::Function(idx) => {
-                    let ty = ids.get_type(idx)?;
+                    let ty = self.types.get_func_type(ids, idx)?;
                     let id = self.add_import_func(entry.module, entry.field, ty);
                     ids.push_func(id.0);
                 }
@@ -136,8 +136,8 @@ impl Module {
                         entry.module,
                         entry.field,
                         m.shared,
-                        m.limits.initial,
-                        m.limits.maximum,
+                        u64::from(m.limits.initial),
+                        m.limits.maximum.map(u64::from),
                     );
                     ids.push_memory(id.0);
                 }
@@ -175,8 +175,8 @@ impl Module {
         module: &str,
         name: &str,
         shared: bool,
-        initial: u32,
-        maximum: Option<u32>,
+        initial: u64,
+        maximum: Option<u64>,
     ) -> (MemoryId, ImportId) {
         let import = self.imports.arena.next_id();
         let mem = self.memories.add_import(shared, initial, maximum, import);
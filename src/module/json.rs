@@ -0,0 +1,105 @@
+//! A JSON summary of a module's structure, for tools not written in Rust.
+
+use crate::{ExportItem, FunctionKind, ImportKind, Module, Type, ValType};
+use serde_json::{json, Value};
+
+impl Module {
+    /// Describe this module's structure as JSON: its types, imports, exports,
+    /// and the signature and number of instructions of each function.
+    ///
+    /// This is a summary, not a full serialization, and can't be turned back
+    /// into a module. It looks like this:
+    ///
+    /// ```json
+    /// {
+    ///   "types": [{ "params": ["i32"], "results": [] }],
+    ///   "imports": [{ "module": "env", "name": "f", "kind": "function" }],
+    ///   "exports": [{ "name": "g", "kind": "function" }],
+    ///   "functions": [
+    ///     { "name": "f", "kind": "import", "params": ["i32"], "results": [] },
+    ///     {
+    ///       "name": null,
+    ///       "kind": "local",
+    ///       "params": [],
+    ///       "results": [],
+    ///       "instructions": 3
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Only available with the `serde` feature.
+    pub fn to_json(&self) -> Value {
+        let types = self
+            .types
+            .iter()
+            .filter(|ty| !ty.is_for_function_entry())
+            .map(signature)
+            .collect::<Vec<_>>();
+
+        let imports = self
+            .imports
+            .iter()
+            .map(|import| {
+                let kind = match import.kind {
+                    ImportKind::Function(_) => "function",
+                    ImportKind::Table(_) => "table",
+                    ImportKind::Memory(_) => "memory",
+                    ImportKind::Global(_) => "global",
+                };
+                json!({
+                    "module": import.module,
+                    "name": import.name,
+                    "kind": kind,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let exports = self
+            .exports
+            .iter()
+            .map(|export| {
+                let kind = match export.item {
+                    ExportItem::Function(_) => "function",
+                    ExportItem::Table(_) => "table",
+                    ExportItem::Memory(_) => "memory",
+                    ExportItem::Global(_) => "global",
+                };
+                json!({ "name": export.name, "kind": kind })
+            })
+            .collect::<Vec<_>>();
+
+        let functions = self
+            .funcs
+            .iter()
+            .map(|func| {
+                let mut summary = signature(self.types.get(func.ty()));
+                summary["name"] = json!(func.name);
+                match &func.kind {
+                    FunctionKind::Import(_) => summary["kind"] = json!("import"),
+                    FunctionKind::Local(local) => {
+                        summary["kind"] = json!("local");
+                        summary["instructions"] = json!(local.size());
+                    }
+                    FunctionKind::Uninitialized(_) => summary["kind"] = json!("uninitialized"),
+                }
+                summary
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "types": types,
+            "imports": imports,
+            "exports": exports,
+            "functions": functions,
+        })
+    }
+}
+
+fn signature(ty: &Type) -> Value {
+    let names = |tys: &[ValType]| tys.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+    json!({
+        "params": names(ty.params()),
+        "results": names(ty.results()),
+    })
+}
@@ -2,10 +2,12 @@
 
 use crate::ir::{Local, LocalId};
 use crate::ty::ValType;
+use crate::Module;
 use id_arena::Arena;
+use std::collections::HashMap;
 
 /// The set of locals in each function in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleLocals {
     arena: Arena<Local>,
 }
@@ -35,3 +37,68 @@ impl ModuleLocals {
         self.arena.iter().map(|(_, f)| f)
     }
 }
+
+impl Module {
+    /// Get the debug name of a local, if it has one.
+    pub fn local_name(&self, local: LocalId) -> Option<&str> {
+        self.locals.get(local).name.as_deref()
+    }
+
+    /// Set the debug name of a local, which is emitted in the `name` section.
+    ///
+    /// Names belong to the local itself rather than to its index, so they
+    /// stay attached to the right local however its function's locals get
+    /// renumbered when the module is emitted.
+    pub fn set_local_name(&mut self, local: LocalId, name: impl Into<String>) {
+        self.locals.get_mut(local).name = Some(name.into());
+    }
+}
+
+/// A pool of scratch locals for code generators.
+///
+/// Generated code often needs short-lived temporaries. Rather than adding a
+/// fresh local for each of them, take one with `get_scratch` and hand it back
+/// with `free_scratch` once its value is dead, so that later temporaries of the
+/// same type reuse it.
+#[derive(Debug, Default)]
+pub struct LocalPool {
+    free: HashMap<ValType, Vec<LocalId>>,
+    in_use: HashMap<LocalId, ValType>,
+}
+
+impl LocalPool {
+    /// Create a new, empty pool.
+    pub fn new() -> LocalPool {
+        LocalPool::default()
+    }
+
+    /// Add a fresh local of the given type that is never reused by this pool.
+    pub fn fresh(&mut self, locals: &mut ModuleLocals, ty: ValType) -> LocalId {
+        locals.add(ty)
+    }
+
+    /// Get a scratch local of the given type, reusing a freed one if there is
+    /// one and adding a new local to `locals` otherwise.
+    pub fn get_scratch(&mut self, locals: &mut ModuleLocals, ty: ValType) -> LocalId {
+        let local = match self.free.get_mut(&ty).and_then(|free| free.pop()) {
+            Some(local) => local,
+            None => locals.add(ty),
+        };
+        self.in_use.insert(local, ty);
+        local
+    }
+
+    /// Return a scratch local to the pool, so that it can be reused.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `local` isn't a scratch local from this pool that is currently
+    /// in use.
+    pub fn free_scratch(&mut self, local: LocalId) {
+        let ty = self
+            .in_use
+            .remove(&local)
+            .expect("can only free scratch locals that are in use");
+        self.free.entry(ty).or_default().push(local);
+    }
+}
@@ -1,24 +1,31 @@
 //! Memories used in a wasm module.
 
 use crate::emit::{Emit, EmitContext, Section};
-use crate::map::IdHashSet;
+use crate::map::{IdHashMap, IdHashSet};
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{Data, ImportId, Module, Result};
+use crate::{ActiveDataLocation, Data, DataKind, ImportId, Module, Result};
+use anyhow::bail;
+use std::convert::TryFrom;
 
 /// The id of a memory.
 pub type MemoryId = Id<Memory>;
 
 /// A memory in the wasm.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Memory {
     id: MemoryId,
     /// Is this memory shared?
     pub shared: bool,
+    /// Is this a 64-bit memory, from the memory64 proposal?
+    ///
+    /// The limits of a 32-bit memory must fit in a `u32` for it to be
+    /// emitted.
+    pub memory64: bool,
     /// The initial page size for this memory.
-    pub initial: u32,
+    pub initial: u64,
     /// The maximum page size for this memory.
-    pub maximum: Option<u32>,
+    pub maximum: Option<u64>,
     /// Whether or not this memory is imported, and if so from where.
     pub import: Option<ImportId>,
     /// Active data segments that will be used to initialize this memory.
@@ -36,23 +43,71 @@ impl Memory {
     pub fn id(&self) -> MemoryId {
         self.id
     }
+
+    /// Mark this memory as shared, with the given maximum page size.
+    ///
+    /// Shared memories must have a maximum size, so the flag and maximum are
+    /// set together. Returns an error, leaving this memory unchanged, if `max`
+    /// is not a valid maximum for this memory.
+    pub fn set_shared(&mut self, max: u64) -> Result<()> {
+        let limit = self.max_pages();
+        if max > limit {
+            bail!("maximum of {} pages exceeds the limit of {}", max, limit);
+        }
+        if max < self.initial {
+            bail!(
+                "maximum of {} pages is less than the initial {} pages",
+                max,
+                self.initial
+            );
+        }
+        self.shared = true;
+        self.maximum = Some(max);
+        Ok(())
+    }
+
+    /// The largest size, in pages, that this memory's limits may have: 32-bit
+    /// memories can address 4GiB, and 64-bit ones 2^64 bytes, in 64KiB pages.
+    pub(crate) fn max_pages(&self) -> u64 {
+        if self.memory64 {
+            1 << 48
+        } else {
+            1 << 16
+        }
+    }
 }
 
 impl Emit for Memory {
     fn emit(&self, cx: &mut EmitContext) {
+        let mut flags = 0x00;
+        if self.maximum.is_some() {
+            flags |= if self.shared { 0x03 } else { 0x01 };
+        }
+        if self.memory64 {
+            flags |= 0x04;
+        }
+        cx.encoder.byte(flags);
+        self.emit_limit(cx, self.initial);
         if let Some(max) = self.maximum {
-            cx.encoder.byte(if self.shared { 0x03 } else { 0x01 });
-            cx.encoder.u32(self.initial);
-            cx.encoder.u32(max);
-        } else {
-            cx.encoder.byte(0x00);
-            cx.encoder.u32(self.initial);
+            self.emit_limit(cx, max);
+        }
+    }
+}
+
+impl Memory {
+    fn emit_limit(&self, cx: &mut EmitContext, pages: u64) {
+        match u32::try_from(pages) {
+            Ok(pages) if !self.memory64 => cx.encoder.u32(pages),
+            // A 32-bit memory whose limit doesn't fit in a `u32` fails
+            // validation, so this emits an invalid module, like any other
+            // invalid module. `Module::try_emit_wasm` reports it instead.
+            _ => cx.encoder.u64(pages),
         }
     }
 }
 
 /// The set of memories in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleMemories {
     arena: TombstoneArena<Memory>,
 }
@@ -62,14 +117,15 @@ impl ModuleMemories {
     pub fn add_import(
         &mut self,
         shared: bool,
-        initial: u32,
-        maximum: Option<u32>,
+        initial: u64,
+        maximum: Option<u64>,
         import: ImportId,
     ) -> MemoryId {
         let id = self.arena.next_id();
         let id2 = self.arena.alloc(Memory {
             id,
             shared,
+            memory64: false,
             initial,
             maximum,
             import: Some(import),
@@ -81,11 +137,12 @@ impl ModuleMemories {
 
     /// Construct a new memory, that does not originate from any of the input
     /// wasm memories.
-    pub fn add_local(&mut self, shared: bool, initial: u32, maximum: Option<u32>) -> MemoryId {
+    pub fn add_local(&mut self, shared: bool, initial: u64, maximum: Option<u64>) -> MemoryId {
         let id = self.arena.next_id();
         let id2 = self.arena.alloc(Memory {
             id,
             shared,
+            memory64: false,
             initial,
             maximum,
             import: None,
@@ -134,15 +191,78 @@ impl Module {
         log::debug!("parse memory section");
         for m in section {
             let m = m?;
-            let id = self
-                .memories
-                .add_local(m.shared, m.limits.initial, m.limits.maximum);
+            let id = self.memories.add_local(
+                m.shared,
+                u64::from(m.limits.initial),
+                m.limits.maximum.map(u64::from),
+            );
             ids.push_memory(id);
         }
         Ok(())
     }
+
+    /// Raise the initial size of each memory so that it covers the end of
+    /// every active data segment with a constant offset that initializes it.
+    ///
+    /// Segments with `global.get` offsets are ignored, since their end isn't
+    /// known until instantiation. Returns an error, leaving every memory
+    /// unchanged, if a memory would have to grow past its maximum size or the
+    /// spec's limit, or if it is imported, since growing it would change the
+    /// type of the import.
+    pub fn fix_memory_minimums(&mut self) -> Result<()> {
+        let mut minimums = IdHashMap::default();
+        for data in self.data.iter() {
+            let active = match &data.kind {
+                DataKind::Active(active) => active,
+                DataKind::Passive => continue,
+            };
+            let offset = match active.location {
+                ActiveDataLocation::Absolute(offset) => offset,
+                ActiveDataLocation::Relative(_) => continue,
+            };
+            let end = u64::from(offset) + data.value.len() as u64;
+            let pages = end.div_ceil(PAGE_SIZE);
+            let min = minimums.entry(active.memory).or_insert(0);
+            *min = pages.max(*min);
+        }
+
+        for (&id, &pages) in minimums.iter() {
+            let memory = self.memories.get(id);
+            if pages <= memory.initial {
+                continue;
+            }
+            if memory.import.is_some() {
+                bail!(
+                    "imported memory {:?} must have at least {} pages for its data segments, \
+                     but is declared with {}",
+                    id,
+                    pages,
+                    memory.initial
+                );
+            }
+            let limit = memory.maximum.unwrap_or(u64::MAX).min(memory.max_pages());
+            if pages > limit {
+                bail!(
+                    "memory {:?} must have at least {} pages for its data segments, \
+                     but can have at most {}",
+                    id,
+                    pages,
+                    limit
+                );
+            }
+        }
+
+        for (id, pages) in minimums {
+            let memory = self.memories.get_mut(id);
+            memory.initial = pages.max(memory.initial);
+        }
+        Ok(())
+    }
 }
 
+/// The size of a wasm page, in bytes.
+const PAGE_SIZE: u64 = 65536;
+
 impl Emit for ModuleMemories {
     fn emit(&self, cx: &mut EmitContext) {
         log::debug!("emit memory section");
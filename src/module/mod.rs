@@ -3,37 +3,50 @@
 mod config;
 mod custom;
 mod data;
+mod dylink;
 mod elements;
 mod exports;
 mod functions;
 mod globals;
+mod hints;
 mod imports;
+#[cfg(feature = "serde")]
+mod json;
 mod locals;
 mod memories;
 mod producers;
 mod tables;
+mod target_features;
 mod types;
 
-use crate::emit::{Emit, EmitContext, IdsToIndices, Section};
+use crate::emit::{
+    Emit, EmitContext, FeatureLevel, IdsToIndices, Section, SectionLayout, SourceMap,
+};
 use crate::encode::Encoder;
-use crate::error::Result;
+use crate::error::{Result, Warning};
 pub use crate::ir::InstrLocId;
+use crate::ir::{Instr, Unop};
 pub use crate::module::custom::{
     CustomSection, CustomSectionId, ModuleCustomSections, RawCustomSection, TypedCustomSectionId,
     UntypedCustomSectionId,
 };
 pub use crate::module::data::{ActiveData, ActiveDataLocation, Data, DataId, DataKind, ModuleData};
+pub use crate::module::dylink::{DylinkInfo, DylinkMemInfo};
 pub use crate::module::elements::ElementKind;
 pub use crate::module::elements::{Element, ElementId, ModuleElements};
 pub use crate::module::exports::{Export, ExportId, ExportItem, ModuleExports};
 pub use crate::module::functions::{Function, FunctionId, ModuleFunctions};
-pub use crate::module::functions::{FunctionKind, ImportedFunction, LocalFunction};
+pub use crate::module::functions::{
+    FunctionKind, ImportedFunction, Liveness, LocalFunction, ResourceUse,
+};
 pub use crate::module::globals::{Global, GlobalId, GlobalKind, ModuleGlobals};
+pub use crate::module::hints::FunctionHint;
 pub use crate::module::imports::{Import, ImportId, ImportKind, ModuleImports};
-pub use crate::module::locals::ModuleLocals;
+pub use crate::module::locals::{LocalPool, ModuleLocals};
 pub use crate::module::memories::{Memory, MemoryId, ModuleMemories};
 pub use crate::module::producers::ModuleProducers;
 pub use crate::module::tables::{ModuleTables, Table, TableId};
+pub use crate::module::target_features::{FeaturePrefix, ModuleTargetFeatures};
 pub use crate::module::types::ModuleTypes;
 use crate::parse::IndicesToIds;
 use anyhow::{bail, Context};
@@ -44,7 +57,11 @@ use std::path::Path;
 pub use self::config::ModuleConfig;
 
 /// A wasm module.
-#[derive(Debug, Default)]
+///
+/// Cloning a module deep-copies all of its items, including function bodies.
+/// Items keep their ids, so an id taken from the original module refers to
+/// the corresponding item in the clone.
+#[derive(Clone, Debug, Default)]
 #[allow(missing_docs)]
 pub struct Module {
     pub imports: ModuleImports,
@@ -63,12 +80,21 @@ pub struct Module {
     pub start: Option<FunctionId>,
     /// Representation of the eventual custom section, `producers`
     pub producers: ModuleProducers,
+    /// The `dylink.0` custom section, if this module is a shared library for
+    /// dynamic linking. If the section can't be parsed, it is kept in
+    /// `customs` as a raw section instead.
+    pub dylink: Option<DylinkInfo>,
+    /// Representation of the eventual custom section, `target_features`
+    pub target_features: ModuleTargetFeatures,
     /// Custom sections found in this module.
     pub customs: ModuleCustomSections,
     /// The name of this module, used for debugging purposes in the `name`
     /// custom section.
     pub name: Option<String>,
     pub(crate) config: ModuleConfig,
+    /// A hash of the code section payload this module was parsed from, used
+    /// to tell whether DWARF custom sections are still valid when emitting.
+    pub(crate) code_section_hash: Option<u64>,
 }
 
 /// Maps from an offset of an instruction in the input Wasm to its offset in the
@@ -111,7 +137,44 @@ impl Module {
         ModuleConfig::new().parse(wasm)
     }
 
-    fn parse(wasm: &[u8], config: &ModuleConfig) -> Result<Module> {
+    /// Construct a new module from the in-memory wasm buffer with the default
+    /// configuration, also returning any non-fatal issues found while parsing
+    /// it.
+    ///
+    /// The warnings are returned even if parsing fails.
+    pub fn from_buffer_with_warnings(wasm: &[u8]) -> (Result<Module>, Vec<Warning>) {
+        ModuleConfig::new().parse_with_warnings(wasm)
+    }
+
+    /// Construct a new module from as much of the in-memory wasm buffer as
+    /// can be parsed, with the default configuration.
+    ///
+    /// Sections are parsed in order until one fails to parse, for example
+    /// because the buffer was truncated in the middle of it. That section and
+    /// everything after it are ignored, and the module parsed so far is
+    /// returned along with the offset of the section that failed, or the
+    /// length of the buffer if the whole module was parsed. Functions whose
+    /// bodies weren't parsed are given a body that is just `unreachable`.
+    ///
+    /// Errors in the module's header are still reported as errors.
+    pub fn parse_lenient(wasm: &[u8]) -> Result<(Module, usize)> {
+        Module::parse_sections(wasm, &ModuleConfig::new(), &mut Vec::new(), true, None)
+    }
+
+    fn parse(wasm: &[u8], config: &ModuleConfig, warnings: &mut Vec<Warning>) -> Result<Module> {
+        Module::parse_sections(wasm, config, warnings, false, None).map(|(module, _)| module)
+    }
+
+    /// Parse the sections of `wasm`. If `function_errors` is given, functions
+    /// that fail to parse are given a stub body and their errors are added to
+    /// it, instead of failing the whole parse.
+    pub(crate) fn parse_sections(
+        wasm: &[u8],
+        config: &ModuleConfig,
+        warnings: &mut Vec<Warning>,
+        lenient: bool,
+        mut function_errors: Option<&mut Vec<(FunctionId, anyhow::Error)>>,
+    ) -> Result<(Module, usize)> {
         let mut parser = wasmparser::ModuleReader::new(wasm)?;
         if parser.get_version() != 1 {
             bail!("only support version 1 of wasm");
@@ -123,113 +186,183 @@ impl Module {
         let mut function_section_size = None;
         let mut data_count = None;
 
+        let mut stopped_at = None;
         while !parser.eof() {
-            let section = parser.read()?;
-            match section.code {
-                wasmparser::SectionCode::Data => {
-                    let reader = section.get_data_section_reader()?;
-                    ret.parse_data(reader, &mut indices, data_count)
-                        .context("failed to parse data section")?;
-                }
-                wasmparser::SectionCode::Type => {
-                    let reader = section.get_type_section_reader()?;
-                    ret.parse_types(reader, &mut indices)
-                        .context("failed to parse type section")?;
-                }
-                wasmparser::SectionCode::Import => {
-                    let reader = section.get_import_section_reader()?;
-                    ret.parse_imports(reader, &mut indices)
-                        .context("failed to parse import section")?;
-                }
-                wasmparser::SectionCode::Table => {
-                    let reader = section.get_table_section_reader()?;
-                    ret.parse_tables(reader, &mut indices)
-                        .context("failed to parse table section")?;
-                }
-                wasmparser::SectionCode::Memory => {
-                    let reader = section.get_memory_section_reader()?;
-                    ret.parse_memories(reader, &mut indices)
-                        .context("failed to parse memory section")?;
-                }
-                wasmparser::SectionCode::Global => {
-                    let reader = section.get_global_section_reader()?;
-                    ret.parse_globals(reader, &mut indices)
-                        .context("failed to parse global section")?;
-                }
-                wasmparser::SectionCode::Export => {
-                    let reader = section.get_export_section_reader()?;
-                    ret.parse_exports(reader, &mut indices)
-                        .context("failed to parse export section")?;
-                }
-                wasmparser::SectionCode::Element => {
-                    let reader = section.get_element_section_reader()?;
-                    ret.parse_elements(reader, &mut indices)
-                        .context("failed to parse element section")?;
-                }
-                wasmparser::SectionCode::Start => {
-                    let idx = section.get_start_section_content()?;
-                    if ret.start.is_some() {
-                        bail!("multiple start sections found");
+            let offset = parser.current_position();
+            let mut parse_section = || -> Result<()> {
+                let section = parser.read()?;
+                match section.code {
+                    wasmparser::SectionCode::Data => {
+                        let reader = section.get_data_section_reader()?;
+                        ret.parse_data(reader, &mut indices, data_count)
+                            .context("failed to parse data section")?;
+                        // The data section has now been checked against the
+                        // `DataCount` section, if any.
+                        data_count = None;
                     }
-                    ret.start = Some(indices.get_func(idx)?);
-                }
-                wasmparser::SectionCode::Function => {
-                    let reader = section.get_function_section_reader()?;
-                    function_section_size = Some(reader.get_count());
-                    ret.declare_local_functions(reader, &mut indices)
-                        .context("failed to parse function section")?;
-                }
-                wasmparser::SectionCode::Code => {
-                    let function_section_size = match function_section_size.take() {
-                        Some(i) => i,
-                        None => bail!("cannot have a code section without function section"),
-                    };
-                    let reader = section.get_code_section_reader()?;
-                    let on_instr_loc = config.on_instr_loc.as_ref().map(|f| f.as_ref());
-                    ret.parse_local_functions(
-                        reader,
-                        function_section_size,
-                        &mut indices,
-                        on_instr_loc,
-                    )
-                    .context("failed to parse code section")?;
-                }
-                wasmparser::SectionCode::DataCount => {
-                    let count = section.get_data_count_section_content()?;
-                    data_count = Some(count);
-                    ret.reserve_data(count, &mut indices);
-                }
-                wasmparser::SectionCode::Custom { name, kind: _ } => {
-                    let result = match name {
-                        "producers" => {
-                            let reader = section.get_producers_section_reader()?;
-                            ret.parse_producers_section(reader)
+                    wasmparser::SectionCode::Type if config.gc_passthrough => {
+                        let mut reader = section.get_binary_reader();
+                        let len = reader.bytes_remaining();
+                        let payload = reader.read_bytes(len)?;
+                        ret.parse_types_gc_passthrough(payload, &mut indices)
+                            .context("failed to parse type section")?;
+                    }
+                    wasmparser::SectionCode::Type => {
+                        let reader = section.get_type_section_reader()?;
+                        ret.parse_types(reader, &mut indices)
+                            .context("failed to parse type section")?;
+                    }
+                    wasmparser::SectionCode::Import => {
+                        let reader = section.get_import_section_reader()?;
+                        ret.parse_imports(reader, &mut indices)
+                            .context("failed to parse import section")?;
+                    }
+                    wasmparser::SectionCode::Table => {
+                        let reader = section.get_table_section_reader()?;
+                        ret.parse_tables(reader, &mut indices)
+                            .context("failed to parse table section")?;
+                    }
+                    wasmparser::SectionCode::Memory => {
+                        let reader = section.get_memory_section_reader()?;
+                        ret.parse_memories(reader, &mut indices)
+                            .context("failed to parse memory section")?;
+                    }
+                    wasmparser::SectionCode::Global => {
+                        let reader = section.get_global_section_reader()?;
+                        ret.parse_globals(reader, &mut indices)
+                            .context("failed to parse global section")?;
+                    }
+                    wasmparser::SectionCode::Export => {
+                        let reader = section.get_export_section_reader()?;
+                        ret.parse_exports(reader, &mut indices)
+                            .context("failed to parse export section")?;
+                    }
+                    wasmparser::SectionCode::Element => {
+                        let reader = section.get_element_section_reader()?;
+                        ret.parse_elements(reader, &mut indices)
+                            .context("failed to parse element section")?;
+                    }
+                    wasmparser::SectionCode::Start => {
+                        let idx = section.get_start_section_content()?;
+                        if ret.start.is_some() {
+                            bail!("multiple start sections found");
                         }
-                        "name" => section
-                            .get_name_section_reader()
-                            .map_err(anyhow::Error::from)
-                            .and_then(|r| ret.parse_name_section(r, &indices)),
-                        _ => {
-                            log::debug!("parsing custom section `{}`", name);
-                            let mut reader = section.get_binary_reader();
-                            let len = reader.bytes_remaining();
-                            let payload = reader.read_bytes(len)?;
-                            ret.customs.add(RawCustomSection {
+                        ret.start = Some(indices.get_func(idx)?);
+                    }
+                    wasmparser::SectionCode::Function => {
+                        let reader = section.get_function_section_reader()?;
+                        function_section_size = Some(reader.get_count());
+                        ret.declare_local_functions(reader, &mut indices)
+                            .context("failed to parse function section")?;
+                    }
+                    wasmparser::SectionCode::Code => {
+                        let function_section_size = match function_section_size.take() {
+                            Some(i) => i,
+                            None => bail!("cannot have a code section without function section"),
+                        };
+                        let range = section.range();
+                        ret.code_section_hash =
+                            Some(functions::code_section_hash(&wasm[range.start..range.end]));
+                        let reader = section.get_code_section_reader()?;
+                        let on_instr_loc = config.on_instr_loc.as_ref().map(|f| f.as_ref());
+                        ret.parse_local_functions(
+                            reader,
+                            function_section_size,
+                            &mut indices,
+                            on_instr_loc,
+                            function_errors.as_deref_mut(),
+                        )
+                        .context("failed to parse code section")?;
+                    }
+                    wasmparser::SectionCode::DataCount => {
+                        let count = section.get_data_count_section_content()?;
+                        data_count = Some(count);
+                        ret.reserve_data(count, &mut indices);
+                    }
+                    wasmparser::SectionCode::Custom { name, kind: _ } => {
+                        let result = match name {
+                            "producers" => {
+                                let reader = section.get_producers_section_reader()?;
+                                ret.parse_producers_section(reader)
+                            }
+                            "dylink.0" => {
+                                let mut reader = section.get_binary_reader();
+                                let len = reader.bytes_remaining();
+                                let payload = reader.read_bytes(len)?;
+                                let result = ret.parse_dylink_section(payload);
+                                if result.is_err() {
+                                    // Keep it as is, so it is still emitted.
+                                    ret.customs.add(RawCustomSection {
+                                        name: name.to_string(),
+                                        data: payload.to_vec(),
+                                    });
+                                }
+                                result
+                            }
+                            "target_features" => {
+                                let mut reader = section.get_binary_reader();
+                                let len = reader.bytes_remaining();
+                                let payload = reader.read_bytes(len)?;
+                                ret.parse_target_features_section(payload)
+                            }
+                            "walrus.hints" => {
+                                let mut reader = section.get_binary_reader();
+                                let len = reader.bytes_remaining();
+                                let payload = reader.read_bytes(len)?;
+                                ret.parse_hints_section(payload, &indices)
+                            }
+                            "name" => section
+                                .get_name_section_reader()
+                                .map_err(anyhow::Error::from)
+                                .and_then(|r| ret.parse_name_section(r, &indices)),
+                            _ => {
+                                log::debug!("parsing custom section `{}`", name);
+                                warnings.push(Warning::UnknownCustomSection {
+                                    name: name.to_string(),
+                                });
+                                let mut reader = section.get_binary_reader();
+                                let len = reader.bytes_remaining();
+                                let payload = reader.read_bytes(len)?;
+                                ret.customs.add(RawCustomSection {
+                                    name: name.to_string(),
+                                    data: payload.to_vec(),
+                                });
+                                return Ok(());
+                            }
+                        };
+                        if let Err(e) = result {
+                            log::warn!("failed to parse `{}` custom section {}", name, e);
+                            warnings.push(Warning::InvalidCustomSection {
                                 name: name.to_string(),
-                                data: payload.to_vec(),
+                                message: e.to_string(),
                             });
-                            continue;
                         }
-                    };
-                    if let Err(e) = result {
-                        log::warn!("failed to parse `{}` custom section {}", name, e);
                     }
                 }
+                Ok(())
+            };
+            if let Err(e) = parse_section() {
+                if !lenient {
+                    return Err(e);
+                }
+                log::warn!("stopped parsing at offset {}: {:?}", offset, e);
+                stopped_at = Some(offset);
+                break;
             }
         }
 
-        if function_section_size.is_some() {
-            bail!("cannot define a function section without a code section");
+        if stopped_at.is_some() {
+            ret.stub_uninitialized_functions();
+        } else {
+            if function_section_size.is_some() {
+                bail!("cannot define a function section without a code section");
+            }
+            if let Some(count) = data_count.filter(|c| *c > 0) {
+                bail!(
+                    "data count section declares {} data segments but there is no data section",
+                    count
+                );
+            }
         }
 
         ret.producers
@@ -245,7 +378,7 @@ impl Module {
         }
 
         log::debug!("parse complete");
-        Ok(ret)
+        Ok((ret, stopped_at.unwrap_or(wasm.len())))
     }
 
     /// Emit this module into a `.wasm` file at the given path.
@@ -260,6 +393,60 @@ impl Module {
 
     /// Emit this module into an in-memory wasm buffer.
     pub fn emit_wasm(&mut self) -> Vec<u8> {
+        self.emit_wasm_with_layout().0
+    }
+
+    /// Validate this module and then emit it into an in-memory wasm buffer.
+    ///
+    /// `emit_wasm` emits whatever is in the module, even if it is invalid,
+    /// such as a 32-bit memory whose limits don't fit in a `u32`, and panics
+    /// on instructions that have no encoding at the configured
+    /// `FeatureLevel`. This runs the same checks as are run when a module is
+    /// parsed first, and checks that every instruction can be encoded, and
+    /// returns an error instead if they fail.
+    pub fn try_emit_wasm(&mut self) -> Result<Vec<u8>> {
+        crate::passes::validate::run(self)?;
+        if self.config.feature_level == FeatureLevel::Legacy {
+            for (id, func) in self.funcs.iter_local() {
+                for (_, seq) in func.builder().arena.iter() {
+                    for (instr, _) in seq.instrs.iter() {
+                        match instr {
+                            Instr::Unop(Unop { op }) if op.is_standard_only() => bail!(
+                                "`{:?}` in function {:?} has no legacy encoding; emit with \
+                                 `FeatureLevel::Standard`",
+                                op,
+                                id
+                            ),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(self.emit_wasm())
+    }
+
+    /// Emit this module into an in-memory wasm buffer, along with where each
+    /// section and function body ended up in it.
+    ///
+    /// Like `emit_wasm`, this takes `&mut self` since custom sections are
+    /// temporarily taken out of the module while the rest of it is emitted.
+    pub fn emit_wasm_with_layout(&mut self) -> (Vec<u8>, SectionLayout) {
+        let (wasm, layout, _) = self.emit_wasm_impl(false);
+        (wasm, layout)
+    }
+
+    /// Emit this module into an in-memory wasm buffer, along with a map from
+    /// the instructions it was parsed from to where they ended up in it.
+    ///
+    /// The map is generated whether or not the module was configured with
+    /// `ModuleConfig::preserve_code_transform`.
+    pub fn emit_wasm_with_source_map(&mut self) -> (Vec<u8>, SourceMap) {
+        let (wasm, _, code_transform) = self.emit_wasm_impl(true);
+        (wasm, SourceMap::new(code_transform))
+    }
+
+    fn emit_wasm_impl(&mut self, source_map: bool) -> (Vec<u8>, SectionLayout, CodeTransform) {
         log::debug!("start emit");
 
         let indices = &mut IdsToIndices::default();
@@ -275,7 +462,16 @@ impl Module {
             encoder: Encoder::new(&mut wasm),
             locals: Default::default(),
             code_transform: Vec::new(),
+            generate_code_transform: self.config.preserve_code_transform || source_map,
+            code_section_hash: None,
+            layout: SectionLayout::default(),
         };
+        // The `dylink.0` section must come before any other section.
+        if let Some(dylink) = &self.dylink {
+            dylink.emit(&mut cx);
+        } else if let Some(raw) = customs.remove_raw("dylink.0") {
+            cx.custom_section(&raw.name).encoder.raw(&raw.data);
+        }
         self.types.emit(&mut cx);
         self.imports.emit(&mut cx);
         self.funcs.emit_func_section(&mut cx);
@@ -298,13 +494,32 @@ impl Module {
         if !self.config.skip_producers_section {
             self.producers.emit(&mut cx);
         }
+        self.target_features.emit(&mut cx);
+        hints::emit_hints_section(&mut cx);
 
         let indices = mem::replace(cx.indices, Default::default());
 
+        // DWARF refers to code by its offset within the code section, so it is
+        // only still valid if we emitted exactly the code section we parsed.
+        let dwarf_is_stale = cx.code_section_hash != self.code_section_hash;
+
         for (_id, section) in customs.iter_mut() {
-            if !self.config.generate_dwarf && section.name().starts_with(".debug") {
-                log::debug!("skipping DWARF custom section {}", section.name());
-                continue;
+            if !self.config.generate_dwarf && dwarf_is_stale && section.name().starts_with(".debug")
+            {
+                let keep = match &self.config.on_dwarf_invalidation {
+                    Some(f) => f(section.name()),
+                    None => {
+                        log::warn!(
+                            "dropping DWARF custom section {} since the code section changed",
+                            section.name()
+                        );
+                        false
+                    }
+                };
+                if !keep {
+                    log::debug!("skipping DWARF custom section {}", section.name());
+                    continue;
+                }
             }
 
             log::debug!("emitting custom section {}", section.name());
@@ -318,8 +533,10 @@ impl Module {
                 .raw(&section.data(&indices));
         }
 
+        let layout = mem::take(&mut cx.layout);
+        let code_transform = mem::take(&mut cx.code_transform);
         log::debug!("emission finished");
-        wasm
+        (wasm, layout, code_transform)
     }
 
     /// Returns an iterator over all functions in this module
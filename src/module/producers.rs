@@ -8,18 +8,18 @@ use crate::error::Result;
 use crate::module::Module;
 
 /// Representation of the wasm custom section `producers`
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleProducers {
     fields: Vec<Field>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Field {
     name: String,
     values: Vec<Value>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Value {
     name: String,
     version: String,
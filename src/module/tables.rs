@@ -11,7 +11,7 @@ use anyhow::bail;
 pub type TableId = Id<Table>;
 
 /// A table in the wasm.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Table {
     id: TableId,
     /// The initial size of this table
@@ -47,7 +47,7 @@ impl Emit for Table {
 }
 
 /// The set of tables in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleTables {
     /// The arena containing this module's tables.
     arena: TombstoneArena<Table>,
@@ -0,0 +1,124 @@
+//! Handling of the wasm `target_features` section
+//!
+//! Specified upstream at
+//! https://github.com/WebAssembly/tool-conventions/blob/master/Linking.md#target-features-section
+
+use crate::emit::{Emit, EmitContext};
+use crate::error::Result;
+use crate::module::Module;
+use anyhow::bail;
+
+/// Representation of the wasm custom section `target_features`, which records
+/// the features a module was compiled with so that linkers can check that
+/// the objects they link together are compatible.
+#[derive(Clone, Debug, Default)]
+pub struct ModuleTargetFeatures {
+    features: Vec<(FeaturePrefix, String)>,
+}
+
+/// How a feature in the `target_features` section is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeaturePrefix {
+    /// The feature is used by this module, written as `+`.
+    Used,
+    /// The feature is used by this module, and any module linked with it
+    /// must use it too, written as `=`.
+    Required,
+    /// The feature must not be used by any module linked with this one,
+    /// written as `-`.
+    Disallowed,
+}
+
+impl FeaturePrefix {
+    fn parse(byte: u8) -> Result<FeaturePrefix> {
+        Ok(match byte {
+            b'+' => FeaturePrefix::Used,
+            b'=' => FeaturePrefix::Required,
+            b'-' => FeaturePrefix::Disallowed,
+            _ => bail!("invalid target feature prefix {:#x}", byte),
+        })
+    }
+
+    fn byte(&self) -> u8 {
+        match self {
+            FeaturePrefix::Used => b'+',
+            FeaturePrefix::Required => b'=',
+            FeaturePrefix::Disallowed => b'-',
+        }
+    }
+}
+
+impl ModuleTargetFeatures {
+    /// Adds a feature, such as `simd128`, to the target features section,
+    /// replacing the prefix it had if it was already there.
+    pub fn add_feature(&mut self, name: &str, prefix: FeaturePrefix) {
+        match self.features.iter_mut().find(|(_, n)| n == name) {
+            Some(feature) => feature.0 = prefix,
+            None => self.features.push((prefix, name.to_string())),
+        }
+    }
+
+    /// Get the prefix of the given feature, if it is in the target features
+    /// section.
+    pub fn get(&self, name: &str) -> Option<FeaturePrefix> {
+        self.features
+            .iter()
+            .find(|(_, n)| n == name)
+            .map(|(prefix, _)| *prefix)
+    }
+
+    /// Removes a feature from the target features section.
+    pub fn remove(&mut self, name: &str) {
+        self.features.retain(|(_, n)| n != name);
+    }
+
+    /// Iterate over the features in the target features section, and their
+    /// prefixes, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, FeaturePrefix)> {
+        self.features
+            .iter()
+            .map(|(prefix, name)| (name.as_str(), *prefix))
+    }
+
+    /// Clear the target features section of all features
+    pub fn clear(&mut self) {
+        self.features.truncate(0);
+    }
+}
+
+impl Module {
+    /// Parse a `target_features` section from the custom section payload
+    /// specified.
+    pub(crate) fn parse_target_features_section(&mut self, data: &[u8]) -> Result<()> {
+        log::debug!("parse target_features section");
+
+        let mut reader = wasmparser::BinaryReader::new(data);
+        let mut features = ModuleTargetFeatures::default();
+        for _ in 0..reader.read_var_u32()? {
+            let prefix = FeaturePrefix::parse(reader.read_u8()? as u8)?;
+            let name = reader.read_string()?;
+            features.add_feature(name, prefix);
+        }
+        if !reader.eof() {
+            bail!("trailing bytes at the end of the target_features section");
+        }
+
+        self.target_features = features;
+        Ok(())
+    }
+}
+
+impl Emit for ModuleTargetFeatures {
+    fn emit(&self, cx: &mut EmitContext) {
+        log::debug!("emit target_features section");
+        if self.features.is_empty() {
+            return;
+        }
+        let mut cx = cx.custom_section("target_features");
+        cx.encoder.usize(self.features.len());
+        for (prefix, name) in self.features.iter() {
+            cx.encoder.byte(prefix.byte());
+            cx.encoder.str(name);
+        }
+    }
+}
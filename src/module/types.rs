@@ -6,9 +6,14 @@ use crate::error::Result;
 use crate::module::Module;
 use crate::parse::IndicesToIds;
 use crate::ty::{Type, TypeId, ValType};
+use anyhow::bail;
+
+/// The forms of GC type definitions that can be preserved opaquely.
+const STRUCT: u8 = 0x5f;
+const ARRAY: u8 = 0x5e;
 
 /// The set of de-duplicated types within a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleTypes {
     arena: ArenaSet<Type>,
 }
@@ -91,7 +96,11 @@ impl ModuleTypes {
     /// Find the existing type for the given parameters and results.
     pub fn find(&self, params: &[ValType], results: &[ValType]) -> Option<TypeId> {
         self.arena.iter().find_map(|(id, ty)| {
-            if !ty.is_for_function_entry() && ty.params() == params && ty.results() == results {
+            if !ty.is_for_function_entry()
+                && !ty.is_opaque()
+                && ty.params() == params
+                && ty.results() == results
+            {
                 Some(id)
             } else {
                 None
@@ -108,6 +117,16 @@ impl ModuleTypes {
             }
         })
     }
+
+    /// Get the function type at `index` in the original wasm binary, failing
+    /// if it is an opaque GC type instead.
+    pub(crate) fn get_func_type(&self, ids: &IndicesToIds, index: u32) -> Result<TypeId> {
+        let id = ids.get_type(index)?;
+        if self.get(id).is_opaque() {
+            bail!("type {} is not a function type", index);
+        }
+        Ok(id)
+    }
 }
 
 impl Module {
@@ -139,6 +158,78 @@ impl Module {
 
         Ok(())
     }
+
+    /// Construct the set of types within a module from the raw type section,
+    /// keeping GC `struct` and `array` types as opaque types.
+    ///
+    /// wasmparser doesn't understand GC types, so this decodes the section
+    /// itself.
+    pub(crate) fn parse_types_gc_passthrough(
+        &mut self,
+        section: &[u8],
+        ids: &mut IndicesToIds,
+    ) -> Result<()> {
+        log::debug!("parsing type section with GC types");
+        let mut reader = wasmparser::BinaryReader::new(section);
+        for _ in 0..reader.read_var_u32()? {
+            let start = reader.current_position();
+            let id = self.types.arena.next_id();
+            let ty = match reader.read_u8()? as u8 {
+                0x60 => {
+                    let params = read_val_types(&mut reader)?;
+                    let results = read_val_types(&mut reader)?;
+                    Type::new(id, params, results)
+                }
+                form @ STRUCT | form @ ARRAY => {
+                    let fields = if form == STRUCT {
+                        reader.read_var_u32()?
+                    } else {
+                        1
+                    };
+                    for _ in 0..fields {
+                        skip_storage_type(&mut reader)?;
+                        match reader.read_u8()? {
+                            0 | 1 => {}
+                            m => bail!("invalid field mutability {:#x}", m),
+                        }
+                    }
+                    let end = reader.current_position();
+                    Type::opaque(id, section[start..end].into())
+                }
+                form => bail!("unsupported type form {:#x}", form),
+            };
+            let id = self.types.arena.insert(ty);
+            ids.push_type(id);
+        }
+        if !reader.eof() {
+            bail!("trailing bytes at the end of the type section");
+        }
+
+        Ok(())
+    }
+}
+
+fn read_val_types(reader: &mut wasmparser::BinaryReader) -> Result<Box<[ValType]>> {
+    (0..reader.read_var_u32()?)
+        .map(|_| ValType::parse(&reader.read_type()?))
+        .collect()
+}
+
+/// Skip over the storage type of a GC type's field, as long as it doesn't
+/// refer to any other type by index.
+fn skip_storage_type(reader: &mut wasmparser::BinaryReader) -> Result<()> {
+    match reader.read_u8()? as u8 {
+        // Numeric and vector types, and the packed `i8` and `i16` types.
+        0x7b..=0x7f | 0x77 | 0x78 => Ok(()),
+        // Nullable references to abstract heap types, such as `i31ref`.
+        0x6a..=0x73 => Ok(()),
+        // References to abstract heap types spelled out in full.
+        0x63 | 0x64 => match reader.read_u8()? as u8 {
+            0x6a..=0x73 => Ok(()),
+            _ => bail!("GC types referring to other types aren't supported"),
+        },
+        ty => bail!("invalid storage type {:#x}", ty),
+    }
 }
 
 impl Emit for ModuleTypes {
@@ -1,7 +1,7 @@
+use crate::error::{IndexError, IndexKind};
 use crate::map::IdHashMap;
 use crate::{DataId, ElementId, Function, FunctionId, GlobalId, Result};
 use crate::{LocalId, MemoryId, TableId, TypeId};
-use anyhow::bail;
 
 /// Maps from old indices in the original Wasm binary to `walrus` IDs.
 ///
@@ -29,7 +29,7 @@ pub struct IndicesToIds {
 }
 
 macro_rules! define_push_get {
-    ( $push:ident, $get:ident, $id_ty:ty, $member:ident ) => {
+    ( $push:ident, $get:ident, $id_ty:ty, $member:ident, $kind:ident ) => {
         impl IndicesToIds {
             /// Pushes a new local ID to map it to the next index internally
             pub(crate) fn $push(&mut self, id: $id_ty) -> u32 {
@@ -40,28 +40,29 @@ macro_rules! define_push_get {
             /// Gets the ID for a particular index.
             ///
             /// If the index did not exist in the original Wasm binary, an `Err`
-            /// is returned.
+            /// wrapping an `IndexError` is returned.
             pub fn $get(&self, index: u32) -> Result<$id_ty> {
                 match self.$member.get(index as usize) {
                     Some(x) => Ok(*x),
-                    None => bail!(
-                        "index `{}` is out of bounds for {}",
+                    None => Err(IndexError {
+                        kind: IndexKind::$kind,
                         index,
-                        stringify!($member)
-                    ),
+                        max: self.$member.len() as u32,
+                    }
+                    .into()),
                 }
             }
         }
     };
 }
 
-define_push_get!(push_table, get_table, TableId, tables);
-define_push_get!(push_type, get_type, TypeId, types);
-define_push_get!(push_func, get_func, FunctionId, funcs);
-define_push_get!(push_global, get_global, GlobalId, globals);
-define_push_get!(push_memory, get_memory, MemoryId, memories);
-define_push_get!(push_element, get_element, ElementId, elements);
-define_push_get!(push_data, get_data, DataId, data);
+define_push_get!(push_table, get_table, TableId, tables, Table);
+define_push_get!(push_type, get_type, TypeId, types, Type);
+define_push_get!(push_func, get_func, FunctionId, funcs, Function);
+define_push_get!(push_global, get_global, GlobalId, globals, Global);
+define_push_get!(push_memory, get_memory, MemoryId, memories, Memory);
+define_push_get!(push_element, get_element, ElementId, elements, Element);
+define_push_get!(push_data, get_data, DataId, data, Data);
 
 impl IndicesToIds {
     /// Pushes a new local ID to map it to the next index internally
@@ -72,14 +73,19 @@ impl IndicesToIds {
     }
 
     /// Gets the ID for a particular index
+    ///
+    /// If the index is not one of the function's parameters or declared
+    /// locals, an `Err` wrapping an `IndexError` is returned.
     pub fn get_local(&self, function: FunctionId, index: u32) -> Result<LocalId> {
-        let ret = self
-            .locals
-            .get(&function)
-            .and_then(|list| list.get(index as usize));
-        match ret {
+        let list = self.locals.get(&function).map_or(&[][..], |l| &l[..]);
+        match list.get(index as usize) {
             Some(x) => Ok(*x),
-            None => bail!("index `{}` is out of bounds for local", index,),
+            None => Err(IndexError {
+                kind: IndexKind::Local,
+                index,
+                max: list.len() as u32,
+            }
+            .into()),
         }
     }
 }
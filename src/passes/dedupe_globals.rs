@@ -0,0 +1,75 @@
+//! Merges identical immutable globals.
+
+use crate::ir::Value;
+use crate::map::IdHashMap;
+use crate::{ExportItem, FunctionId, GlobalId, GlobalKind, InitExpr, Module, ValType};
+use std::collections::HashMap;
+
+impl Module {
+    /// Merge immutable globals that have the same type and initializer into
+    /// a single global.
+    ///
+    /// Every use of a duplicate, including exports of it, is redirected to the
+    /// first such global with `remap_global`, and the duplicate is deleted.
+    /// Imported and mutable globals are never merged, since each one is a
+    /// distinct location.
+    pub fn dedupe_globals(&mut self) {
+        let ids = self.globals.iter().map(|g| g.id()).collect::<Vec<_>>();
+        let mut canonical = HashMap::new();
+        let mut merged = IdHashMap::default();
+        for id in ids {
+            let global = self.globals.get(id);
+            let init = match global.kind {
+                GlobalKind::Local(init) if !global.mutable => init,
+                _ => continue,
+            };
+            // The key is computed only now, so that initializers that refer to
+            // globals that were already merged are seen as equal.
+            let first = *canonical.entry((global.ty, Key::new(init))).or_insert(id);
+            if first != id {
+                self.remap_global(id, first)
+                    .expect("globals with the same type can be remapped");
+                merged.insert(id, first);
+            }
+        }
+
+        for export in self.exports.iter_mut() {
+            if let ExportItem::Global(global) = &mut export.item {
+                if let Some(first) = merged.get(global) {
+                    *global = *first;
+                }
+            }
+        }
+        for id in merged.keys() {
+            self.globals.delete(*id);
+        }
+    }
+}
+
+/// A global's initializer, comparing floats by their bits.
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    V128(u128),
+    Global(GlobalId),
+    RefNull(ValType),
+    RefFunc(FunctionId),
+}
+
+impl Key {
+    fn new(init: InitExpr) -> Key {
+        match init {
+            InitExpr::Value(Value::I32(i)) => Key::I32(i),
+            InitExpr::Value(Value::I64(i)) => Key::I64(i),
+            InitExpr::Value(Value::F32(f)) => Key::F32(f.to_bits()),
+            InitExpr::Value(Value::F64(f)) => Key::F64(f.to_bits()),
+            InitExpr::Value(Value::V128(v)) => Key::V128(v),
+            InitExpr::Global(global) => Key::Global(global),
+            InitExpr::RefNull(ty) => Key::RefNull(ty),
+            InitExpr::RefFunc(func) => Key::RefFunc(func),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Extracts a single function into a module of its own.
+
+use crate::ir::*;
+use crate::map::IdHashMap;
+use crate::tombstone_arena::Id;
+use crate::{
+    DataId, ElementId, Function, FunctionId, FunctionKind, Global, GlobalId, GlobalKind, ImportId,
+    Memory, MemoryId, Module, Result, Table, TableId, Type, TypeId,
+};
+use anyhow::bail;
+
+impl Module {
+    /// Create a new module containing just the local function `id`, exported
+    /// under its name, or `func{index}` if it doesn't have one.
+    ///
+    /// Everything else the function references is imported by the new module:
+    /// the functions it calls or takes references to, and the globals,
+    /// memories, and tables it uses. Items that were imported by this module
+    /// keep their import's module and name, while items defined by this
+    /// module are imported from `env` under their name, or their kind and
+    /// index, such as `global0`, if they don't have one. The new module only
+    /// contains the types that the function and its imports need.
+    ///
+    /// Returns an error if `id` is an imported function, or if the function
+    /// uses any data or element segments, which can't be imported.
+    pub fn extract_function(&self, id: FunctionId) -> Result<Module> {
+        let func = self.funcs.get(id);
+        let mut local = match &func.kind {
+            FunctionKind::Local(local) => local.clone(),
+            _ => bail!("can only extract local functions"),
+        };
+
+        let mut extract = Extract {
+            module: self,
+            new: Module::default(),
+            funcs: IdHashMap::default(),
+            globals: IdHashMap::default(),
+            memories: IdHashMap::default(),
+            tables: IdHashMap::default(),
+            types: IdHashMap::default(),
+            locals: IdHashMap::default(),
+            segment: None,
+        };
+
+        // Calls to the function itself are left alone until we know its id in
+        // the new module.
+        extract.funcs.insert(id, id);
+        for arg in local.args.iter_mut() {
+            extract.visit_local_id_mut(arg);
+        }
+        local.builder_mut().ty = extract.ty(func.ty());
+        for (_id, seq) in local.builder_mut().arena.iter_mut() {
+            if let InstrSeqType::MultiValue(ty) = &mut seq.ty {
+                *ty = extract.ty(*ty);
+            }
+            for (instr, _) in seq.instrs.iter_mut() {
+                instr.visit_mut(&mut extract);
+            }
+        }
+        if let Some(segment) = extract.segment {
+            bail!(
+                "can't extract a function that uses {}, since it can't be imported",
+                segment
+            );
+        }
+
+        let mut new = extract.new;
+        let new_id = new.funcs.add_local(local);
+        let mut rename = Rename {
+            from: id,
+            to: new_id,
+        };
+        let body = new.funcs.get_mut(new_id).kind.unwrap_local_mut();
+        for (_id, seq) in body.builder_mut().arena.iter_mut() {
+            for (instr, _) in seq.instrs.iter_mut() {
+                instr.visit_mut(&mut rename);
+            }
+        }
+        new.funcs.get_mut(new_id).name = func.name.clone();
+        new.exports
+            .add(&item_name(func.name.as_deref(), "func", id), new_id);
+        Ok(new)
+    }
+}
+
+/// Copies the items a function uses into a new module, and rewrites the
+/// function's ids to refer to them.
+///
+/// An instruction's ids can be visited more than once, so every id in the new
+/// module also maps to itself.
+struct Extract<'a> {
+    module: &'a Module,
+    new: Module,
+    funcs: IdHashMap<Function, FunctionId>,
+    globals: IdHashMap<Global, GlobalId>,
+    memories: IdHashMap<Memory, MemoryId>,
+    tables: IdHashMap<Table, TableId>,
+    types: IdHashMap<Type, TypeId>,
+    locals: IdHashMap<Local, LocalId>,
+    /// A data or element segment the function uses, if any.
+    segment: Option<&'static str>,
+}
+
+impl Extract<'_> {
+    fn ty(&mut self, ty: TypeId) -> TypeId {
+        if let Some(new) = self.types.get(&ty) {
+            return *new;
+        }
+        let old = self.module.types.get(ty);
+        let new = if old.is_for_function_entry() {
+            self.new.types.add_entry_ty(old.results())
+        } else {
+            self.new.types.add(old.params(), old.results())
+        };
+        self.types.insert(ty, new);
+        self.types.insert(new, new);
+        new
+    }
+
+    /// The module and name to import an item under.
+    fn import_name<T>(
+        &self,
+        import: Option<ImportId>,
+        name: Option<&str>,
+        kind: &str,
+        id: Id<T>,
+    ) -> (String, String) {
+        match import {
+            Some(import) => {
+                let import = self.module.imports.get(import);
+                (import.module.clone(), import.name.clone())
+            }
+            None => ("env".to_string(), item_name(name, kind, id)),
+        }
+    }
+}
+
+/// The name of an item, or its kind and index if it doesn't have one.
+fn item_name<T>(name: Option<&str>, kind: &str, id: Id<T>) -> String {
+    match name {
+        Some(name) => name.to_string(),
+        None => format!("{}{}", kind, id.index()),
+    }
+}
+
+impl VisitorMut for Extract<'_> {
+    fn visit_function_id_mut(&mut self, func: &mut FunctionId) {
+        if let Some(new) = self.funcs.get(func) {
+            *func = *new;
+            return;
+        }
+        let f = self.module.funcs.get(*func);
+        let import = match &f.kind {
+            FunctionKind::Import(import) => Some(import.import),
+            _ => None,
+        };
+        let (module, name) = self.import_name(import, f.name.as_deref(), "func", *func);
+        let ty = self.ty(f.ty());
+        let (new, _) = self.new.add_import_func(&module, &name, ty);
+        self.funcs.insert(*func, new);
+        self.funcs.insert(new, new);
+        *func = new;
+    }
+
+    fn visit_global_id_mut(&mut self, global: &mut GlobalId) {
+        if let Some(new) = self.globals.get(global) {
+            *global = *new;
+            return;
+        }
+        let g = self.module.globals.get(*global);
+        let import = match g.kind {
+            GlobalKind::Import(import) => Some(import),
+            GlobalKind::Local(_) => None,
+        };
+        let (module, name) = self.import_name(import, None, "global", *global);
+        let (new, _) = self.new.add_import_global(&module, &name, g.ty, g.mutable);
+        self.globals.insert(*global, new);
+        self.globals.insert(new, new);
+        *global = new;
+    }
+
+    fn visit_memory_id_mut(&mut self, memory: &mut MemoryId) {
+        if let Some(new) = self.memories.get(memory) {
+            *memory = *new;
+            return;
+        }
+        let m = self.module.memories.get(*memory);
+        let (module, name) = self.import_name(m.import, None, "memory", *memory);
+        let (new, _) = self
+            .new
+            .add_import_memory(&module, &name, m.shared, m.initial, m.maximum);
+        self.new.memories.get_mut(new).memory64 = m.memory64;
+        self.memories.insert(*memory, new);
+        self.memories.insert(new, new);
+        *memory = new;
+    }
+
+    fn visit_table_id_mut(&mut self, table: &mut TableId) {
+        if let Some(new) = self.tables.get(table) {
+            *table = *new;
+            return;
+        }
+        let t = self.module.tables.get(*table);
+        let (module, name) = self.import_name(t.import, None, "table", *table);
+        let (new, _) =
+            self.new
+                .add_import_table(&module, &name, t.initial, t.maximum, t.element_ty);
+        self.tables.insert(*table, new);
+        self.tables.insert(new, new);
+        *table = new;
+    }
+
+    fn visit_type_id_mut(&mut self, ty: &mut TypeId) {
+        *ty = self.ty(*ty);
+    }
+
+    fn visit_local_id_mut(&mut self, local: &mut LocalId) {
+        if let Some(new) = self.locals.get(local) {
+            *local = *new;
+            return;
+        }
+        let old = self.module.locals.get(*local);
+        let new = self.new.locals.add(old.ty());
+        self.new.locals.get_mut(new).name = old.name.clone();
+        self.locals.insert(*local, new);
+        self.locals.insert(new, new);
+        *local = new;
+    }
+
+    fn visit_data_id_mut(&mut self, _: &mut DataId) {
+        self.segment = Some("a data segment");
+    }
+
+    fn visit_element_id_mut(&mut self, _: &mut ElementId) {
+        self.segment = Some("an element segment");
+    }
+}
+
+/// Replaces calls and references to one function with another.
+struct Rename {
+    from: FunctionId,
+    to: FunctionId,
+}
+
+impl VisitorMut for Rename {
+    fn visit_function_id_mut(&mut self, func: &mut FunctionId) {
+        if *func == self.from {
+            *func = self.to;
+        }
+    }
+}
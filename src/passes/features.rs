@@ -0,0 +1,408 @@
+//! Finds which WebAssembly proposals a module uses.
+
+use crate::ir::*;
+use crate::{ElementKind, Module, ValType};
+
+/// A WebAssembly proposal that a module may use beyond the MVP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// The fixed-width SIMD proposal: the `v128` type and its instructions.
+    Simd,
+    /// The threads proposal: shared memories and atomic instructions.
+    Atomics,
+    /// The bulk memory operations proposal, e.g. `memory.copy` and passive
+    /// segments.
+    BulkMemory,
+    /// The reference types proposal: `externref`, table instructions, typed
+    /// `select`, and multiple tables.
+    ReferenceTypes,
+    /// The multi-value proposal: functions and blocks with multiple results,
+    /// or blocks with parameters.
+    MultiValue,
+    /// The sign-extension operators proposal, e.g. `i32.extend8_s`.
+    SignExtension,
+    /// The non-trapping float-to-int conversions proposal, e.g.
+    /// `i32.trunc_sat_f32_s`.
+    SaturatingFloatToInt,
+}
+
+/// Every `Feature`, in declaration order.
+const ALL_FEATURES: [Feature; 7] = [
+    Feature::Simd,
+    Feature::Atomics,
+    Feature::BulkMemory,
+    Feature::ReferenceTypes,
+    Feature::MultiValue,
+    Feature::SignExtension,
+    Feature::SaturatingFloatToInt,
+];
+
+/// A set of WebAssembly proposals, such as the ones a target engine supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FeatureSet {
+    bits: u8,
+}
+
+impl FeatureSet {
+    /// Only the features of the MVP.
+    pub fn mvp() -> FeatureSet {
+        FeatureSet { bits: 0 }
+    }
+
+    /// All of the features that `walrus` knows about.
+    pub fn all() -> FeatureSet {
+        let mut set = FeatureSet::mvp();
+        for feature in ALL_FEATURES.iter() {
+            set.insert(*feature);
+        }
+        set
+    }
+
+    /// Is `feature` in this set?
+    pub fn contains(&self, feature: Feature) -> bool {
+        self.bits & FeatureSet::bit(feature) != 0
+    }
+
+    /// Add `feature` to this set.
+    pub fn insert(&mut self, feature: Feature) {
+        self.bits |= FeatureSet::bit(feature);
+    }
+
+    /// Remove `feature` from this set.
+    pub fn remove(&mut self, feature: Feature) {
+        self.bits &= !FeatureSet::bit(feature);
+    }
+
+    fn bit(feature: Feature) -> u8 {
+        1 << feature as u8
+    }
+}
+
+impl Default for FeatureSet {
+    fn default() -> FeatureSet {
+        FeatureSet::all()
+    }
+}
+
+impl Module {
+    /// Does this module use `feature`?
+    ///
+    /// This looks at the types of functions, locals, globals, and tables, the
+    /// memories and segments, and every instruction of every local function.
+    /// It can be used to produce an accurate `target_features` section, or to
+    /// reject modules that a target engine does not support.
+    pub fn uses_feature(&self, feature: Feature) -> bool {
+        let uses_ty = |ty: ValType| match ty {
+            ValType::V128 => feature == Feature::Simd,
+            ValType::Externref => feature == Feature::ReferenceTypes,
+            _ => false,
+        };
+
+        let types = self
+            .types
+            .iter()
+            .filter(|ty| !ty.is_for_function_entry() && !ty.is_opaque());
+        for ty in types {
+            if feature == Feature::MultiValue && ty.results().len() > 1 {
+                return true;
+            }
+            if ty.params().iter().chain(ty.results()).any(|t| uses_ty(*t)) {
+                return true;
+            }
+        }
+        if self.locals.iter().any(|l| uses_ty(l.ty()))
+            || self.globals.iter().any(|g| uses_ty(g.ty))
+            || self.tables.iter().any(|t| uses_ty(t.element_ty))
+        {
+            return true;
+        }
+
+        let found = match feature {
+            Feature::Atomics => self.memories.iter().any(|m| m.shared),
+            Feature::BulkMemory => {
+                self.data.iter().any(|d| d.is_passive())
+                    || self
+                        .elements
+                        .iter()
+                        .any(|e| matches!(e.kind, ElementKind::Passive))
+            }
+            Feature::ReferenceTypes => {
+                self.tables.iter().count() > 1
+                    || self.elements.iter().any(|e| {
+                        matches!(e.kind, ElementKind::Declared) || e.members.contains(&None)
+                    })
+            }
+            _ => false,
+        };
+        if found {
+            return true;
+        }
+
+        for (_id, func) in self.funcs.iter_local() {
+            let entry = func.entry_block();
+            let multi_value_block = func
+                .builder()
+                .arena
+                .iter()
+                .any(|(id, seq)| id != entry && matches!(seq.ty, InstrSeqType::MultiValue(_)));
+            if feature == Feature::MultiValue && multi_value_block {
+                return true;
+            }
+            let uses = func.fold(false, |uses, instr| {
+                uses || instr_feature(instr) == Some(feature)
+            });
+            if uses {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// The proposal that `instr` is part of, if any.
+fn instr_feature(instr: &Instr) -> Option<Feature> {
+    Some(match instr {
+        Instr::Const(Const {
+            value: Value::V128(_),
+        })
+        | Instr::V128Bitselect(_)
+        | Instr::V128Swizzle(_)
+        | Instr::V128Shuffle(_)
+        | Instr::LoadSimd(_) => Feature::Simd,
+        Instr::Load(Load { kind, .. }) => match kind {
+            LoadKind::V128 => Feature::Simd,
+            _ if kind.atomic() => Feature::Atomics,
+            _ => return None,
+        },
+        Instr::Store(Store { kind, .. }) => match kind {
+            StoreKind::V128 => Feature::Simd,
+            _ if kind.atomic() => Feature::Atomics,
+            _ => return None,
+        },
+        Instr::AtomicRmw(_)
+        | Instr::Cmpxchg(_)
+        | Instr::AtomicNotify(_)
+        | Instr::AtomicWait(_)
+        | Instr::AtomicFence(_) => Feature::Atomics,
+        Instr::MemoryInit(_)
+        | Instr::DataDrop(_)
+        | Instr::MemoryCopy(_)
+        | Instr::MemoryFill(_)
+        | Instr::TableInit(_)
+        | Instr::ElemDrop(_)
+        | Instr::TableCopy(_) => Feature::BulkMemory,
+        Instr::TableGet(_)
+        | Instr::TableSet(_)
+        | Instr::TableGrow(_)
+        | Instr::TableSize(_)
+        | Instr::TableFill(_)
+        | Instr::RefNull(_)
+        | Instr::RefIsNull(_)
+        | Instr::RefFunc(_)
+        | Instr::Select(Select { ty: Some(_) }) => Feature::ReferenceTypes,
+        Instr::Binop(Binop { op }) => return binop_feature(*op),
+        Instr::Unop(Unop { op }) => return unop_feature(*op),
+        _ => return None,
+    })
+}
+
+fn binop_feature(op: BinaryOp) -> Option<Feature> {
+    use BinaryOp::*;
+    match op {
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge | I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And
+        | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Add | I64Sub
+        | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl
+        | I64ShrS | I64ShrU | I64Rotl | I64Rotr | F32Add | F32Sub | F32Mul | F32Div | F32Min
+        | F32Max | F32Copysign | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max
+        | F64Copysign => None,
+        I8x16ReplaceLane { .. }
+        | I16x8ReplaceLane { .. }
+        | I32x4ReplaceLane { .. }
+        | I64x2ReplaceLane { .. }
+        | F32x4ReplaceLane { .. }
+        | F64x2ReplaceLane { .. }
+        | I8x16Eq
+        | I8x16Ne
+        | I8x16LtS
+        | I8x16LtU
+        | I8x16GtS
+        | I8x16GtU
+        | I8x16LeS
+        | I8x16LeU
+        | I8x16GeS
+        | I8x16GeU
+        | I16x8Eq
+        | I16x8Ne
+        | I16x8LtS
+        | I16x8LtU
+        | I16x8GtS
+        | I16x8GtU
+        | I16x8LeS
+        | I16x8LeU
+        | I16x8GeS
+        | I16x8GeU
+        | I32x4Eq
+        | I32x4Ne
+        | I32x4LtS
+        | I32x4LtU
+        | I32x4GtS
+        | I32x4GtU
+        | I32x4LeS
+        | I32x4LeU
+        | I32x4GeS
+        | I32x4GeU
+        | F32x4Eq
+        | F32x4Ne
+        | F32x4Lt
+        | F32x4Gt
+        | F32x4Le
+        | F32x4Ge
+        | F64x2Eq
+        | F64x2Ne
+        | F64x2Lt
+        | F64x2Gt
+        | F64x2Le
+        | F64x2Ge
+        | V128And
+        | V128Or
+        | V128Xor
+        | V128AndNot
+        | I8x16Shl
+        | I8x16ShrS
+        | I8x16ShrU
+        | I8x16Add
+        | I8x16AddSaturateS
+        | I8x16AddSaturateU
+        | I8x16Sub
+        | I8x16SubSaturateS
+        | I8x16SubSaturateU
+        | I16x8Shl
+        | I16x8ShrS
+        | I16x8ShrU
+        | I16x8Add
+        | I16x8AddSaturateS
+        | I16x8AddSaturateU
+        | I16x8Sub
+        | I16x8SubSaturateS
+        | I16x8SubSaturateU
+        | I16x8Mul
+        | I32x4Shl
+        | I32x4ShrS
+        | I32x4ShrU
+        | I32x4Add
+        | I32x4Sub
+        | I32x4Mul
+        | I64x2Shl
+        | I64x2ShrS
+        | I64x2ShrU
+        | I64x2Add
+        | I64x2Sub
+        | I64x2Mul
+        | F32x4Add
+        | F32x4Sub
+        | F32x4Mul
+        | F32x4Div
+        | F32x4Min
+        | F32x4Max
+        | F64x2Add
+        | F64x2Sub
+        | F64x2Mul
+        | F64x2Div
+        | F64x2Min
+        | F64x2Max
+        | I8x16NarrowI16x8S
+        | I8x16NarrowI16x8U
+        | I16x8NarrowI32x4S
+        | I16x8NarrowI32x4U
+        | I8x16RoundingAverageU
+        | I16x8RoundingAverageU
+        | I8x16MinS
+        | I8x16MinU
+        | I8x16MaxS
+        | I8x16MaxU
+        | I16x8MinS
+        | I16x8MinU
+        | I16x8MaxS
+        | I16x8MaxU
+        | I32x4MinS
+        | I32x4MinU
+        | I32x4MaxS
+        | I32x4MaxU => Some(Feature::Simd),
+    }
+}
+
+fn unop_feature(op: UnaryOp) -> Option<Feature> {
+    use UnaryOp::*;
+    match op {
+        I32Eqz | I32Clz | I32Ctz | I32Popcnt | I64Eqz | I64Clz | I64Ctz | I64Popcnt | F32Abs
+        | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt | F64Abs | F64Neg
+        | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt | I32WrapI64 | I32TruncSF32
+        | I32TruncUF32 | I32TruncSF64 | I32TruncUF64 | I64ExtendSI32 | I64ExtendUI32
+        | I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64 | F32ConvertSI32
+        | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64 | F64ConvertSI32
+        | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32 | I32ReinterpretF32
+        | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => None,
+        I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => {
+            Some(Feature::SignExtension)
+        }
+        I32TruncSSatF32 | I32TruncUSatF32 | I32TruncSSatF64 | I32TruncUSatF64 | I64TruncSSatF32
+        | I64TruncUSatF32 | I64TruncSSatF64 | I64TruncUSatF64 => {
+            Some(Feature::SaturatingFloatToInt)
+        }
+        I8x16Splat
+        | I8x16ExtractLaneS { .. }
+        | I8x16ExtractLaneU { .. }
+        | I16x8Splat
+        | I16x8ExtractLaneS { .. }
+        | I16x8ExtractLaneU { .. }
+        | I32x4Splat
+        | I32x4ExtractLane { .. }
+        | I64x2Splat
+        | I64x2ExtractLane { .. }
+        | F32x4Splat
+        | F32x4ExtractLane { .. }
+        | F64x2Splat
+        | F64x2ExtractLane { .. }
+        | V128Not
+        | I8x16Abs
+        | I8x16Neg
+        | I8x16AnyTrue
+        | I8x16AllTrue
+        | I16x8Abs
+        | I16x8Neg
+        | I16x8AnyTrue
+        | I16x8AllTrue
+        | I32x4Abs
+        | I32x4Neg
+        | I32x4AnyTrue
+        | I32x4AllTrue
+        | I64x2Neg
+        | F32x4Abs
+        | F32x4Neg
+        | F32x4Sqrt
+        | F64x2Abs
+        | F64x2Neg
+        | F64x2Sqrt
+        | I32x4TruncSatF32x4S
+        | I32x4TruncSatF32x4U
+        | F32x4ConvertI32x4S
+        | F32x4ConvertI32x4U
+        | I32x4TruncSatF64x2SZero
+        | I32x4TruncSatF64x2UZero
+        | F64x2ConvertLowI32x4S
+        | F64x2ConvertLowI32x4U
+        | F32x4DemoteF64x2Zero
+        | F64x2PromoteLowF32x4
+        | I16x8WidenLowI8x16S
+        | I16x8WidenLowI8x16U
+        | I16x8WidenHighI8x16S
+        | I16x8WidenHighI8x16U
+        | I32x4WidenLowI16x8S
+        | I32x4WidenLowI16x8U
+        | I32x4WidenHighI16x8S
+        | I32x4WidenHighI16x8U => Some(Feature::Simd),
+    }
+}
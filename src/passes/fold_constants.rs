@@ -0,0 +1,85 @@
+//! Folds binary operations on constants.
+
+use crate::ir::*;
+use crate::Module;
+
+impl Module {
+    /// Replace integer and floating point arithmetic whose operands are both
+    /// pushed by the constant instructions right before it with a constant of
+    /// its result.
+    ///
+    /// Only operations that can't trap are folded: integer addition,
+    /// subtraction, multiplication, and bitwise operations, and floating
+    /// point addition, subtraction, multiplication, and division. Floating
+    /// point operations whose result is NaN are only folded if
+    /// `ModuleConfig::fold_float_constants` is enabled.
+    ///
+    /// Returns whether anything was folded.
+    pub fn fold_constants(&mut self) -> bool {
+        let fold_nans = self.config.fold_float_constants;
+        let mut changed = false;
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                let old = std::mem::take(&mut seq.instrs);
+                let mut new: Vec<(Instr, InstrLocId)> = Vec::with_capacity(old.len());
+                for (instr, loc) in old {
+                    if let Instr::Binop(Binop { op }) = instr {
+                        let n = new.len();
+                        if n >= 2 {
+                            if let (Instr::Const(a), Instr::Const(b)) =
+                                (&new[n - 2].0, &new[n - 1].0)
+                            {
+                                if let Some(value) = fold(op, a.value, b.value, fold_nans) {
+                                    new.truncate(n - 2);
+                                    new.push((Const { value }.into(), loc));
+                                    changed = true;
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+                    new.push((instr, loc));
+                }
+                seq.instrs = new;
+            }
+        }
+        changed
+    }
+}
+
+/// The result of `a <op> b`, if it can be folded.
+fn fold(op: BinaryOp, a: Value, b: Value, fold_nans: bool) -> Option<Value> {
+    use BinaryOp::*;
+    let value = match (op, a, b) {
+        (I32Add, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_add(b)),
+        (I32Sub, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_sub(b)),
+        (I32Mul, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_mul(b)),
+        (I32And, Value::I32(a), Value::I32(b)) => Value::I32(a & b),
+        (I32Or, Value::I32(a), Value::I32(b)) => Value::I32(a | b),
+        (I32Xor, Value::I32(a), Value::I32(b)) => Value::I32(a ^ b),
+        (I64Add, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_add(b)),
+        (I64Sub, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_sub(b)),
+        (I64Mul, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_mul(b)),
+        (I64And, Value::I64(a), Value::I64(b)) => Value::I64(a & b),
+        (I64Or, Value::I64(a), Value::I64(b)) => Value::I64(a | b),
+        (I64Xor, Value::I64(a), Value::I64(b)) => Value::I64(a ^ b),
+        (F32Add, Value::F32(a), Value::F32(b)) => Value::F32(a + b),
+        (F32Sub, Value::F32(a), Value::F32(b)) => Value::F32(a - b),
+        (F32Mul, Value::F32(a), Value::F32(b)) => Value::F32(a * b),
+        (F32Div, Value::F32(a), Value::F32(b)) => Value::F32(a / b),
+        (F64Add, Value::F64(a), Value::F64(b)) => Value::F64(a + b),
+        (F64Sub, Value::F64(a), Value::F64(b)) => Value::F64(a - b),
+        (F64Mul, Value::F64(a), Value::F64(b)) => Value::F64(a * b),
+        (F64Div, Value::F64(a), Value::F64(b)) => Value::F64(a / b),
+        _ => return None,
+    };
+    let nan = match value {
+        Value::F32(f) => f.is_nan(),
+        Value::F64(f) => f.is_nan(),
+        _ => false,
+    };
+    if nan && !fold_nans {
+        return None;
+    }
+    Some(value)
+}
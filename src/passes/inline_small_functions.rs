@@ -0,0 +1,231 @@
+//! Inlines calls to small functions.
+
+use crate::ir::*;
+use crate::map::{IdHashMap, IdHashSet};
+use crate::{Function, LocalFunction, Module, ModuleLocals, ModuleTypes, ValType};
+
+/// The most rounds of inlining to do. Inlining a function copies the calls it
+/// makes into its callers, to be inlined in the next round, so this bounds
+/// how much chains of small functions can grow their callers.
+const MAX_ROUNDS: usize = 16;
+
+impl Module {
+    /// Inline every direct call to a local function whose `size` is less than
+    /// `max_callee_size`, unless the function is recursive.
+    ///
+    /// Each call is replaced by stores of its arguments to fresh locals,
+    /// followed by a block holding a copy of the callee's body that produces
+    /// the call's results, so the block never takes parameters. The copy gets
+    /// fresh locals, with the callee's non-parameter locals explicitly zeroed
+    /// at the start. Calls to small functions that were copied into a caller
+    /// are inlined in turn, until there are none left or a fixed number of
+    /// rounds have been done.
+    ///
+    /// The callees themselves are left in place; run the `gc` pass to remove
+    /// any that are no longer used.
+    pub fn inline_small_functions(&mut self, max_callee_size: u64) {
+        let recursive = recursive_functions(self);
+        for _ in 0..MAX_ROUNDS {
+            let callees = self
+                .funcs
+                .iter_local()
+                .filter(|(id, func)| !recursive.contains(id) && func.size() < max_callee_size)
+                .map(|(id, func)| (id, func.clone()))
+                .collect::<IdHashMap<Function, _>>();
+
+            let mut inlined = false;
+            for (_id, caller) in self.funcs.iter_local_mut() {
+                let mut calls = Vec::new();
+                for (seq_id, seq) in caller.builder().arena.iter() {
+                    for (i, (instr, _)) in seq.instrs.iter().enumerate() {
+                        if let Instr::Call(Call { func }) = instr {
+                            if callees.contains_key(func) {
+                                calls.push((seq_id, i, *func));
+                            }
+                        }
+                    }
+                }
+
+                // Replace the calls last to first, so that the positions of
+                // the calls before each one in its sequence don't change.
+                for (seq, i, callee) in calls.into_iter().rev() {
+                    let instrs =
+                        inline(caller, &callees[&callee], &mut self.locals, &mut self.types);
+                    let seq = caller.block_mut(seq);
+                    let loc = seq.instrs[i].1;
+                    seq.instrs
+                        .splice(i..i + 1, instrs.into_iter().map(|instr| (instr, loc)));
+                    inlined = true;
+                }
+            }
+            if !inlined {
+                return;
+            }
+        }
+    }
+}
+
+/// Copy `callee`'s body into a new block in `caller`, returning the
+/// instructions to replace a call to it with.
+fn inline(
+    caller: &mut LocalFunction,
+    callee: &LocalFunction,
+    locals: &mut ModuleLocals,
+    types: &mut ModuleTypes,
+) -> Vec<Instr> {
+    let results = types.results(callee.ty()).to_vec();
+
+    let mut remap = Remap::default();
+    let mut callee_locals = callee
+        .used_locals()
+        .into_iter()
+        .filter(|l| !callee.args.contains(l))
+        .collect::<Vec<_>>();
+    callee_locals.sort();
+    for local in callee.args.iter().chain(callee_locals.iter()) {
+        let new = locals.add(locals.get(*local).ty());
+        remap.locals.insert(*local, new);
+        remap.locals.insert(new, new);
+    }
+
+    let entry = callee.entry_block();
+    let ty = InstrSeqType::new(types, &[], &results);
+    for (id, seq) in callee.builder().arena.iter() {
+        let ty = if id == entry { ty } else { seq.ty };
+        let new = caller.builder_mut().dangling_instr_seq(ty).id();
+        remap.seqs.insert(id, new);
+        remap.seqs.insert(new, new);
+    }
+    let block = remap.seqs[&entry];
+
+    for (id, seq) in callee.builder().arena.iter() {
+        let mut instrs = Vec::new();
+        if id == entry {
+            for local in callee_locals.iter() {
+                let ty = locals.get(*local).ty();
+                instrs.push((zero(ty), Default::default()));
+                let local = remap.locals[local];
+                instrs.push((LocalSet { local }.into(), Default::default()));
+            }
+        }
+        for (instr, loc) in seq.instrs.iter() {
+            let mut instr = match instr {
+                Instr::Return(_) => Br { block }.into(),
+                instr => instr.clone(),
+            };
+            remap.seq_ids(&mut instr);
+            instr.visit_mut(&mut remap);
+            instrs.push((instr, *loc));
+        }
+        caller.block_mut(remap.seqs[&id]).instrs = instrs;
+    }
+
+    // The arguments are on the stack in place of the call.
+    let mut instrs = callee
+        .args
+        .iter()
+        .rev()
+        .map(|arg| {
+            LocalSet {
+                local: remap.locals[arg],
+            }
+            .into()
+        })
+        .collect::<Vec<Instr>>();
+    instrs.push(Block { seq: block }.into());
+    instrs
+}
+
+/// The zero value of the given type, which locals start out with.
+fn zero(ty: ValType) -> Instr {
+    let value = match ty {
+        ValType::I32 => Value::I32(0),
+        ValType::I64 => Value::I64(0),
+        ValType::F32 => Value::F32(0.0),
+        ValType::F64 => Value::F64(0.0),
+        ValType::V128 => Value::V128(0),
+        ValType::Externref | ValType::Funcref => return RefNull { ty }.into(),
+    };
+    Const { value }.into()
+}
+
+/// Renames the locals and instruction sequences of a copied function body.
+///
+/// An instruction's locals can be visited more than once, and `return`s are
+/// already replaced by branches to the new block, so every new local and
+/// instruction sequence also maps to itself.
+#[derive(Default)]
+struct Remap {
+    locals: IdHashMap<Local, LocalId>,
+    seqs: IdHashMap<InstrSeq, InstrSeqId>,
+}
+
+impl Remap {
+    fn seq_ids(&self, instr: &mut Instr) {
+        let seq = |id: &mut InstrSeqId| *id = self.seqs[id];
+        match instr {
+            Instr::Block(Block { seq: id }) | Instr::Loop(Loop { seq: id }) => seq(id),
+            Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) => {
+                seq(consequent);
+                seq(alternative);
+            }
+            Instr::Br(Br { block }) | Instr::BrIf(BrIf { block }) => seq(block),
+            Instr::BrTable(BrTable { blocks, default }) => {
+                blocks.iter_mut().for_each(seq);
+                seq(default);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl VisitorMut for Remap {
+    fn visit_local_id_mut(&mut self, local: &mut LocalId) {
+        *local = self.locals[local];
+    }
+}
+
+/// Find the local functions that can call themselves, directly or through
+/// other functions.
+fn recursive_functions(module: &Module) -> IdHashSet<Function> {
+    let calls = module
+        .funcs
+        .iter_local()
+        .map(|(id, func)| (id, direct_callees(func)))
+        .collect::<IdHashMap<Function, _>>();
+
+    let mut recursive = IdHashSet::default();
+    for f in calls.keys() {
+        let mut seen = IdHashSet::default();
+        let mut stack = calls[f].iter().cloned().collect::<Vec<_>>();
+        while let Some(g) = stack.pop() {
+            if g == *f {
+                recursive.insert(*f);
+                break;
+            }
+            if seen.insert(g) {
+                if let Some(callees) = calls.get(&g) {
+                    stack.extend(callees.iter().cloned());
+                }
+            }
+        }
+    }
+    recursive
+}
+
+fn direct_callees(func: &LocalFunction) -> IdHashSet<Function> {
+    struct Callees(IdHashSet<Function>);
+
+    impl<'instr> Visitor<'instr> for Callees {
+        fn visit_call(&mut self, call: &Call) {
+            self.0.insert(call.func);
+        }
+    }
+
+    let mut callees = Callees(IdHashSet::default());
+    dfs_in_order(&mut callees, func, func.entry_block());
+    callees.0
+}
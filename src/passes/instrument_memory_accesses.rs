@@ -0,0 +1,102 @@
+//! Inserts calls to hooks before memory accesses.
+
+use crate::ir::*;
+use crate::{FunctionId, LocalId, Module, Result, ValType};
+use anyhow::bail;
+use std::collections::HashMap;
+
+impl Module {
+    /// Insert a call to `on_load` before every `load` instruction, and a call
+    /// to `on_store` before every `store` instruction, for instrumenting a
+    /// module's memory accesses, for example in a memory sanitizer.
+    ///
+    /// Both hooks must have the type `[i32 i32 i32] -> []`, and are passed the
+    /// address operand of the access, the access's constant offset, and the
+    /// number of bytes being accessed. The effective address is the sum of
+    /// the first two as a 33-bit number, which an `i32` can't always hold, so
+    /// the hooks get them separately. The offset is passed as an `i32` with
+    /// the same bits. The operands of the access are saved to new locals
+    /// around the call, which are shared by all the accesses in a function.
+    ///
+    /// The hooks' own bodies are not instrumented, so that they can access
+    /// memory without calling themselves.
+    pub fn instrument_memory_accesses(
+        &mut self,
+        on_load: FunctionId,
+        on_store: FunctionId,
+    ) -> Result<()> {
+        for hook in [on_load, on_store].iter() {
+            let ty = self.types.get(self.funcs.get(*hook).ty());
+            if ty.params() != [ValType::I32, ValType::I32, ValType::I32] || !ty.results().is_empty()
+            {
+                bail!(
+                    "memory access hook {:?} must have the type [i32 i32 i32] -> []",
+                    hook
+                );
+            }
+        }
+
+        let locals = &mut self.locals;
+        for (id, func) in self.funcs.iter_local_mut() {
+            if id == on_load || id == on_store {
+                continue;
+            }
+            let mut address = None;
+            let mut values: HashMap<ValType, LocalId> = HashMap::new();
+            let builder = func.builder_mut();
+            for (_id, seq) in builder.arena.iter_mut() {
+                if !seq.instrs.iter().any(|(i, _)| i.is_load() || i.is_store()) {
+                    continue;
+                }
+                let old = std::mem::take(&mut seq.instrs);
+                let mut new = Vec::with_capacity(old.len());
+                for (instr, loc) in old {
+                    let (hook, arg, width, value) = match &instr {
+                        Instr::Load(Load { kind, arg, .. }) => (on_load, *arg, kind.width(), None),
+                        Instr::Store(Store { kind, arg, .. }) => {
+                            let ty = stored_type(kind);
+                            let value = *values.entry(ty).or_insert_with(|| locals.add(ty));
+                            (on_store, *arg, kind.width(), Some(value))
+                        }
+                        _ => {
+                            new.push((instr, loc));
+                            continue;
+                        }
+                    };
+                    let address = *address.get_or_insert_with(|| locals.add(ValType::I32));
+
+                    if let Some(value) = value {
+                        new.push((LocalSet { local: value }.into(), loc));
+                    }
+                    new.push((LocalTee { local: address }.into(), loc));
+                    let offset = Value::I32(arg.offset as i32);
+                    new.push((Const { value: offset }.into(), loc));
+                    let width = Value::I32(width as i32);
+                    new.push((Const { value: width }.into(), loc));
+                    new.push((Call { func: hook }.into(), loc));
+                    new.push((LocalGet { local: address }.into(), loc));
+                    if let Some(value) = value {
+                        new.push((LocalGet { local: value }.into(), loc));
+                    }
+                    new.push((instr, loc));
+                }
+                seq.instrs = new;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The type of the value operand of a store of `kind`.
+fn stored_type(kind: &StoreKind) -> ValType {
+    match kind {
+        StoreKind::I32 { .. } | StoreKind::I32_8 { .. } | StoreKind::I32_16 { .. } => ValType::I32,
+        StoreKind::I64 { .. }
+        | StoreKind::I64_8 { .. }
+        | StoreKind::I64_16 { .. }
+        | StoreKind::I64_32 { .. } => ValType::I64,
+        StoreKind::F32 => ValType::F32,
+        StoreKind::F64 => ValType::F64,
+        StoreKind::V128 => ValType::V128,
+    }
+}
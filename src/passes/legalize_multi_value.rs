@@ -0,0 +1,287 @@
+//! Lowers multi-value blocks, loops, and ifs for engines without multi-value.
+
+use crate::ir::*;
+use crate::{LocalFunction, Module, ModuleLocals, ModuleTypes, ValType};
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+impl Module {
+    /// Rewrite blocks, loops, and ifs that use multi-value signatures so that
+    /// they only use MVP signatures, for engines that don't support
+    /// multi-value.
+    ///
+    /// Parameters of an instruction sequence are spilled to fresh locals
+    /// before entering it and reloaded at its start. Multiple results are
+    /// spilled to fresh locals at every exit of the sequence, including
+    /// branches to it, and reloaded after it.
+    ///
+    /// Functions that themselves return multiple values are left alone, and so
+    /// are sequences that a `br_table` targets alongside a label that keeps
+    /// its values on the stack, such as the body of such a function, since
+    /// every target of a `br_table` must take the same values.
+    pub fn legalize_multi_value(&mut self) {
+        let Module {
+            funcs,
+            locals,
+            types,
+            ..
+        } = self;
+        for (_id, func) in funcs.iter_local_mut() {
+            legalize_function(func, locals, types);
+        }
+    }
+}
+
+/// The locals used to pass values into and out of a lowered instruction
+/// sequence.
+#[derive(Clone)]
+struct Lowered {
+    /// The sequence's new, MVP type.
+    ty: InstrSeqType,
+    /// Locals holding the sequence's parameters.
+    params: Vec<LocalId>,
+    /// Locals holding the sequence's results, if it has more than one.
+    results: Option<Vec<LocalId>>,
+    /// Is this the body of a loop?
+    is_loop: bool,
+}
+
+impl Lowered {
+    /// The locals that branches to this sequence must store their values in,
+    /// if they need to do anything at all.
+    fn label_locals(&self) -> Option<&[LocalId]> {
+        if self.is_loop {
+            if self.params.is_empty() {
+                None
+            } else {
+                Some(&self.params)
+            }
+        } else {
+            self.results.as_deref()
+        }
+    }
+}
+
+fn legalize_function(func: &mut LocalFunction, locals: &mut ModuleLocals, types: &ModuleTypes) {
+    let entry = func.entry_block();
+    let arena = &mut func.builder_mut().arena;
+
+    let mut loops = HashSet::new();
+    let mut if_elses = HashMap::new();
+    for (_id, seq) in arena.iter() {
+        for (instr, _) in seq.instrs.iter() {
+            match instr {
+                Instr::Loop(Loop { seq }) => {
+                    loops.insert(*seq);
+                }
+                Instr::IfElse(IfElse {
+                    consequent,
+                    alternative,
+                }) => {
+                    if_elses.insert(*alternative, *consequent);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Allocate locals for every sequence with a multi-value signature.
+    let mut lowered = HashMap::new();
+    for (id, seq) in arena.iter() {
+        let ty = match seq.ty {
+            InstrSeqType::MultiValue(ty) if id != entry => types.get(ty),
+            _ => continue,
+        };
+        if if_elses.contains_key(&id) {
+            continue;
+        }
+        let (params, results) = (ty.params(), ty.results());
+        lowered.insert(
+            id,
+            Lowered {
+                ty: InstrSeqType::Simple(match results {
+                    [result] => Some(*result),
+                    _ => None,
+                }),
+                params: params.iter().map(|ty| locals.add(*ty)).collect(),
+                results: if results.len() > 1 {
+                    Some(results.iter().map(|ty| locals.add(*ty)).collect())
+                } else {
+                    None
+                },
+                is_loop: loops.contains(&id),
+            },
+        );
+    }
+
+    // Both arms of an `if` share the values on the stack when entering it and
+    // the values they leave behind, so they need to share locals too.
+    for (alternative, consequent) in if_elses.iter() {
+        if let Some(info) = lowered.get(consequent).cloned() {
+            lowered.insert(*alternative, info);
+        }
+    }
+
+    // A `br_table` can't pass values in locals to some of its targets and on
+    // the stack to others, so don't lower any of its targets unless they all
+    // take their values in locals. Un-lowering a target can make another
+    // `br_table` mixed in the same way, so repeat until nothing changes.
+    let tables = arena
+        .iter()
+        .flat_map(|(_id, seq)| seq.instrs.iter())
+        .filter_map(|(instr, _)| match instr {
+            Instr::BrTable(BrTable { blocks, default }) => {
+                Some(blocks.iter().chain(Some(default)).cloned().collect())
+            }
+            _ => None,
+        })
+        .collect::<Vec<Vec<_>>>();
+    loop {
+        let in_locals = |t: &InstrSeqId| lowered.get(t).and_then(|l| l.label_locals()).is_some();
+        let mixed = tables
+            .iter()
+            .filter(|targets| targets.iter().any(&in_locals) && !targets.iter().all(&in_locals))
+            .flat_map(|targets| targets.iter().filter(|t| in_locals(t)))
+            .cloned()
+            .collect::<Vec<_>>();
+        if mixed.is_empty() {
+            break;
+        }
+        for target in mixed {
+            lowered.remove(&target);
+            for (alternative, consequent) in if_elses.iter() {
+                if target == *alternative || target == *consequent {
+                    lowered.remove(alternative);
+                    lowered.remove(consequent);
+                }
+            }
+        }
+    }
+    if lowered.is_empty() {
+        return;
+    }
+
+    // Scratch locals for temporarily holding values, keyed by type and by
+    // position among the values being held.
+    let mut scratch = HashMap::new();
+    let mut scratch_local = |locals: &mut ModuleLocals, ty: ValType, i: usize| {
+        *scratch.entry((ty, i)).or_insert_with(|| locals.add(ty))
+    };
+
+    let ids = arena.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    for id in ids {
+        let seq = &mut arena[id];
+        let old = std::mem::take(&mut seq.instrs);
+        let mut new = Vec::with_capacity(old.len());
+        let this = lowered.get(&id);
+        if let Some(this) = this {
+            seq.ty = this.ty;
+            get_all(&mut new, &this.params, Default::default());
+        }
+
+        for (instr, loc) in old {
+            match &instr {
+                Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
+                    let nested = lowered.get(seq);
+                    if let Some(nested) = nested {
+                        set_all(&mut new, &nested.params, loc);
+                    }
+                    new.push((instr, loc));
+                    if let Some(results) = nested.and_then(|n| n.results.as_ref()) {
+                        get_all(&mut new, results, loc);
+                    }
+                }
+
+                Instr::IfElse(IfElse { consequent, .. }) => {
+                    let nested = lowered.get(consequent);
+                    match nested {
+                        Some(nested) if !nested.params.is_empty() => {
+                            let condition = scratch_local(locals, ValType::I32, 0);
+                            new.push((LocalSet { local: condition }.into(), loc));
+                            set_all(&mut new, &nested.params, loc);
+                            new.push((LocalGet { local: condition }.into(), loc));
+                        }
+                        _ => {}
+                    }
+                    new.push((instr, loc));
+                    if let Some(results) = nested.and_then(|n| n.results.as_ref()) {
+                        get_all(&mut new, results, loc);
+                    }
+                }
+
+                Instr::Br(Br { block }) => {
+                    if let Some(label) = lowered.get(block).and_then(|l| l.label_locals()) {
+                        set_all(&mut new, label, loc);
+                    }
+                    new.push((instr, loc));
+                }
+
+                Instr::BrIf(BrIf { block }) => {
+                    // Store the values for when the branch is taken, but also
+                    // leave them on the stack for when it isn't.
+                    if let Some(label) = lowered.get(block).and_then(|l| l.label_locals()) {
+                        let condition = scratch_local(locals, ValType::I32, 0);
+                        new.push((LocalSet { local: condition }.into(), loc));
+                        set_all(&mut new, label, loc);
+                        get_all(&mut new, label, loc);
+                        new.push((LocalGet { local: condition }.into(), loc));
+                    }
+                    new.push((instr, loc));
+                }
+
+                Instr::BrTable(BrTable { blocks, default }) => {
+                    let mut targets = blocks.iter().chain(Some(default)).collect::<Vec<_>>();
+                    targets.sort();
+                    targets.dedup();
+                    let labels = targets
+                        .into_iter()
+                        .filter_map(|t| lowered.get(t).and_then(|l| l.label_locals()))
+                        .collect::<Vec<_>>();
+                    if let Some(first) = labels.first() {
+                        // Every target takes the same types of values, and in
+                        // locals, so spill the values to scratch locals of
+                        // those types and then copy them to each target's
+                        // locals.
+                        let index = scratch_local(locals, ValType::I32, 0);
+                        new.push((LocalSet { local: index }.into(), loc));
+                        let mut values = Vec::with_capacity(first.len());
+                        for (i, local) in first.iter().enumerate() {
+                            let ty = locals.get(*local).ty();
+                            values.push(scratch_local(locals, ty, i + 1));
+                        }
+                        set_all(&mut new, &values, loc);
+                        for label in labels {
+                            for (value, local) in values.iter().zip(label) {
+                                new.push((LocalGet { local: *value }.into(), loc));
+                                new.push((LocalSet { local: *local }.into(), loc));
+                            }
+                        }
+                        new.push((LocalGet { local: index }.into(), loc));
+                    }
+                    new.push((instr, loc));
+                }
+
+                _ => new.push((instr, loc)),
+            }
+        }
+
+        if let Some(results) = this.and_then(|t| t.results.as_ref()) {
+            set_all(&mut new, results, Default::default());
+        }
+        arena[id].instrs = new;
+    }
+}
+
+/// Pop values off the stack into `locals`, the last local first.
+fn set_all(instrs: &mut Vec<(Instr, InstrLocId)>, locals: &[LocalId], loc: InstrLocId) {
+    for local in locals.iter().rev() {
+        instrs.push((LocalSet { local: *local }.into(), loc));
+    }
+}
+
+/// Push the values of `locals` onto the stack, the first local first.
+fn get_all(instrs: &mut Vec<(Instr, InstrLocId)>, locals: &[LocalId], loc: InstrLocId) {
+    for local in locals {
+        instrs.push((LocalGet { local: *local }.into(), loc));
+    }
+}
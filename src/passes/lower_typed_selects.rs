@@ -0,0 +1,100 @@
+//! Lowers typed `select` instructions to untyped ones where possible, or to
+//! `if`/`else` otherwise.
+
+use crate::ir::*;
+use crate::{LocalId, Module, ValType};
+use std::collections::HashMap;
+
+impl Module {
+    /// Rewrite typed `select` instructions over numeric types into untyped
+    /// `select` instructions, for engines that don't support the typed form.
+    ///
+    /// For `i32`, `i64`, `f32`, and `f64` operands the two forms are
+    /// equivalent. Selects over other types, such as reference types, must
+    /// remain typed and are left alone.
+    pub fn lower_typed_selects(&mut self) {
+        for (_id, func) in self.funcs.iter_local_mut() {
+            let entry = func.entry_block();
+            dfs_pre_order_mut(&mut LowerTypedSelects, func, entry);
+        }
+    }
+
+    /// Rewrite typed `select` instructions over reference types into
+    /// `if`/`else` instructions that push one of the two operands.
+    ///
+    /// Reference types can't be selected with an untyped `select`, so this is
+    /// how to run such code on engines that don't support the typed form. The
+    /// operands and condition are stored to new locals, which are shared by
+    /// all the selects of the same type in a function.
+    pub fn lower_reference_selects(&mut self) {
+        let locals = &mut self.locals;
+        for (_id, func) in self.funcs.iter_local_mut() {
+            let mut condition = None;
+            let mut operands: HashMap<ValType, (LocalId, LocalId)> = HashMap::new();
+            let builder = func.builder_mut();
+            let seqs = builder.arena.iter().map(|(id, _)| id).collect::<Vec<_>>();
+            for seq in seqs {
+                let instrs = &builder.arena[seq].instrs;
+                if !instrs.iter().any(|(i, _)| reference_select(i).is_some()) {
+                    continue;
+                }
+                let old = std::mem::take(&mut builder.arena[seq].instrs);
+                let mut new = Vec::with_capacity(old.len());
+                for (instr, loc) in old {
+                    let ty = match reference_select(&instr) {
+                        Some(ty) => ty,
+                        None => {
+                            new.push((instr, loc));
+                            continue;
+                        }
+                    };
+                    let cond = *condition.get_or_insert_with(|| locals.add(ValType::I32));
+                    let (a, b) = *operands
+                        .entry(ty)
+                        .or_insert_with(|| (locals.add(ty), locals.add(ty)));
+                    let consequent = builder.dangling_instr_seq(ty).local_get(a).id();
+                    let alternative = builder.dangling_instr_seq(ty).local_get(b).id();
+                    new.push((LocalSet { local: cond }.into(), loc));
+                    new.push((LocalSet { local: b }.into(), loc));
+                    new.push((LocalSet { local: a }.into(), loc));
+                    new.push((LocalGet { local: cond }.into(), loc));
+                    new.push((
+                        IfElse {
+                            consequent,
+                            alternative,
+                        }
+                        .into(),
+                        loc,
+                    ));
+                }
+                builder.arena[seq].instrs = new;
+            }
+        }
+    }
+}
+
+/// The type of `instr`, if it is a typed `select` over a reference type.
+fn reference_select(instr: &Instr) -> Option<ValType> {
+    match instr {
+        Instr::Select(Select {
+            ty: Some(ty @ ValType::Externref),
+        })
+        | Instr::Select(Select {
+            ty: Some(ty @ ValType::Funcref),
+        }) => Some(*ty),
+        _ => None,
+    }
+}
+
+struct LowerTypedSelects;
+
+impl VisitorMut for LowerTypedSelects {
+    fn visit_select_mut(&mut self, select: &mut Select) {
+        match select.ty {
+            Some(ValType::I32) | Some(ValType::I64) | Some(ValType::F32) | Some(ValType::F64) => {
+                select.ty = None;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Merges adjacent active data segments.
+
+use crate::ir::{dfs_in_order, Visitor};
+use crate::{ActiveDataLocation, DataId, DataKind, MemoryId, Module};
+use std::collections::HashSet;
+
+impl Module {
+    /// Merge active data segments that are adjacent in memory into a single
+    /// segment, to reduce the overhead of the data section.
+    ///
+    /// Segments with constant offsets are merged when one ends exactly where
+    /// the next begins, concatenating their bytes into the segment with the
+    /// lower offset. Passive segments, segments with `global.get` offsets, and
+    /// segments referenced by `memory.init` or `data.drop` instructions are
+    /// never merged.
+    ///
+    /// Segments are only merged when doing so can't change the contents of
+    /// memory after instantiation, that is when no other segment that is
+    /// initialized in between them could overlap with them. The result is
+    /// deterministic: it only depends on the module's segments.
+    pub fn merge_data_segments(&mut self) {
+        let referenced = self.referenced_data();
+
+        // Every segment, in the order they're initialized, along with the
+        // memory it's initialized in and the range it initializes, if known.
+        let segments = self
+            .data
+            .iter()
+            .filter_map(|data| match &data.kind {
+                DataKind::Active(active) => {
+                    let range = match active.location {
+                        ActiveDataLocation::Absolute(offset) => {
+                            let start = u64::from(offset);
+                            Some((start, start + data.value.len() as u64))
+                        }
+                        ActiveDataLocation::Relative(_) => None,
+                    };
+                    Some((data.id(), active.memory, range))
+                }
+                DataKind::Passive => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut candidates = segments
+            .iter()
+            .enumerate()
+            .filter_map(|(position, (id, memory, range))| {
+                let (start, end) = (*range)?;
+                if referenced.contains(id) {
+                    return None;
+                }
+                Some(Segment {
+                    id: *id,
+                    memory: *memory,
+                    start,
+                    end,
+                    first: position,
+                    last: position,
+                    merged: HashSet::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|s| (s.memory.index(), s.start, s.first));
+
+        let mut candidates = candidates.into_iter();
+        let mut run = match candidates.next() {
+            Some(segment) => segment,
+            None => return,
+        };
+        for next in candidates {
+            let mergeable = next.memory == run.memory
+                && next.start == run.end
+                && !segments[run.first.min(next.first)..=run.last.max(next.last)]
+                    .iter()
+                    .any(|(id, memory, range)| {
+                        *memory == run.memory
+                            && *id != run.id
+                            && *id != next.id
+                            && !run.merged.contains(id)
+                            && match range {
+                                Some((start, end)) => *start < next.end && run.start < *end,
+                                None => true,
+                            }
+                    });
+            if !mergeable {
+                run = next;
+                continue;
+            }
+
+            let value = std::mem::take(&mut self.data.get_mut(next.id).value);
+            self.data.get_mut(run.id).value.extend(value);
+            self.data.delete(next.id);
+            run.end = next.end;
+            run.first = run.first.min(next.first);
+            run.last = run.last.max(next.last);
+            run.merged.insert(next.id);
+        }
+    }
+
+    /// The data segments referenced by instructions in local functions.
+    fn referenced_data(&self) -> HashSet<DataId> {
+        struct Referenced(HashSet<DataId>);
+
+        impl<'instr> Visitor<'instr> for Referenced {
+            fn visit_data_id(&mut self, data: &DataId) {
+                self.0.insert(*data);
+            }
+        }
+
+        let mut referenced = Referenced(HashSet::new());
+        for (_id, func) in self.funcs.iter_local() {
+            dfs_in_order(&mut referenced, func, func.entry_block());
+        }
+        referenced.0
+    }
+}
+
+/// A run of adjacent segments that are being merged into the segment `id`.
+struct Segment {
+    id: DataId,
+    memory: MemoryId,
+    start: u64,
+    end: u64,
+    /// The initialization order of the earliest and latest segment in the run.
+    first: usize,
+    last: usize,
+    /// The segments that have been merged into `id` so far.
+    merged: HashSet<DataId>,
+}
@@ -1,6 +1,27 @@
 //! Passes over whole modules or individual functions.
 
+mod dedupe_globals;
+mod extract_function;
+mod features;
+mod fold_constants;
 pub mod gc;
+mod inline_small_functions;
+mod instrument_memory_accesses;
+mod legalize_multi_value;
+mod lower_typed_selects;
+mod merge_data_segments;
+mod polyfill;
+mod remap_global;
+mod remove_noops;
+mod remove_unused_pure_calls;
+mod shift_memory_accesses;
+mod simplify;
+mod simplify_shuffles;
+mod split_large_functions;
+mod unroll_loop;
 mod used;
 pub mod validate;
+pub use self::features::{Feature, FeatureSet};
+pub use self::simplify::SimplifyOptions;
+pub use self::unroll_loop::{InductionVariable, LoopInfo};
 pub use self::used::Roots;
@@ -0,0 +1,70 @@
+//! Replaces instructions that a target engine doesn't support with equivalent
+//! sequences of instructions that it does.
+
+use crate::ir::{BinaryOp, Binop, Const, Instr, InstrLocId, UnaryOp, Unop, Value};
+use crate::passes::{Feature, FeatureSet};
+use crate::Module;
+
+impl Module {
+    /// Replace instructions from proposals that are not in `features` with
+    /// equivalent sequences of instructions that are.
+    ///
+    /// Currently this lowers the sign-extension operators into a pair of
+    /// shifts. Instructions from other proposals are left alone.
+    pub fn polyfill(&mut self, features: FeatureSet) {
+        if features.contains(Feature::SignExtension) {
+            return;
+        }
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                if !seq.instrs.iter().any(|(i, _)| sign_extension(i).is_some()) {
+                    continue;
+                }
+                let instrs = std::mem::take(&mut seq.instrs);
+                for (instr, loc) in instrs {
+                    match sign_extension(&instr) {
+                        Some(lowering) => lower_sign_extension(&mut seq.instrs, lowering, loc),
+                        None => seq.instrs.push((instr, loc)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How to lower a sign-extension operator: the shift amount, and the shift
+/// operators to use.
+struct SignExtension {
+    shift: Value,
+    shl: BinaryOp,
+    shr_s: BinaryOp,
+}
+
+fn sign_extension(instr: &Instr) -> Option<SignExtension> {
+    let (shift, shl, shr_s) = match instr {
+        Instr::Unop(Unop { op }) => match op {
+            UnaryOp::I32Extend8S => (Value::I32(24), BinaryOp::I32Shl, BinaryOp::I32ShrS),
+            UnaryOp::I32Extend16S => (Value::I32(16), BinaryOp::I32Shl, BinaryOp::I32ShrS),
+            UnaryOp::I64Extend8S => (Value::I64(56), BinaryOp::I64Shl, BinaryOp::I64ShrS),
+            UnaryOp::I64Extend16S => (Value::I64(48), BinaryOp::I64Shl, BinaryOp::I64ShrS),
+            UnaryOp::I64Extend32S => (Value::I64(32), BinaryOp::I64Shl, BinaryOp::I64ShrS),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(SignExtension { shift, shl, shr_s })
+}
+
+/// Sign-extend the low bits of a value by shifting them to the top of the
+/// value, and then arithmetically shifting them back down again.
+fn lower_sign_extension(
+    instrs: &mut Vec<(Instr, InstrLocId)>,
+    lowering: SignExtension,
+    loc: InstrLocId,
+) {
+    let SignExtension { shift, shl, shr_s } = lowering;
+    instrs.push((Const { value: shift }.into(), loc));
+    instrs.push((Binop { op: shl }.into(), loc));
+    instrs.push((Const { value: shift }.into(), loc));
+    instrs.push((Binop { op: shr_s }.into(), loc));
+}
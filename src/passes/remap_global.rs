@@ -0,0 +1,75 @@
+//! Redirects uses of one global to another.
+
+use crate::ir::{dfs_pre_order_mut, VisitorMut};
+use crate::Result;
+use crate::{ActiveDataLocation, DataKind, ElementKind, GlobalId, GlobalKind, InitExpr, Module};
+use anyhow::bail;
+
+impl Module {
+    /// Rewrite every use of the global `from` to use `to` instead, for
+    /// coalescing globals.
+    ///
+    /// This rewrites `global.get` and `global.set` instructions, as well as the
+    /// initializers of other globals and the offsets of data and element
+    /// segments that are `global.get` expressions. Exports of `from` are left
+    /// alone, and `from` itself isn't deleted, so that the `gc` pass can remove it
+    /// once it's unused.
+    ///
+    /// Returns an error, without changing anything, if the globals have
+    /// different types or mutability.
+    pub fn remap_global(&mut self, from: GlobalId, to: GlobalId) -> Result<()> {
+        let (a, b) = (self.globals.get(from), self.globals.get(to));
+        if a.ty != b.ty || a.mutable != b.mutable {
+            bail!(
+                "can't remap a global of type {}{} to one of type {}{}",
+                if a.mutable { "mut " } else { "" },
+                a.ty,
+                if b.mutable { "mut " } else { "" },
+                b.ty
+            );
+        }
+
+        let mut remap = RemapGlobal { from, to };
+        for (_id, func) in self.funcs.iter_local_mut() {
+            let entry = func.entry_block();
+            dfs_pre_order_mut(&mut remap, func, entry);
+        }
+
+        let mut remap_init = |init: &mut InitExpr| {
+            if let InitExpr::Global(global) = init {
+                remap.visit_global_id_mut(global);
+            }
+        };
+        for global in self.globals.iter_mut() {
+            if let GlobalKind::Local(init) = &mut global.kind {
+                remap_init(init);
+            }
+        }
+        for elem in self.elements.iter_mut() {
+            if let ElementKind::Active { offset, .. } = &mut elem.kind {
+                remap_init(offset);
+            }
+        }
+        for data in self.data.iter_mut() {
+            if let DataKind::Active(active) = &mut data.kind {
+                if let ActiveDataLocation::Relative(global) = &mut active.location {
+                    remap.visit_global_id_mut(global);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct RemapGlobal {
+    from: GlobalId,
+    to: GlobalId,
+}
+
+impl VisitorMut for RemapGlobal {
+    fn visit_global_id_mut(&mut self, global: &mut GlobalId) {
+        if *global == self.from {
+            *global = self.to;
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Removes instruction sequences that have no effect.
+
+use crate::ir::*;
+use crate::Module;
+
+impl Module {
+    /// Remove instruction sequences that provably do nothing.
+    ///
+    /// These are an `i32.or` or `i32.mul` whose second operand is pushed by an
+    /// `i32.const 0` or `i32.const 1`, respectively, right before it, and a
+    /// `local.get` that is immediately dropped. Sequences exposed by a
+    /// removal, such as a `local.get` followed by `i32.const 0; i32.or;
+    /// drop`, are removed as well.
+    ///
+    /// `nop` instructions are already discarded when a module is parsed, so
+    /// there are none left for this pass to remove.
+    ///
+    /// Returns whether anything was removed.
+    pub fn remove_noops(&mut self) -> bool {
+        let mut changed = false;
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                let old = std::mem::take(&mut seq.instrs);
+                let mut new: Vec<(Instr, InstrLocId)> = Vec::with_capacity(old.len());
+                for (instr, loc) in old {
+                    let noop = match (new.last().map(|(i, _)| i), &instr) {
+                        (Some(Instr::Const(c)), Instr::Binop(Binop { op })) => matches!(
+                            (op, c.value),
+                            (BinaryOp::I32Or, Value::I32(0)) | (BinaryOp::I32Mul, Value::I32(1))
+                        ),
+                        (Some(Instr::LocalGet(_)), Instr::Drop(_)) => true,
+                        _ => false,
+                    };
+                    if noop {
+                        new.pop();
+                        changed = true;
+                        continue;
+                    }
+                    new.push((instr, loc));
+                }
+                seq.instrs = new;
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,109 @@
+//! Removes calls to pure functions whose results are all dropped.
+
+use crate::ir::*;
+use crate::map::{IdHashMap, IdHashSet};
+use crate::{Function, LocalFunction, Module};
+
+impl Module {
+    /// Remove calls to pure functions when every result of the call is
+    /// immediately dropped, such as the runs of `drop`s left behind by code
+    /// that ignores the results of a multi-value call.
+    ///
+    /// The call's arguments are dropped in its place. A function is
+    /// considered pure when it is a local function that can neither trap, loop
+    /// forever, nor have any side effects: besides its own locals, blocks,
+    /// ifs, and forward branches, it only uses instructions for which
+    /// `Instr::has_side_effects` is `false`, and calls to other pure
+    /// functions. Imported functions and recursive functions are never pure.
+    ///
+    /// Returns whether any calls were removed.
+    pub fn remove_unused_pure_calls(&mut self) -> bool {
+        let pure = pure_functions(self);
+        if pure.is_empty() {
+            return false;
+        }
+        let mut changed = false;
+
+        // The number of parameters and results of each pure function.
+        let counts = pure
+            .iter()
+            .map(|f| {
+                let (params, results) = self.types.params_results(self.funcs.get(*f).ty());
+                (*f, (params.len(), results.len()))
+            })
+            .collect::<IdHashMap<Function, _>>();
+
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                let old = std::mem::take(&mut seq.instrs);
+                let mut new = Vec::with_capacity(old.len());
+                let mut i = 0;
+                while i < old.len() {
+                    if let Instr::Call(Call { func }) = old[i].0 {
+                        if let Some(&(params, results)) = counts.get(&func) {
+                            let dropped = old[i + 1..]
+                                .iter()
+                                .take(results)
+                                .take_while(|(instr, _)| instr.is_drop())
+                                .count();
+                            if dropped == results {
+                                let loc = old[i].1;
+                                for _ in 0..params {
+                                    new.push((Drop {}.into(), loc));
+                                }
+                                i += 1 + results;
+                                changed = true;
+                                continue;
+                            }
+                        }
+                    }
+                    new.push(old[i].clone());
+                    i += 1;
+                }
+                seq.instrs = new;
+            }
+        }
+        changed
+    }
+}
+
+/// Find the pure local functions in `module`.
+fn pure_functions(module: &Module) -> IdHashSet<Function> {
+    let mut pure = IdHashSet::default();
+    loop {
+        let mut changed = false;
+        for (id, func) in module.funcs.iter_local() {
+            if !pure.contains(&id) && is_pure(func, &pure) {
+                pure.insert(id);
+                changed = true;
+            }
+        }
+        if !changed {
+            return pure;
+        }
+    }
+}
+
+/// Is `func` pure, assuming that exactly the functions in `pure` are?
+fn is_pure(func: &LocalFunction, pure: &IdHashSet<Function>) -> bool {
+    struct IsPure<'a> {
+        pure: &'a IdHashSet<Function>,
+        is_pure: bool,
+    }
+
+    impl<'instr> Visitor<'instr> for IsPure<'_> {
+        fn visit_instr(&mut self, instr: &'instr Instr, _: &'instr InstrLocId) {
+            self.is_pure &= match instr {
+                Instr::Call(Call { func }) => self.pure.contains(func),
+                _ => instr.is_pure_without_loops(),
+            };
+        }
+    }
+
+    let mut visitor = IsPure {
+        pure,
+        is_pure: true,
+    };
+    dfs_in_order(&mut visitor, func, func.entry_block());
+    visitor.is_pure
+}
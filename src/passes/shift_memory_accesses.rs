@@ -0,0 +1,108 @@
+//! Shifts the constant offsets of memory accesses.
+
+use crate::ir::*;
+use crate::{ActiveData, ActiveDataLocation, DataKind, MemoryId, Module, Result};
+use anyhow::bail;
+use std::convert::TryFrom;
+
+impl Module {
+    /// Add `delta` to the constant offset of every instruction that accesses
+    /// `memory`, and of every active data segment initialized in `memory` at
+    /// an absolute address, for relocating a module's data within its memory.
+    ///
+    /// Data segments whose address is a global's value are left unchanged.
+    ///
+    /// Returns an error, without changing anything, if any offset would be
+    /// shifted below zero or above `u32::MAX`.
+    pub fn shift_memory_accesses(&mut self, memory: MemoryId, delta: i64) -> Result<()> {
+        // Find the smallest and largest offsets first, so that we either shift
+        // every offset or none of them.
+        let mut range: Option<(u32, u32)> = None;
+        let mut include = |offset: u32| {
+            range = Some(match range {
+                Some((min, max)) => (min.min(offset), max.max(offset)),
+                None => (offset, offset),
+            });
+        };
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                for (instr, _) in seq.instrs.iter_mut() {
+                    if let Some(arg) = mem_arg(instr, memory) {
+                        include(arg.offset);
+                    }
+                }
+            }
+        }
+        for data in self.data.iter_mut() {
+            if let Some(offset) = data_offset(&mut data.kind, memory) {
+                include(*offset);
+            }
+        }
+        let (min, max) = match range {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+        for offset in [min, max].iter() {
+            shift(*offset, delta)?;
+        }
+
+        for (_id, func) in self.funcs.iter_local_mut() {
+            for (_id, seq) in func.builder_mut().arena.iter_mut() {
+                for (instr, _) in seq.instrs.iter_mut() {
+                    if let Some(arg) = mem_arg(instr, memory) {
+                        arg.offset = shift(arg.offset, delta)?;
+                    }
+                }
+            }
+        }
+        for data in self.data.iter_mut() {
+            if let Some(offset) = data_offset(&mut data.kind, memory) {
+                *offset = shift(*offset, delta)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn shift(offset: u32, delta: i64) -> Result<u32> {
+    match i64::from(offset)
+        .checked_add(delta)
+        .and_then(|shifted| u32::try_from(shifted).ok())
+    {
+        Some(shifted) => Ok(shifted),
+        None => bail!(
+            "shifting the memory offset {} by {} would move it out of range",
+            offset,
+            delta
+        ),
+    }
+}
+
+/// The memory argument of `instr`, if it accesses `memory`.
+fn mem_arg(instr: &mut Instr, memory: MemoryId) -> Option<&mut MemArg> {
+    match instr {
+        Instr::Load(Load { memory: m, arg, .. })
+        | Instr::Store(Store { memory: m, arg, .. })
+        | Instr::AtomicRmw(AtomicRmw { memory: m, arg, .. })
+        | Instr::Cmpxchg(Cmpxchg { memory: m, arg, .. })
+        | Instr::AtomicNotify(AtomicNotify { memory: m, arg, .. })
+        | Instr::AtomicWait(AtomicWait { memory: m, arg, .. })
+        | Instr::LoadSimd(LoadSimd { memory: m, arg, .. })
+            if *m == memory =>
+        {
+            Some(arg)
+        }
+        _ => None,
+    }
+}
+
+/// The absolute address of an active data segment initialized in `memory`.
+fn data_offset(kind: &mut DataKind, memory: MemoryId) -> Option<&mut u32> {
+    match kind {
+        DataKind::Active(ActiveData {
+            memory: m,
+            location: ActiveDataLocation::Absolute(offset),
+        }) if *m == memory => Some(offset),
+        _ => None,
+    }
+}
@@ -0,0 +1,78 @@
+//! Runs the peephole passes until they stop finding anything to simplify.
+
+use crate::Module;
+
+/// Which passes `Module::simplify_with` runs, and how many times at most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SimplifyOptions {
+    /// Run `Module::fold_constants`.
+    pub fold_constants: bool,
+    /// Run `Module::remove_noops`.
+    pub remove_noops: bool,
+    /// Run `LocalFunction::remove_dead_code` on every local function.
+    pub remove_dead_code: bool,
+    /// Run `Module::remove_unused_pure_calls`.
+    pub remove_unused_pure_calls: bool,
+    /// Run `Module::eliminate_identity_shuffles` and
+    /// `Module::compose_shuffles`.
+    pub simplify_shuffles: bool,
+    /// The most times to run the passes, in case they never settle.
+    pub max_iterations: u32,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> SimplifyOptions {
+        SimplifyOptions {
+            fold_constants: true,
+            remove_noops: true,
+            remove_dead_code: true,
+            remove_unused_pure_calls: true,
+            simplify_shuffles: true,
+            max_iterations: 16,
+        }
+    }
+}
+
+impl Module {
+    /// Run all of the peephole passes until none of them changes anything.
+    ///
+    /// See `simplify_with` for choosing which passes run.
+    pub fn simplify(&mut self) {
+        self.simplify_with(&SimplifyOptions::default());
+    }
+
+    /// Run the passes selected by `options` over and over, until a round of
+    /// them leaves every function unchanged or `options.max_iterations`
+    /// rounds have run, since one pass can expose more for another to
+    /// simplify.
+    ///
+    /// Returns the number of rounds that were run.
+    pub fn simplify_with(&mut self, options: &SimplifyOptions) -> u32 {
+        for round in 1..=options.max_iterations {
+            // Every pass runs each round, even once one has changed something.
+            let mut changed = false;
+            if options.remove_dead_code {
+                for (_id, func) in self.funcs.iter_local_mut() {
+                    changed |= func.remove_dead_code();
+                }
+            }
+            if options.fold_constants {
+                changed |= self.fold_constants();
+            }
+            if options.remove_noops {
+                changed |= self.remove_noops();
+            }
+            if options.remove_unused_pure_calls {
+                changed |= self.remove_unused_pure_calls();
+            }
+            if options.simplify_shuffles {
+                changed |= self.eliminate_identity_shuffles();
+                changed |= self.compose_shuffles();
+            }
+            if !changed {
+                return round;
+            }
+        }
+        options.max_iterations
+    }
+}
@@ -0,0 +1,127 @@
+//! Simplifies `i8x16.shuffle` instructions.
+
+use crate::ir::*;
+use crate::Module;
+
+impl Module {
+    /// Remove `i8x16.shuffle` instructions that return one of their operands
+    /// unchanged.
+    ///
+    /// When both operands are pushed by the `local.get`, `global.get`, or
+    /// constant instructions right before the shuffle, a shuffle with the
+    /// lanes `0..16` or `16..32` is removed along with the instruction pushing
+    /// the other operand. A shuffle of the same local or global twice is
+    /// likewise removed if each lane `i` selects lane `i` of either operand.
+    /// Otherwise a shuffle with the lanes `0..16` is replaced by a `drop` of
+    /// its second operand.
+    ///
+    /// Returns whether any shuffles were removed.
+    pub fn eliminate_identity_shuffles(&mut self) -> bool {
+        rewrite_shuffles(self, |new, indices| {
+            let n = new.len();
+            if n >= 2 && is_pure_push(&new[n - 2].0) && is_pure_push(&new[n - 1].0) {
+                let same_operands = match (&new[n - 2].0, &new[n - 1].0) {
+                    (Instr::LocalGet(a), Instr::LocalGet(b)) => a.local == b.local,
+                    (Instr::GlobalGet(a), Instr::GlobalGet(b)) => a.global == b.global,
+                    _ => false,
+                };
+                let first = indices.iter().enumerate().all(|(i, l)| *l as usize == i);
+                if first
+                    || same_operands
+                        && indices
+                            .iter()
+                            .enumerate()
+                            .all(|(i, l)| *l as usize % 16 == i)
+                {
+                    new.pop();
+                    return true;
+                }
+                if indices
+                    .iter()
+                    .enumerate()
+                    .all(|(i, l)| *l as usize == i + 16)
+                {
+                    new.remove(n - 2);
+                    return true;
+                }
+            }
+            if indices.iter().enumerate().all(|(i, l)| *l as usize == i) {
+                let loc = new.last().map(|(_, loc)| *loc).unwrap_or_default();
+                new.push((Drop {}.into(), loc));
+                return true;
+            }
+            false
+        })
+    }
+
+    /// Compose pairs of `i8x16.shuffle` instructions into a single shuffle
+    /// where possible.
+    ///
+    /// This applies when the result of one shuffle is the first operand of the
+    /// next, the next shuffle's second operand is pushed by a single
+    /// `local.get`, `global.get`, or constant, and the next shuffle only
+    /// selects lanes from its first operand. The second operand is removed and
+    /// the pair is replaced by one shuffle of the first shuffle's operands.
+    ///
+    /// Returns whether any shuffles were composed.
+    pub fn compose_shuffles(&mut self) -> bool {
+        rewrite_shuffles(self, |new, outer| {
+            let n = new.len();
+            if n < 2 || !is_pure_push(&new[n - 1].0) || outer.iter().any(|l| *l >= 16) {
+                return false;
+            }
+            let inner = match &mut new[n - 2].0 {
+                Instr::V128Shuffle(V128Shuffle { indices }) => indices,
+                _ => return false,
+            };
+            let mut composed = [0; 16];
+            for (lane, outer) in composed.iter_mut().zip(outer.iter()) {
+                *lane = inner[*outer as usize];
+            }
+            *inner = composed;
+            new.pop();
+            true
+        })
+    }
+}
+
+/// Rewrite every sequence in every local function, giving `rewrite` each
+/// shuffle's lane indices and the instructions before it. If `rewrite` returns
+/// `true` it has replaced the shuffle, otherwise the shuffle is kept.
+///
+/// Returns whether any shuffle was replaced.
+fn rewrite_shuffles(
+    module: &mut Module,
+    mut rewrite: impl FnMut(&mut Vec<(Instr, InstrLocId)>, &ShuffleIndices) -> bool,
+) -> bool {
+    let mut changed = false;
+    for (_id, func) in module.funcs.iter_local_mut() {
+        for (_id, seq) in func.builder_mut().arena.iter_mut() {
+            if !seq.instrs.iter().any(|(instr, _)| instr.is_v128_shuffle()) {
+                continue;
+            }
+            let old = std::mem::take(&mut seq.instrs);
+            let mut new = Vec::with_capacity(old.len());
+            for (instr, loc) in old {
+                if let Instr::V128Shuffle(V128Shuffle { indices }) = &instr {
+                    if rewrite(&mut new, indices) {
+                        changed = true;
+                        continue;
+                    }
+                }
+                new.push((instr, loc));
+            }
+            seq.instrs = new;
+        }
+    }
+    changed
+}
+
+/// Does this instruction push a single value without any side effects, so
+/// that it can be removed when the value isn't needed?
+fn is_pure_push(instr: &Instr) -> bool {
+    matches!(
+        instr,
+        Instr::LocalGet(..) | Instr::GlobalGet(..) | Instr::Const(..)
+    )
+}
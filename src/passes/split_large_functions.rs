@@ -0,0 +1,200 @@
+//! Splits functions that are too large into smaller ones.
+
+use crate::emit::IdsToIndices;
+use crate::ir::*;
+use crate::map::IdHashMap;
+use crate::{FunctionBuilder, FunctionId, LocalFunction, Module, ModuleLocals};
+
+impl Module {
+    /// Split every local function whose body is encoded in more than
+    /// `max_bytes` bytes, for platforms that reject large functions.
+    ///
+    /// A tail of the function's entry block is moved into a new helper
+    /// function, which the original function calls in its place and whose
+    /// results it returns. Every local used by the tail is passed to the
+    /// helper as a parameter. This is repeated for both functions until they
+    /// fit in `max_bytes`.
+    ///
+    /// Only straight-line tails are split off: the tail may not contain any
+    /// blocks, loops, ifs, or branches, and the stack must be empty where it
+    /// begins. Functions that can't be split this way, or not enough, are left
+    /// larger than `max_bytes`. Sizes are estimated with
+    /// `LocalFunction::encoded_len`.
+    pub fn split_large_functions(&mut self, max_bytes: usize) {
+        let mut indices = IdsToIndices::estimate(self);
+        let mut worklist = self
+            .funcs
+            .iter_local()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        worklist.reverse();
+
+        while let Some(id) = worklist.pop() {
+            let len = self.encoded_len(id, &indices);
+            if len <= max_bytes {
+                continue;
+            }
+            let helper = match split_tail(self, id) {
+                Some(helper) => helper,
+                None => continue,
+            };
+            indices.push_func(helper);
+
+            // Only keep splitting functions that got smaller, so that we can't
+            // get stuck splitting the same code back and forth.
+            for func in [id, helper].iter() {
+                if self.encoded_len(*func, &indices) < len {
+                    worklist.push(*func);
+                }
+            }
+        }
+    }
+
+    fn encoded_len(&self, func: FunctionId, indices: &IdsToIndices) -> usize {
+        self.funcs
+            .get(func)
+            .kind
+            .unwrap_local()
+            .encoded_len_with(self, indices)
+    }
+}
+
+/// Move a straight-line tail of `id`'s entry block into a new function, and
+/// call it instead. Returns the new function, if there was a tail to move.
+fn split_tail(module: &mut Module, id: FunctionId) -> Option<FunctionId> {
+    let func = module.funcs.get(id);
+    let at = split_point(func.kind.unwrap_local(), module)?;
+    let results = module.types.results(func.ty()).to_vec();
+    let name = func.name.as_ref().map(|name| format!("{}$split", name));
+
+    let func = module.funcs.get_mut(id).kind.unwrap_local_mut();
+    let entry = func.entry_block();
+    let mut tail = func.block_mut(entry).instrs.split_off(at);
+    let loc = tail[0].1;
+
+    // Give the helper a parameter for every local the tail uses.
+    let mut params = Params {
+        locals: &mut module.locals,
+        params: IdHashMap::default(),
+        used: Vec::new(),
+    };
+    for (instr, _) in tail.iter_mut() {
+        instr.visit_mut(&mut params);
+    }
+    let Params { params, used, .. } = params;
+    let args = used.iter().map(|local| params[local]).collect::<Vec<_>>();
+    let param_tys = args
+        .iter()
+        .map(|arg| module.locals.get(*arg).ty())
+        .collect::<Vec<_>>();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &param_tys, &results);
+    if let Some(name) = name {
+        builder.name(name);
+    }
+    *builder.func_body().instrs_mut() = tail;
+    let helper = builder.finish(args, &mut module.funcs);
+
+    let func = module.funcs.get_mut(id).kind.unwrap_local_mut();
+    let instrs = &mut func.block_mut(entry).instrs;
+    for local in used {
+        instrs.push((LocalGet { local }.into(), loc));
+    }
+    instrs.push((Call { func: helper }.into(), loc));
+    Some(helper)
+}
+
+/// Replaces locals with new ones, recording every local it replaces in the
+/// order they're first seen.
+///
+/// An instruction's locals can be visited more than once, so the new locals
+/// also map to themselves.
+struct Params<'a> {
+    locals: &'a mut ModuleLocals,
+    params: IdHashMap<Local, LocalId>,
+    used: Vec<LocalId>,
+}
+
+impl VisitorMut for Params<'_> {
+    fn visit_local_id_mut(&mut self, local: &mut LocalId) {
+        let Params {
+            locals,
+            params,
+            used,
+        } = self;
+        if let Some(param) = params.get(local) {
+            *local = *param;
+            return;
+        }
+        used.push(*local);
+        let param = locals.add(locals.get(*local).ty());
+        params.insert(*local, param);
+        params.insert(param, param);
+        *local = param;
+    }
+}
+
+/// Find where to split `func`'s entry block: a position where the stack is
+/// empty and after which there is no control flow, as close to the middle of
+/// the block as possible.
+fn split_point(func: &LocalFunction, module: &Module) -> Option<usize> {
+    let instrs = &func.block(func.entry_block()).instrs;
+    let straight = instrs
+        .iter()
+        .rposition(|(instr, _)| is_control(instr))
+        .map_or(0, |i| i + 1)
+        .max(1);
+
+    let mut best = None;
+    let mut height = 0u32;
+    for (i, (instr, _)) in instrs.iter().enumerate() {
+        if i >= straight && height == 0 {
+            let closer = match best {
+                Some(best) => distance(i, instrs.len()) < distance(best, instrs.len()),
+                None => true,
+            };
+            if closer {
+                best = Some(i);
+            }
+        }
+        if instr.following_instructions_are_unreachable() {
+            break;
+        }
+        let (pops, pushes) = match instr {
+            Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
+                let ty = func.block(*seq).ty;
+                (
+                    ty.params(&module.types).len() as u32,
+                    ty.results(&module.types).len() as u32,
+                )
+            }
+            Instr::IfElse(IfElse { consequent, .. }) => {
+                let ty = func.block(*consequent).ty;
+                (
+                    ty.params(&module.types).len() as u32 + 1,
+                    ty.results(&module.types).len() as u32,
+                )
+            }
+            _ => instr.stack_effect(module),
+        };
+        height = height.saturating_sub(pops) + pushes;
+    }
+    best
+}
+
+/// How far position `i` is from the middle of a block of `len` instructions.
+fn distance(i: usize, len: usize) -> usize {
+    (2 * i).max(len) - (2 * i).min(len)
+}
+
+fn is_control(instr: &Instr) -> bool {
+    matches!(
+        instr,
+        Instr::Block(..)
+            | Instr::Loop(..)
+            | Instr::IfElse(..)
+            | Instr::Br(..)
+            | Instr::BrIf(..)
+            | Instr::BrTable(..)
+    )
+}
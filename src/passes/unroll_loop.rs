@@ -0,0 +1,277 @@
+//! Unrolls simple counted loops.
+
+use crate::ir::*;
+use crate::LocalFunction;
+
+/// The most iterations we're willing to simulate to find a loop's trip count.
+const MAX_TRIP_COUNT: u32 = 1 << 20;
+
+impl LocalFunction {
+    /// Unroll the loop whose body is `seq` by duplicating its body `factor`
+    /// times, so that it branches back once for every `factor` iterations.
+    ///
+    /// Only simple counted loops are unrolled. The loop must take and return
+    /// no values, and be immediately preceded by the initialization of an
+    /// `i32` counter with a constant. Its body must end with the counter being
+    /// incremented by a constant, compared against a constant, and a `br_if`
+    /// back to the loop:
+    ///
+    /// ```text
+    /// i32.const 0
+    /// local.set $i
+    /// loop $l
+    ///   ;; ...
+    ///   local.get $i
+    ///   i32.const 1
+    ///   i32.add
+    ///   local.tee $i
+    ///   i32.const 10
+    ///   i32.lt_u
+    ///   br_if $l
+    /// end
+    /// ```
+    ///
+    /// The rest of the body must not contain any blocks, loops, ifs, or
+    /// branches, nor write to the counter, and the number of times the loop
+    /// runs must be a multiple of `factor`.
+    ///
+    /// Returns whether the loop was unrolled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is zero.
+    pub fn unroll_loop(&mut self, seq: InstrSeqId, factor: u32) -> bool {
+        assert!(factor > 0, "can't unroll a loop zero times");
+        let counted = match counted_loop(self, seq) {
+            Some(counted) => counted,
+            None => return false,
+        };
+        if counted.trip_count % factor != 0 {
+            return false;
+        }
+
+        let body = &self.block(seq).instrs;
+        let (work, tail) = body.split_at(counted.work);
+        let increment = &tail[..3];
+        let store = (
+            LocalSet {
+                local: counted.counter,
+            }
+            .into(),
+            tail[3].1,
+        );
+
+        let mut new = Vec::with_capacity(body.len() * factor as usize);
+        for _ in 1..factor {
+            new.extend(work.iter().cloned());
+            new.extend(increment.iter().cloned());
+            new.push(store.clone());
+        }
+        new.extend(body.iter().cloned());
+        self.block_mut(seq).instrs = new;
+        true
+    }
+
+    /// Find every loop in this function, along with its induction variable if
+    /// it is a counted loop.
+    ///
+    /// A loop is counted if its body ends with the pattern described on
+    /// `unroll_loop`, whatever the rest of its body does. Nothing is changed;
+    /// this is for deciding which loops to unroll or otherwise transform.
+    pub fn analyze_loops(&self) -> Vec<LoopInfo> {
+        let mut loops = Vec::new();
+        for (_, seq) in self.builder().arena.iter() {
+            for (instr, _) in seq.instrs.iter() {
+                if let Instr::Loop(Loop { seq }) = instr {
+                    loops.push(LoopInfo {
+                        seq: *seq,
+                        induction: induction_variable(self, *seq).map(|(induction, _)| induction),
+                    });
+                }
+            }
+        }
+        loops
+    }
+}
+
+/// What `LocalFunction::analyze_loops` found out about a loop.
+#[derive(Clone, Debug)]
+pub struct LoopInfo {
+    /// The loop's body.
+    pub seq: InstrSeqId,
+    /// The local counting the loop's iterations, if the loop is a counted loop
+    /// like the ones `unroll_loop` handles.
+    pub induction: Option<InductionVariable>,
+}
+
+/// The counter of a counted loop, which is incremented by a constant at the
+/// end of each iteration and compared against a constant to decide whether to
+/// loop again.
+#[derive(Clone, Debug)]
+pub struct InductionVariable {
+    /// The counter.
+    pub local: LocalId,
+    /// The constant the counter is set to right before the loop, if any.
+    pub init: Option<i32>,
+    /// How much the counter is incremented by in each iteration.
+    pub step: i32,
+    /// The comparison of the incremented counter (on the left) against
+    /// `bound` (on the right) that must hold to loop again.
+    pub comparison: BinaryOp,
+    /// The constant the counter is compared against.
+    pub bound: i32,
+    /// The number of times the loop's body runs, if `init` is known and the
+    /// loop ends after at most a million or so iterations.
+    pub trip_count: Option<u32>,
+}
+
+/// A loop matching the pattern that `unroll_loop` handles.
+struct CountedLoop {
+    /// The loop counter.
+    counter: LocalId,
+    /// The number of instructions in the body before the counter is
+    /// incremented.
+    work: usize,
+    /// The number of times the loop's body runs.
+    trip_count: u32,
+}
+
+fn counted_loop(func: &LocalFunction, seq: InstrSeqId) -> Option<CountedLoop> {
+    if func.block(seq).ty != InstrSeqType::Simple(None) {
+        return None;
+    }
+    let (induction, work) = induction_variable(func, seq)?;
+    let counter = induction.local;
+
+    let instrs = &func.block(seq).instrs;
+    let straight_line = instrs[..work].iter().all(|(instr, _)| match instr {
+        Instr::Block(..)
+        | Instr::Loop(..)
+        | Instr::IfElse(..)
+        | Instr::Br(..)
+        | Instr::BrIf(..)
+        | Instr::BrTable(..) => false,
+        Instr::LocalSet(LocalSet { local }) | Instr::LocalTee(LocalTee { local }) => {
+            *local != counter
+        }
+        _ => true,
+    });
+    if !straight_line {
+        return None;
+    }
+
+    Some(CountedLoop {
+        counter,
+        work,
+        trip_count: induction.trip_count?,
+    })
+}
+
+/// Match the tail of the loop body `seq` against the counted loop pattern
+/// described on `unroll_loop`, returning its induction variable and the
+/// number of instructions in the body before the variable is incremented.
+fn induction_variable(func: &LocalFunction, seq: InstrSeqId) -> Option<(InductionVariable, usize)> {
+    // The loop condition.
+    let instrs = func
+        .block(seq)
+        .instrs
+        .iter()
+        .map(|(instr, _)| instr)
+        .collect::<Vec<_>>();
+    let n = instrs.len();
+    if n < 7 {
+        return None;
+    }
+    let (bound, comparison) = match instrs[n - 3..] {
+        [Instr::Const(Const {
+            value: Value::I32(bound),
+        }), Instr::Binop(Binop { op }), Instr::BrIf(BrIf { block })]
+            if *block == seq =>
+        {
+            (*bound, *op)
+        }
+        _ => return None,
+    };
+
+    // The counter's new value, either stored with a `local.tee` or with a
+    // `local.set` followed by a `local.get`.
+    let (local, work) = match instrs[..n - 3] {
+        [.., Instr::LocalTee(LocalTee { local })] => (*local, n - 7),
+        [.., Instr::LocalSet(LocalSet { local }), Instr::LocalGet(LocalGet { local: reload })]
+            if local == reload && n >= 8 =>
+        {
+            (*local, n - 8)
+        }
+        _ => return None,
+    };
+
+    // The counter's increment.
+    let step = match instrs[work..work + 3] {
+        [Instr::LocalGet(LocalGet { local: l }), Instr::Const(Const {
+            value: Value::I32(step),
+        }), Instr::Binop(Binop {
+            op: BinaryOp::I32Add,
+        })] if *l == local => *step,
+        _ => return None,
+    };
+
+    // The counter may be set to a constant right before the loop.
+    let init = func.builder().arena.iter().find_map(|(_, parent)| {
+        let at = parent
+            .instrs
+            .iter()
+            .position(|(instr, _)| matches!(instr, Instr::Loop(Loop { seq: s }) if *s == seq))?;
+        match parent.instrs[..at] {
+            [.., (
+                Instr::Const(Const {
+                    value: Value::I32(init),
+                }),
+                _,
+            ), (Instr::LocalSet(LocalSet { local: l }), _)]
+                if l == local =>
+            {
+                Some(init)
+            }
+            _ => None,
+        }
+    });
+
+    let trip_count = init.and_then(|init| trip_count(init, step, bound, comparison));
+    let induction = InductionVariable {
+        local,
+        init,
+        step,
+        comparison,
+        bound,
+        trip_count,
+    };
+    Some((induction, work))
+}
+
+/// Count how many times a loop body runs when its counter starts at `init`,
+/// is incremented by `step` each time, and it loops again while
+/// `counter <op> bound` holds.
+fn trip_count(init: i32, step: i32, bound: i32, op: BinaryOp) -> Option<u32> {
+    let mut counter = init;
+    for trips in 1..=MAX_TRIP_COUNT {
+        counter = counter.wrapping_add(step);
+        let (a, b) = (counter, bound);
+        let again = match op {
+            BinaryOp::I32Eq => a == b,
+            BinaryOp::I32Ne => a != b,
+            BinaryOp::I32LtS => a < b,
+            BinaryOp::I32LtU => (a as u32) < (b as u32),
+            BinaryOp::I32GtS => a > b,
+            BinaryOp::I32GtU => (a as u32) > (b as u32),
+            BinaryOp::I32LeS => a <= b,
+            BinaryOp::I32LeU => (a as u32) <= (b as u32),
+            BinaryOp::I32GeS => a >= b,
+            BinaryOp::I32GeU => (a as u32) >= (b as u32),
+            _ => return None,
+        };
+        if !again {
+            return Some(trips);
+        }
+    }
+    None
+}
@@ -5,18 +5,172 @@
 
 use crate::ir::*;
 use crate::ValType;
-use crate::{ElementKind, Function, FunctionId, FunctionKind, InitExpr, Result};
-use crate::{Global, GlobalKind, Memory, MemoryId, Module, Table};
+use crate::{DataId, ElementId, ElementKind, Function, FunctionId, FunctionKind, InitExpr, Result};
+use crate::{Global, GlobalId, GlobalKind, Memory, MemoryId, Module, ModuleConfig, Table, TableId};
 use anyhow::{anyhow, bail, Context};
 use std::collections::HashSet;
+use std::fmt;
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+/// A problem found by `Module::validate_all`.
+#[derive(Debug)]
+pub struct ValidationError {
+    /// Where in the module the problem was found.
+    pub location: ValidationLocation,
+    /// What the problem is.
+    pub error: anyhow::Error,
+}
+
+/// The part of a module that a `ValidationError` is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationLocation {
+    /// The module as a whole, e.g. its number of memories, its exports, or
+    /// its start function.
+    Module,
+    /// A memory.
+    Memory(MemoryId),
+    /// A table.
+    Table(TableId),
+    /// A global.
+    Global(GlobalId),
+    /// An instruction in a local function.
+    Function(FunctionId),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            ValidationLocation::Module => write!(f, "{}", self.error),
+            ValidationLocation::Memory(id) => write!(f, "in memory {:?}: {}", id, self.error),
+            ValidationLocation::Table(id) => write!(f, "in table {:?}: {}", id, self.error),
+            ValidationLocation::Global(id) => write!(f, "in global {:?}: {}", id, self.error),
+            ValidationLocation::Function(id) => {
+                write!(f, "in function {:?}: {}", id, self.error)
+            }
+        }
+    }
+}
+
+impl Module {
+    /// Validate this module, returning every problem found rather than
+    /// stopping at the first one.
+    ///
+    /// Each memory, table, global, and local function is checked on its own,
+    /// so a problem in one does not hide problems in the others. This runs
+    /// the same checks as are run when a module is parsed, which can be
+    /// skipped with `ModuleConfig::strict_validate(false)`; instructions that
+    /// fail to parse are still reported when parsing, see `validate_buffer`
+    /// for reporting all of them.
+    pub fn validate_all(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let mut check = |location, result: Result<()>| {
+            if let Err(error) = result {
+                errors.push(ValidationError { location, error });
+            }
+        };
+
+        check(ValidationLocation::Module, validate_stable_features(self));
+        check(ValidationLocation::Module, validate_config_limits(self));
+        for memory in self.memories.iter() {
+            check(
+                ValidationLocation::Memory(memory.id()),
+                validate_memory(memory),
+            );
+        }
+        for table in self.tables.iter() {
+            check(ValidationLocation::Table(table.id()), validate_table(table));
+        }
+        let defined_funcs = declared_funcs(self);
+        for global in self.globals.iter() {
+            check(
+                ValidationLocation::Global(global.id()),
+                validate_global(self, global, &defined_funcs),
+            );
+        }
+        check(ValidationLocation::Module, validate_exports(self));
+        check(ValidationLocation::Module, validate_start(self));
+
+        for (id, errs) in validate_functions(self, &defined_funcs) {
+            for error in errs {
+                check(ValidationLocation::Function(id), Err(error));
+            }
+        }
+        errors
+    }
+
+    /// Parse and validate the wasm module in `wasm`, returning every problem
+    /// found, like `validate_all`.
+    ///
+    /// Unlike parsing the module and then calling `validate_all`, this also
+    /// reports every function whose body fails to parse, such as those with
+    /// operands of the wrong type, rather than stopping at the first one.
+    /// Those functions are given a body that is just `unreachable` in the
+    /// returned module, which the errors' locations refer to. Problems
+    /// elsewhere that stop the module from being parsed at all are still
+    /// returned as an error.
+    pub fn validate_buffer(wasm: &[u8]) -> Result<(Module, Vec<ValidationError>)> {
+        let mut config = ModuleConfig::new();
+        config.strict_validate(false);
+        let mut function_errors = Vec::new();
+        let (module, _) = Module::parse_sections(
+            wasm,
+            &config,
+            &mut Vec::new(),
+            false,
+            Some(&mut function_errors),
+        )?;
+        let mut errors = function_errors
+            .into_iter()
+            .map(|(id, error)| ValidationError {
+                location: ValidationLocation::Function(id),
+                error,
+            })
+            .collect::<Vec<_>>();
+        errors.extend(module.validate_all());
+        Ok((module, errors))
+    }
+}
+
 /// Validate a wasm module, returning an error if it fails to validate.
 pub fn run(module: &Module) -> Result<()> {
     log::debug!("validating module");
 
+    validate_stable_features(module)?;
+    validate_config_limits(module)?;
+
+    for memory in module.memories.iter() {
+        validate_memory(memory)?;
+    }
+    for table in module.tables.iter() {
+        validate_table(table)?;
+    }
+    let defined_funcs = declared_funcs(module);
+    for global in module.globals.iter() {
+        validate_global(module, global, &defined_funcs)?;
+    }
+    validate_exports(module)?;
+    validate_start(module)?;
+
+    // Validate each function in the module, collecting errors and returning
+    // them all at once if there are any.
+    let errs = validate_functions(module, &defined_funcs);
+    if errs.is_empty() {
+        return Ok(());
+    }
+
+    let mut msg = format!("errors validating module:\n");
+    for error in errs.into_iter().flat_map(|(_, v)| v) {
+        msg.push_str(&format!("  * {}\n", error));
+        for cause in error.chain() {
+            msg.push_str(&format!("    * {}\n", cause));
+        }
+    }
+    bail!("{}", msg)
+}
+
+fn validate_stable_features(module: &Module) -> Result<()> {
     if module.config.only_stable_features {
         if module.tables.iter().count() > 1 {
             bail!("multiple tables not allowed in the wasm spec yet");
@@ -25,25 +179,23 @@ pub fn run(module: &Module) -> Result<()> {
             bail!("multiple memories not allowed in the wasm spec yet");
         }
     }
+    Ok(())
+}
 
-    for memory in module.memories.iter() {
-        validate_memory(memory)?;
-    }
-    for table in module.tables.iter() {
-        validate_table(table)?;
-    }
+/// The functions declared in element segments, which may be referenced by
+/// `ref.func`.
+fn declared_funcs(module: &Module) -> HashSet<FunctionId> {
     let mut defined_funcs = HashSet::new();
     for element in module.elements.iter() {
         if let ElementKind::Declared = element.kind {
             defined_funcs.extend(element.members.iter().cloned().filter_map(|x| x));
         }
     }
-    for global in module.globals.iter() {
-        validate_global(module, global, &defined_funcs)?;
-    }
-    validate_exports(module)?;
+    defined_funcs
+}
 
-    // Validate the start function, if present, has the correct signature
+/// Validate the start function, if present, has the correct signature
+fn validate_start(module: &Module) -> Result<()> {
     if let Some(start) = module.start {
         let ty = module.funcs.get(start).ty();
         let ty = module.types.get(ty);
@@ -51,56 +203,101 @@ pub fn run(module: &Module) -> Result<()> {
             bail!("start function must take no arguments and return nothing");
         }
     }
+    Ok(())
+}
 
-    // Validate each function in the module, collecting errors and returning
-    // them all at once if there are any.
+/// Validate the instructions of each local function, returning the errors
+/// found in each function that has any.
+fn validate_functions(
+    module: &Module,
+    defined_funcs: &HashSet<FunctionId>,
+) -> Vec<(FunctionId, Vec<anyhow::Error>)> {
+    let data = module.data.iter().map(|d| d.id()).collect::<HashSet<_>>();
+    let elements = module
+        .elements
+        .iter()
+        .map(|e| e.id())
+        .collect::<HashSet<_>>();
     let funcs = &module.funcs;
     let errs = maybe_parallel!(funcs.(iter | par_iter))
         .map(|function| {
             let mut errs = Vec::new();
             let local = match &function.kind {
                 FunctionKind::Local(local) => local,
-                _ => return Vec::new(),
+                _ => return (function.id(), Vec::new()),
             };
             let mut cx = Validate {
                 errs: &mut errs,
                 function,
                 module,
-                defined_funcs: &defined_funcs,
+                defined_funcs,
+                data: &data,
+                elements: &elements,
+                dropped: Vec::new(),
             };
             dfs_in_order(&mut cx, local, local.entry_block());
-            errs
+            (function.id(), errs)
         })
         .collect::<Vec<_>>();
-    if errs.iter().all(|e| e.is_empty()) {
-        return Ok(());
-    }
+    errs.into_iter().filter(|(_, e)| !e.is_empty()).collect()
+}
 
-    let mut msg = format!("errors validating module:\n");
-    for error in errs.into_iter().flat_map(|v| v) {
-        msg.push_str(&format!("  * {}\n", error));
-        for cause in error.chain() {
-            msg.push_str(&format!("    * {}\n", cause));
+/// Validate the limits on memories and tables set in the module's config.
+fn validate_config_limits(module: &Module) -> Result<()> {
+    let config = &module.config;
+    if let Some(max) = config.max_memories {
+        let count = module.memories.iter().count();
+        if count > max {
+            bail!(
+                "module has {} memories, but at most {} are allowed",
+                count,
+                max
+            );
         }
     }
-    bail!("{}", msg)
+    if let Some(max) = config.max_tables {
+        let count = module.tables.iter().count();
+        if count > max {
+            bail!(
+                "module has {} tables, but at most {} are allowed",
+                count,
+                max
+            );
+        }
+    }
+    if let Some(max) = config.max_memory_pages {
+        for memory in module.memories.iter() {
+            if memory.initial > u64::from(max) {
+                bail!(
+                    "memory has an initial size of {} pages, but at most {} are allowed",
+                    memory.initial,
+                    max
+                );
+            }
+        }
+    }
+    Ok(())
 }
 
 fn validate_memory(m: &Memory) -> Result<()> {
     if m.shared && m.maximum.is_none() {
         bail!("shared memories must have a maximum size");
     }
-    validate_limits(m.initial, m.maximum, u32::from(u16::max_value()) + 1)
-        .context("when validating a memory")?;
+    validate_limits(m.initial, m.maximum, m.max_pages()).context("when validating a memory")?;
     Ok(())
 }
 
 fn validate_table(t: &Table) -> Result<()> {
-    validate_limits(t.initial, t.maximum, u32::max_value()).context("when validating a table")?;
+    validate_limits(
+        u64::from(t.initial),
+        t.maximum.map(u64::from),
+        u64::from(u32::MAX),
+    )
+    .context("when validating a table")?;
     Ok(())
 }
 
-fn validate_limits(initial: u32, maximum: Option<u32>, k: u32) -> Result<()> {
+fn validate_limits(initial: u64, maximum: Option<u64>, k: u64) -> Result<()> {
     match (initial, maximum) {
         (min, Some(max)) if max < min || max > k => {
             bail!("invalid limits: min = {}, max = {}; k = {}", min, max, k)
@@ -183,6 +380,11 @@ struct Validate<'a> {
     function: &'a Function,
     module: &'a Module,
     defined_funcs: &'a HashSet<FunctionId>,
+    data: &'a HashSet<DataId>,
+    elements: &'a HashSet<ElementId>,
+    /// For each instruction sequence we're currently in, the segments that
+    /// have been dropped so far in that sequence.
+    dropped: Vec<(HashSet<DataId>, HashSet<ElementId>)>,
 }
 
 impl Validate<'_> {
@@ -209,6 +411,31 @@ impl Validate<'_> {
         }
     }
 
+    fn require_data(&mut self, data: DataId, instr: &str) {
+        if !self.data.contains(&data) {
+            self.err(&format!(
+                "{} references a data segment that does not exist",
+                instr
+            ));
+        }
+    }
+
+    fn require_element(&mut self, elem: ElementId, instr: &str) {
+        if !self.elements.contains(&elem) {
+            self.err(&format!(
+                "{} references an element segment that does not exist",
+                instr
+            ));
+        }
+    }
+
+    fn warn(&self, msg: &str) {
+        match &self.function.name {
+            Some(name) => log::warn!("{} in function {}", msg, name),
+            None => log::warn!("{}", msg),
+        }
+    }
+
     fn err(&mut self, msg: &str) {
         let mut err = anyhow!("{}", msg);
         if let Some(name) = &self.function.name {
@@ -219,6 +446,38 @@ impl Validate<'_> {
 }
 
 impl<'a> Visitor<'a> for Validate<'a> {
+    fn start_instr_seq(&mut self, _: &'a InstrSeq) {
+        self.dropped.push(Default::default());
+    }
+
+    fn end_instr_seq(&mut self, _: &'a InstrSeq) {
+        self.dropped.pop();
+    }
+
+    fn visit_memory_init(&mut self, e: &MemoryInit) {
+        self.require_data(e.data, "memory.init");
+        if self.dropped.last().unwrap().0.contains(&e.data) {
+            self.warn("memory.init of a data segment that was already dropped");
+        }
+    }
+
+    fn visit_data_drop(&mut self, e: &DataDrop) {
+        self.require_data(e.data, "data.drop");
+        self.dropped.last_mut().unwrap().0.insert(e.data);
+    }
+
+    fn visit_table_init(&mut self, e: &TableInit) {
+        self.require_element(e.elem, "table.init");
+        if self.dropped.last().unwrap().1.contains(&e.elem) {
+            self.warn("table.init of an element segment that was already dropped");
+        }
+    }
+
+    fn visit_elem_drop(&mut self, e: &ElemDrop) {
+        self.require_element(e.elem, "elem.drop");
+        self.dropped.last_mut().unwrap().1.insert(e.elem);
+    }
+
     fn visit_load(&mut self, e: &Load) {
         if e.kind.atomic() {
             self.require_atomic(e.memory, &e.arg, e.kind.width());
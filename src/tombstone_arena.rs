@@ -11,7 +11,7 @@ pub use id_arena::Id;
 
 /// A wrapper around an `id_arena::Arena` that adds a tombstone set for deleting
 /// items.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TombstoneArena<T> {
     inner: InnerArena<T>,
     dead: IdHashSet<T>,
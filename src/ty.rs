@@ -9,6 +9,7 @@ use id_arena::Id;
 use std::cmp::Ordering;
 use std::fmt;
 use std::hash;
+use std::str::FromStr;
 
 /// An identifier for types.
 pub type TypeId = Id<Type>;
@@ -25,6 +26,10 @@ pub struct Type {
     // serialize the Type section.
     is_for_function_entry: bool,
 
+    // The encoding of a GC type that walrus doesn't understand, if this is
+    // one, which is emitted as-is.
+    opaque: Option<Box<[u8]>>,
+
     /// An optional name for debugging.
     ///
     /// This is not really used by anything currently, but a theoretical WAT to
@@ -39,6 +44,7 @@ impl PartialEq for Type {
         self.params == rhs.params
             && self.results == rhs.results
             && self.is_for_function_entry == rhs.is_for_function_entry
+            && self.opaque == rhs.opaque
     }
 }
 
@@ -55,6 +61,7 @@ impl Ord for Type {
         self.params()
             .cmp(rhs.params())
             .then_with(|| self.results().cmp(rhs.results()))
+            .then_with(|| self.opaque.cmp(&rhs.opaque))
     }
 }
 
@@ -65,6 +72,7 @@ impl hash::Hash for Type {
         self.params.hash(h);
         self.results.hash(h);
         self.is_for_function_entry.hash(h);
+        self.opaque.hash(h);
     }
 }
 
@@ -72,6 +80,7 @@ impl Tombstone for Type {
     fn on_delete(&mut self) {
         self.params = Box::new([]);
         self.results = Box::new([]);
+        self.opaque = None;
     }
 }
 
@@ -84,6 +93,7 @@ impl Type {
             params,
             results,
             is_for_function_entry: false,
+            opaque: None,
             name: None,
         }
     }
@@ -97,6 +107,20 @@ impl Type {
             params,
             results,
             is_for_function_entry: true,
+            opaque: None,
+            name: None,
+        }
+    }
+
+    /// Construct a new opaque type from its encoding.
+    #[inline]
+    pub(crate) fn opaque(id: TypeId, encoding: Box<[u8]>) -> Type {
+        Type {
+            id,
+            params: Box::new([]),
+            results: Box::new([]),
+            is_for_function_entry: false,
+            opaque: Some(encoding),
             name: None,
         }
     }
@@ -122,11 +146,25 @@ impl Type {
     pub(crate) fn is_for_function_entry(&self) -> bool {
         self.is_for_function_entry
     }
+
+    /// Is this a GC `struct` or `array` type that was preserved without being
+    /// understood, because `ModuleConfig::gc_passthrough` is enabled?
+    ///
+    /// Opaque types have no parameters or results, and are emitted exactly as
+    /// they were parsed.
+    #[inline]
+    pub fn is_opaque(&self) -> bool {
+        self.opaque.is_some()
+    }
 }
 
 impl Emit for Type {
     fn emit(&self, cx: &mut EmitContext) {
         assert!(!self.is_for_function_entry());
+        if let Some(encoding) = &self.opaque {
+            cx.encoder.raw(encoding);
+            return;
+        }
         cx.encoder.byte(0x60);
         cx.list(self.params.iter());
         cx.list(self.results.iter());
@@ -174,6 +212,14 @@ impl ValType {
         }
     }
 
+    /// Is this type a subtype of `other`?
+    ///
+    /// The reference types proposal has no subtyping between `funcref` and
+    /// `externref`, so for now this is the same as equality.
+    pub fn is_subtype_of(&self, other: ValType) -> bool {
+        *self == other
+    }
+
     pub(crate) fn emit(&self, encoder: &mut Encoder) {
         match self {
             ValType::I32 => encoder.byte(0x7f),
@@ -205,6 +251,25 @@ impl fmt::Display for ValType {
     }
 }
 
+impl FromStr for ValType {
+    type Err = anyhow::Error;
+
+    /// Parse a value type from its text format mnemonic, such as `i32` or
+    /// `funcref`, as printed by `ValType`'s `Display` implementation.
+    fn from_str(s: &str) -> Result<ValType> {
+        Ok(match s {
+            "i32" => ValType::I32,
+            "i64" => ValType::I64,
+            "f32" => ValType::F32,
+            "f64" => ValType::F64,
+            "v128" => ValType::V128,
+            "externref" => ValType::Externref,
+            "funcref" => ValType::Funcref,
+            _ => bail!("unknown value type `{}`", s),
+        })
+    }
+}
+
 impl Emit for ValType {
     fn emit(&self, cx: &mut EmitContext) {
         self.emit(&mut cx.encoder);